@@ -3,6 +3,8 @@ use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::fs;
 use crate::avc::AVCAlert;
+use crate::file_contexts::{apply_equivalences, FcEquivalence, Mislabel};
+use crate::ports::PortRange;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Advice {
@@ -23,6 +25,65 @@ pub struct AutoRecommendation {
     pub action_value: Option<String>,
 }
 
+/// Порт службы и тип, который отводит ему политика.
+pub struct PortAdviceEntry {
+    pub port: u16,
+    pub protocols: &'static [&'static str],
+    pub context: &'static str,
+    pub service: &'static str,
+    pub risk: &'static str,
+    /// Стандартный порт службы: переназначать его на другой тип опасно
+    pub well_known: bool,
+}
+
+const TCP: &[&str] = &["tcp"];
+const UDP: &[&str] = &["udp"];
+const TCP_UDP: &[&str] = &["tcp", "udp"];
+
+/// Известные порты служб
+pub const PORT_TABLE: &[PortAdviceEntry] = &[
+    PortAdviceEntry { port: 20, protocols: TCP, context: "ftp_data_port_t", service: "FTP data", risk: "Medium", well_known: true },
+    PortAdviceEntry { port: 21, protocols: TCP, context: "ftp_port_t", service: "FTP", risk: "Medium", well_known: true },
+    PortAdviceEntry { port: 22, protocols: TCP, context: "ssh_port_t", service: "SSH", risk: "Low", well_known: true },
+    PortAdviceEntry { port: 25, protocols: TCP, context: "smtp_port_t", service: "SMTP", risk: "Medium", well_known: true },
+    PortAdviceEntry { port: 53, protocols: TCP_UDP, context: "dns_port_t", service: "DNS", risk: "Medium", well_known: true },
+    PortAdviceEntry { port: 67, protocols: UDP, context: "dhcpd_port_t", service: "DHCP", risk: "Medium", well_known: true },
+    PortAdviceEntry { port: 80, protocols: TCP, context: "http_port_t", service: "HTTP", risk: "Low", well_known: true },
+    PortAdviceEntry { port: 88, protocols: TCP_UDP, context: "kerberos_port_t", service: "Kerberos", risk: "Medium", well_known: true },
+    PortAdviceEntry { port: 110, protocols: TCP, context: "pop_port_t", service: "POP3", risk: "Medium", well_known: true },
+    PortAdviceEntry { port: 111, protocols: TCP_UDP, context: "portmap_port_t", service: "rpcbind", risk: "Medium", well_known: true },
+    PortAdviceEntry { port: 123, protocols: UDP, context: "ntp_port_t", service: "NTP", risk: "Low", well_known: true },
+    PortAdviceEntry { port: 143, protocols: TCP, context: "pop_port_t", service: "IMAP", risk: "Medium", well_known: true },
+    PortAdviceEntry { port: 161, protocols: TCP_UDP, context: "snmp_port_t", service: "SNMP", risk: "Medium", well_known: true },
+    PortAdviceEntry { port: 389, protocols: TCP_UDP, context: "ldap_port_t", service: "LDAP", risk: "Medium", well_known: true },
+    PortAdviceEntry { port: 443, protocols: TCP, context: "http_port_t", service: "HTTPS", risk: "Low", well_known: true },
+    PortAdviceEntry { port: 445, protocols: TCP, context: "smbd_port_t", service: "SMB", risk: "Medium", well_known: true },
+    PortAdviceEntry { port: 514, protocols: UDP, context: "syslogd_port_t", service: "syslog", risk: "Low", well_known: true },
+    PortAdviceEntry { port: 587, protocols: TCP, context: "smtp_port_t", service: "SMTP submission", risk: "Medium", well_known: true },
+    PortAdviceEntry { port: 631, protocols: TCP_UDP, context: "ipp_port_t", service: "CUPS", risk: "Low", well_known: true },
+    PortAdviceEntry { port: 636, protocols: TCP_UDP, context: "ldap_port_t", service: "LDAPS", risk: "Medium", well_known: true },
+    PortAdviceEntry { port: 993, protocols: TCP, context: "pop_port_t", service: "IMAPS", risk: "Medium", well_known: true },
+    PortAdviceEntry { port: 2049, protocols: TCP_UDP, context: "nfs_port_t", service: "NFS", risk: "Medium", well_known: true },
+    PortAdviceEntry { port: 3306, protocols: TCP, context: "mysqld_port_t", service: "MySQL", risk: "Medium", well_known: true },
+    PortAdviceEntry { port: 3389, protocols: TCP, context: "xrdp_port_t", service: "RDP", risk: "Medium", well_known: false },
+    PortAdviceEntry { port: 5432, protocols: TCP, context: "postgresql_port_t", service: "PostgreSQL", risk: "Medium", well_known: true },
+    PortAdviceEntry { port: 5672, protocols: TCP, context: "amqp_port_t", service: "AMQP", risk: "Medium", well_known: false },
+    PortAdviceEntry { port: 6379, protocols: TCP, context: "redis_port_t", service: "Redis", risk: "Medium", well_known: false },
+    PortAdviceEntry { port: 8080, protocols: TCP, context: "http_cache_port_t", service: "HTTP proxy", risk: "Low", well_known: false },
+    PortAdviceEntry { port: 8443, protocols: TCP, context: "http_port_t", service: "HTTPS (alt)", risk: "Low", well_known: false },
+    PortAdviceEntry { port: 9090, protocols: TCP, context: "websm_port_t", service: "Cockpit", risk: "Low", well_known: false },
+    PortAdviceEntry { port: 11211, protocols: TCP_UDP, context: "memcache_port_t", service: "memcached", risk: "Medium", well_known: false },
+    PortAdviceEntry { port: 27017, protocols: TCP, context: "mongod_port_t", service: "MongoDB", risk: "Medium", well_known: false },
+];
+
+/// Стандартные порты в диапазоне, которые получат чужой тип
+pub fn well_known_reassignments(range: &PortRange, protocol: &str, context: &str) -> Vec<&'static PortAdviceEntry> {
+    PORT_TABLE
+        .iter()
+        .filter(|e| e.well_known && range.contains(e.port) && e.protocols.contains(&protocol) && e.context != context)
+        .collect()
+}
+
 pub struct Advisor {
     pub knowledge_base: HashMap<String, Advice>,
 }
@@ -52,55 +113,25 @@ impl Advisor {
         self.knowledge_base.get(key)
     }
 
-    /// Получает рекомендации для портов
+    /// Получает рекомендации для портов (порт или диапазон — по первому известному порту в нем)
     pub fn get_port_advice(&self, port: &str, protocol: &str) -> Option<Advice> {
-        // Стандартные порты и их рекомендуемые контексты
-        let port_num: u16 = port.parse().ok()?;
-        let (context, risk, suggestion) = match (port_num, protocol.to_lowercase().as_str()) {
-            (80, "tcp") | (443, "tcp") => (
-                "http_port_t",
-                "Low",
-                "Стандартные порты для HTTP/HTTPS. Используйте http_port_t."
-            ),
-            (22, "tcp") => (
-                "ssh_port_t",
-                "Low",
-                "Стандартный порт SSH. Используйте ssh_port_t."
-            ),
-            (25, "tcp") => (
-                "smtp_port_t",
-                "Medium",
-                "Порт SMTP. Используйте smtp_port_t."
-            ),
-            (53, "tcp") | (53, "udp") => (
-                "dns_port_t",
-                "Medium",
-                "Порт DNS. Используйте dns_port_t."
-            ),
-            (3306, "tcp") => (
-                "mysqld_port_t",
-                "Medium",
-                "Порт MySQL. Используйте mysqld_port_t."
-            ),
-            (5432, "tcp") => (
-                "postgresql_port_t",
-                "Medium",
-                "Порт PostgreSQL. Используйте postgresql_port_t."
-            ),
-            (8080, "tcp") | (8443, "tcp") => (
-                "http_port_t",
-                "Low",
-                "Альтернативные порты для веб-серверов. Используйте http_port_t."
-            ),
-            _ => return None,
+        let range: PortRange = port.parse().ok()?;
+        let protocol = protocol.to_lowercase();
+        let entry = PORT_TABLE
+            .iter()
+            .find(|e| range.contains(e.port) && e.protocols.contains(&protocol.as_str()))?;
+        let suggestion = if entry.well_known {
+            format!("Стандартный порт {}. Используйте {}.", entry.service, entry.context)
+        } else {
+            format!("Порт {}. Используйте {}.", entry.service, entry.context)
         };
 
         Some(Advice {
             key: format!("port_{}_{}", port, protocol),
              title: format!("Рекомендация для порта {}/{}", port, protocol),
-             description: format!("Рекомендуемый контекст: {}", context),
-             risk: risk.to_string(),
-             suggestion: suggestion.to_string(),
+             description: format!("Рекомендуемый контекст: {}", entry.context),
+             risk: entry.risk.to_string(),
+             suggestion,
         })
     }
 
@@ -168,8 +199,11 @@ impl Advisor {
         })
     }
 
-    /// Возвращает предложенные контексты для пути (используется в UI)
-    pub fn get_suggested_file_contexts(&self, path_part: &str) -> Vec<String> {
+    /// Возвращает предложенные контексты для пути (используется в UI).
+    /// Путь под эквивалентностью оценивается по пути, который ему соответствует.
+    pub fn get_suggested_file_contexts(&self, path_part: &str, equivalences: &[FcEquivalence]) -> Vec<String> {
+        let substituted = apply_equivalences(equivalences, path_part);
+        let path_part = substituted.as_deref().unwrap_or(path_part);
         // Простая эвристика на основе пути
         let mut suggestions = Vec::new();
         if path_part.contains("www") || path_part.contains("html") {
@@ -190,11 +224,31 @@ impl Advisor {
         suggestions
     }
 
-    /// Анализирует список AVC алертов и возвращает рекомендации
-    pub fn analyze_avc_alerts(&self, alerts: &[AVCAlert]) -> Vec<AutoRecommendation> {
+    /// Анализирует список AVC алертов и возвращает рекомендации.
+    /// Для файлов с неверной меткой (`mislabels`) предлагается restorecon вместо
+    /// allow-правила, и такие рекомендации идут первыми.
+    pub fn analyze_avc_alerts(&self, alerts: &[AVCAlert], mislabels: &[Mislabel]) -> Vec<AutoRecommendation> {
+        let mut relabels: Vec<AutoRecommendation> = Vec::new();
         let mut recommendations = Vec::new();
 
         for alert in alerts {
+            if let Some(m) = mislabels.iter().find(|m| m.path == alert.path) {
+                if !relabels.iter().any(|r| r.action_key == m.path) {
+                    relabels.push(AutoRecommendation {
+                        title: "Файл с неверной меткой".to_string(),
+                        description: format!(
+                            "{} помечен как {}, а политика ожидает {}. Выполните restorecon -v {} вместо добавления правил.",
+                            m.path, m.current, m.expected, m.path
+                        ),
+                        risk: "Low".to_string(),
+                        action_type: "restorecon".to_string(),
+                        action_key: m.path.clone(),
+                        action_value: Some(m.expected.clone()),
+                    });
+                }
+                continue;
+            }
+
             if let Some(advice) = self.get_avc_advice(alert) {
                 recommendations.push(AutoRecommendation {
                     title: advice.title.clone(),
@@ -237,7 +291,8 @@ impl Advisor {
             }
         }
 
-        recommendations
+        relabels.extend(recommendations);
+        relabels
     }
 
     /// Получает рекомендацию для конкретного AVC алерта
@@ -1,7 +1,12 @@
 use serde::{Deserialize, Serialize};
-use std::process::Command;
+use std::collections::HashMap;
 use regex::Regex;
 use anyhow::Result;
+use lazy_static::lazy_static;
+use crate::avc_query::AVCQuery;
+use crate::avc_source::{extract_messages, journal_units, AVCSource};
+use crate::correlation::{unit_from_cgroup, Correlator, ProcessOrigin};
+use crate::severity_rules::{context_type, SeverityRules};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AVCAlert {
@@ -12,7 +17,31 @@ pub struct AVCAlert {
     pub permission: String,
     pub comm: String,
     pub path: String,
+    #[serde(default)]
+    pub pid: u32,
     pub severity: AVCSeverity,
+    /// systemd-юнит процесса (если удалось определить)
+    #[serde(default)]
+    pub unit: Option<String>,
+    /// Контейнер процесса: имя и короткий ID
+    #[serde(default)]
+    pub container: Option<String>,
+    /// Порт из отказа на сокете (`src=` для name_bind, `dest=` для name_connect)
+    #[serde(default)]
+    pub port: Option<u16>,
+}
+
+impl AVCAlert {
+    /// Сигнатура отказа без привязки ко времени: домен, тип цели, класс, разрешение.
+    pub fn signature(&self) -> String {
+        format!(
+            "{} -> {}:{} {{ {} }}",
+            context_type(&self.source_context),
+            context_type(&self.target_context),
+            self.target_class,
+            self.permission
+        )
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -22,58 +51,218 @@ pub enum AVCSeverity {
     Low,
 }
 
+/// Группировка списка AVC в представлении
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AVCGrouping {
+    None,
+    Unit,
+    Container,
+}
+
+impl AVCGrouping {
+    pub fn next(&self) -> Self {
+        match self {
+            Self::None => Self::Unit,
+            Self::Unit => Self::Container,
+            Self::Container => Self::None,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::None => "none",
+            Self::Unit => "unit",
+            Self::Container => "container",
+        }
+    }
+
+    /// Ключ группы для отказа; `None` для режима без группировки
+    pub fn key(&self, alert: &AVCAlert) -> Option<String> {
+        let value = match self {
+            Self::None => return None,
+            Self::Unit => alert.unit.as_deref(),
+            Self::Container => alert.container.as_deref(),
+        };
+        Some(value.unwrap_or("-").to_string())
+    }
+}
+
 #[derive(Clone)]
 pub struct AVCManager {
     pub alerts: Vec<AVCAlert>,
+    pub severity_rules: SeverityRules,
+    /// Выбранный источник (может быть Auto)
+    pub source: AVCSource,
+    /// Источник, из которого реально были прочитаны последние данные
+    pub active_source: Option<AVCSource>,
+    /// Текущий запрос: временной диапазон применяется при загрузке,
+    /// фильтры по полям — в представлении
+    pub query: AVCQuery,
+    /// Происхождение уже обработанных отказов: процесс может завершиться
+    /// к следующему обновлению, поэтому результат запоминается
+    origin_cache: HashMap<String, ProcessOrigin>,
 }
 
 impl AVCManager {
     pub fn new() -> Self {
-        Self { alerts: Vec::new() }
+        Self {
+            alerts: Vec::new(),
+            severity_rules: SeverityRules::new(),
+            source: AVCSource::Auto,
+            active_source: None,
+            query: AVCQuery::default(),
+            origin_cache: HashMap::new(),
+        }
+    }
+
+    pub fn set_source(&mut self, source: AVCSource) {
+        self.source = source;
+        self.active_source = None;
     }
 
     pub fn load_avc_logs(&mut self) -> Result<()> {
-        let output = Command::new("ausearch")
-        .args(&["-m", "avc", "--raw", "-ts", "recent"])
-        .output()?
-        .stdout;
-
-        let logs = String::from_utf8_lossy(&output);
-        let re = Regex::new(r"type=AVC msg=audit\((.*?)\): avc:  denied  \{ (.*?) \} for  pid=\d+ comm=(.*?) (?:name=(.*?))? (?:dev=(.*?))? (?:ino=\d+ )?scontext=(.*?) tcontext=(.*?) tclass=(.*?) permissive=\d")?;
-
-        self.alerts.clear();
-        for cap in re.captures_iter(&logs) {
-            let timestamp = cap[1].to_string();
-            let permission = cap[2].to_string();
-            let comm = cap[3].to_string().replace("\"", "");
-            let path = cap.get(4).map_or("".to_string(), |m| m.as_str().to_string().replace("\"", ""));
-            let source_context = cap[6].to_string();
-            let target_context = cap[7].to_string();
-            let target_class = cap[8].to_string();
-
-            let severity = match permission.as_str() {
-                "execute" | "write" | "unlink" => AVCSeverity::High,
-                "read" | "getattr" => AVCSeverity::Medium,
-                _ => AVCSeverity::Low,
+        // Подхватываем правки файла правил без перезапуска
+        self.severity_rules.refresh();
+
+        let resolved = self.source.resolve();
+        let raw = resolved.fetch(self.query.since.as_ref(), self.query.until.as_ref())?;
+        self.active_source = Some(resolved);
+
+        let mut alerts = self.parse_avc_records(&raw);
+        // Источники-файлы не умеют фильтровать по времени сами
+        alerts.retain(|a| self.query.matches_time(a));
+        self.correlate(&mut alerts);
+        self.alerts = alerts;
+        Ok(())
+    }
+
+    /// Дополняет отказы systemd-юнитом и контейнером.
+    fn correlate(&mut self, alerts: &mut [AVCAlert]) {
+        let key = |a: &AVCAlert| format!("{}|{}", a.timestamp, a.pid);
+        let mut correlator: Option<Correlator> = None;
+        for alert in alerts.iter_mut() {
+            let origin = match self.origin_cache.get(&key(alert)) {
+                Some(o) => o.clone(),
+                None => {
+                    // Снимок процессов делаем лениво — только если есть новые отказы
+                    let c = correlator.get_or_insert_with(Correlator::snapshot);
+                    let o = c.origin(
+                        alert.pid,
+                        &alert.comm,
+                        &alert.source_context,
+                        alert.container.as_deref(),
+                        alert.unit.as_deref(),
+                    );
+                    self.origin_cache.insert(key(alert), o.clone());
+                    o
+                }
             };
+            alert.unit = origin.unit;
+            alert.container = origin.container;
+        }
+    }
 
-            self.alerts.push(AVCAlert {
-                timestamp,
-                source_context,
-                target_context,
-                target_class,
-                permission,
-                comm,
-                path,
-                severity,
-            });
+    /// Разбирает записи AVC из сырого текста: audit-строки (`type=AVC msg=audit(...)`),
+    /// строки ядра (`audit: type=1400 audit(...)`) и JSON-вывод journalctl.
+    pub fn parse_avc_records(&self, raw: &str) -> Vec<AVCAlert> {
+        lazy_static! {
+            static ref CONTID_RE: Regex = Regex::new(r"type=CONTAINER_ID msg=audit\(([^)]+)\):.*\bcontid=(\S+)").unwrap();
+            static ref CGROUP_RE: Regex = Regex::new(r#"audit\(([^)]+)\):.*\bcgroup="?([^"\s]+)"#).unwrap();
         }
+        let messages = extract_messages(raw);
 
-        Ok(())
+        // Запись CONTAINER_ID того же события (audit с поддержкой contid)
+        let container_ids: HashMap<String, String> = messages
+            .iter()
+            .filter_map(|m| CONTID_RE.captures(m).map(|c| (c[1].to_string(), c[2].to_string())))
+            .collect();
+
+        // Юнит из cgroup записей того же события или из метаданных журнала —
+        // нужен, когда процесс уже завершился и /proc/<pid>/cgroup не прочитать
+        let mut units = journal_units(raw);
+        for caps in messages.iter().filter_map(|m| CGROUP_RE.captures(m)) {
+            if let Some(unit) = unit_from_cgroup(&format!("0::{}", &caps[2])) {
+                units.entry(caps[1].to_string()).or_insert(unit);
+            }
+        }
+
+        let paths = event_paths(&messages);
+
+        messages
+            .iter()
+            .filter_map(|msg| self.parse_avc_line(msg))
+            .map(|mut alert| {
+                alert.container = container_ids.get(&alert.timestamp).cloned();
+                alert.unit = units.get(&alert.timestamp).cloned();
+                // В AVC обычно только `name=` (базовое имя) — полный путь берем из
+                // PATH/CWD того же события и заново оцениваем серьезность
+                if !alert.path.starts_with('/') {
+                    if let Some(path) = paths.get(&alert.timestamp).and_then(|e| e.resolve(&alert.path)) {
+                        alert.path = path;
+                        alert.severity = self.severity_rules.classify(
+                            &alert.source_context,
+                            &alert.target_context,
+                            &alert.target_class,
+                            &alert.permission,
+                            &alert.path,
+                        );
+                    }
+                }
+                alert
+            })
+            .collect()
+    }
+
+    fn parse_avc_line(&self, line: &str) -> Option<AVCAlert> {
+        lazy_static! {
+            static ref TIMESTAMP_RE: Regex = Regex::new(r"audit\(([^)]+)\)").unwrap();
+            static ref DENIED_RE: Regex = Regex::new(r"avc:\s+denied\s+\{\s*([^}]*?)\s*\}\s+for\s+(.*)$").unwrap();
+            static ref FIELD_RE: Regex = Regex::new(r#"(\w+)=("[^"]*"|\S+)"#).unwrap();
+        }
+
+        let denied = DENIED_RE.captures(line)?;
+        let timestamp = TIMESTAMP_RE.captures(line).map_or(String::new(), |c| c[1].to_string());
+        let permission = denied[1].to_string();
+
+        let mut fields: HashMap<String, String> = HashMap::new();
+        let mut raw_path: Option<String> = None;
+        for cap in FIELD_RE.captures_iter(&denied[2]) {
+            // Имя с пробелами audit пишет hex-строкой без кавычек
+            if &cap[1] == "path" || (&cap[1] == "name" && raw_path.is_none()) {
+                raw_path = Some(audit_string(&cap[2]).unwrap_or_else(|| cap[2].replace('"', "")));
+            }
+            fields.insert(cap[1].to_string(), cap[2].replace('"', ""));
+        }
+
+        let source_context = fields.get("scontext")?.clone();
+        let target_context = fields.get("tcontext")?.clone();
+        let target_class = fields.get("tclass")?.clone();
+        let comm = fields.get("comm").cloned().unwrap_or_default();
+        let pid = fields.get("pid").and_then(|p| p.parse().ok()).unwrap_or(0);
+        let path = raw_path.unwrap_or_default();
+        let port = fields.get("dest").or_else(|| fields.get("src")).and_then(|p| p.parse().ok());
+
+        let severity = self.severity_rules.classify(&source_context, &target_context, &target_class, &permission, &path);
+
+        Some(AVCAlert {
+            timestamp,
+            source_context,
+            target_context,
+            target_class,
+            permission,
+            comm,
+            path,
+            pid,
+            severity,
+            unit: None,
+            container: None,
+            port,
+        })
     }
 
     pub fn load_simulation_data(&mut self) {
-        self.alerts = vec![
+        self.severity_rules.refresh();
+        let alerts = vec![
             AVCAlert {
                 timestamp: "2024-01-15 10:30:00".to_string(),
                 source_context: "httpd_t".to_string(),
@@ -82,44 +271,148 @@ impl AVCManager {
                 permission: "read".to_string(),
                 comm: "httpd".to_string(),
                 path: "/home/user/file.txt".to_string(),
+                pid: 1234,
+                severity: AVCSeverity::Medium,
+                unit: Some("httpd.service".to_string()),
+                container: None,
+                port: None,
+            },
+            AVCAlert {
+                timestamp: "2024-01-15 10:32:00".to_string(),
+                source_context: "system_u:system_r:httpd_t:s0".to_string(),
+                target_context: "system_u:object_r:unreserved_port_t:s0".to_string(),
+                target_class: "tcp_socket".to_string(),
+                permission: "name_bind".to_string(),
+                comm: "httpd".to_string(),
+                path: String::new(),
+                pid: 1234,
                 severity: AVCSeverity::Medium,
+                unit: Some("httpd.service".to_string()),
+                container: None,
+                port: Some(8888),
+            },
+            AVCAlert {
+                timestamp: "2024-01-15 10:35:00".to_string(),
+                source_context: "system_u:system_r:httpd_t:s0".to_string(),
+                target_context: "unconfined_u:object_r:user_home_t:s0".to_string(),
+                target_class: "file".to_string(),
+                permission: "read".to_string(),
+                comm: "httpd".to_string(),
+                path: "/var/www/html/index.html".to_string(),
+                pid: 1234,
+                severity: AVCSeverity::Medium,
+                unit: Some("httpd.service".to_string()),
+                container: None,
+                port: None,
             },
         ];
+        self.alerts = alerts.into_iter().filter(|a| self.query.matches_time(a)).collect();
     }
+}
 
-    pub fn analyze_avc(&self, alert: &AVCAlert) -> Option<AVCSolution> {
-        let mut module_content = String::new();
-        let mut commands = vec![];
+/// Записи CWD и PATH одного события audit
+#[derive(Debug, Default)]
+struct EventPaths {
+    cwd: Option<String>,
+    /// `name=` и `nametype=` по порядку `item=`
+    items: Vec<(String, String)>,
+}
 
-        if alert.source_context.contains("httpd_t") && alert.permission == "read" && alert.target_context.contains("home") {
-            module_content = format!("allow {} {}:{} {{ {} }};", alert.source_context, alert.target_context, alert.target_class, alert.permission);
-            commands.push(format!("audit2allow -M mymodule -i <(ausearch -m avc -ts recent)"));
-            commands.push("semodule -i mymodule.pp".to_string());
-        } else if alert.permission == "execute" {
-            commands.push(format!("setsebool -P allow_execmem 1"));
+impl EventPaths {
+    /// Абсолютный путь объекта отказа: элемент PATH с тем же базовым именем,
+    /// что `name=` в AVC, иначе первый не-PARENT элемент. Относительное имя
+    /// дополняется каталогом из CWD.
+    fn resolve(&self, name: &str) -> Option<String> {
+        let basename = |p: &str| p.trim_end_matches('/').rsplit('/').next().unwrap_or("").to_string();
+        let (path, _) = self
+            .items
+            .iter()
+            .find(|(p, _)| !name.is_empty() && basename(p) == name)
+            .or_else(|| self.items.iter().find(|(_, t)| t != "PARENT"))?;
+        if path.starts_with('/') {
+            return Some(path.clone());
         }
-
-        Some(AVCSolution {
-            description: format!("Allow {} for {} on {}", alert.permission, alert.source_context, alert.target_context),
-             module_content,
-             commands,
-        })
+        let cwd = self.cwd.as_deref()?;
+        let path = path.trim_start_matches("./");
+        Some(format!("{}/{}", cwd.trim_end_matches('/'), path))
     }
+}
 
-    pub fn apply_solution(&self, solution: &AVCSolution, simulation: bool) -> Result<()> {
-        if simulation {
-            return Ok(());
-        }
-        for cmd in &solution.commands {
-            Command::new("sh").arg("-c").arg(cmd).output()?;
+/// CWD (1307) и PATH (1302) по событиям: и в формате audit.log, и в строках ядра
+fn event_paths(messages: &[String]) -> HashMap<String, EventPaths> {
+    lazy_static! {
+        static ref RECORD_RE: Regex = Regex::new(r"type=(CWD|PATH|1307|1302)\b.*?audit\(([^)]+)\):(.*)$").unwrap();
+        static ref FIELD_RE: Regex = Regex::new(r#"(\w+)=("[^"]*"|\S+)"#).unwrap();
+    }
+    let mut events: HashMap<String, EventPaths> = HashMap::new();
+    for caps in messages.iter().filter_map(|m| RECORD_RE.captures(m)) {
+        let fields: HashMap<&str, &str> = FIELD_RE
+            .captures_iter(caps.get(3).map_or("", |m| m.as_str()))
+            .map(|c| (c.get(1).unwrap().as_str(), c.get(2).unwrap().as_str()))
+            .collect();
+        let event = events.entry(caps[2].to_string()).or_default();
+        match &caps[1] {
+            "CWD" | "1307" => event.cwd = fields.get("cwd").and_then(|v| audit_string(v)),
+            _ => {
+                if let Some(name) = fields.get("name").and_then(|v| audit_string(v)) {
+                    let nametype = fields.get("nametype").copied().unwrap_or("NORMAL").to_string();
+                    event.items.push((name, nametype));
+                }
+            }
         }
-        Ok(())
     }
+    events
+}
+
+/// Значение поля audit: в кавычках — как есть, без кавычек — hex-кодированная
+/// строка (так пишутся имена с пробелами и спецсимволами); `(null)` — нет значения
+fn audit_string(value: &str) -> Option<String> {
+    if let Some(quoted) = value.strip_prefix('"') {
+        return Some(quoted.trim_end_matches('"').to_string());
+    }
+    if !value.len().is_multiple_of(2) || !value.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+    let bytes: Vec<u8> = (0..value.len())
+        .step_by(2)
+        .filter_map(|i| u8::from_str_radix(&value[i..i + 2], 16).ok())
+        .collect();
+    Some(String::from_utf8_lossy(&bytes).into_owned())
 }
 
-#[derive(Debug, Clone)]
-pub struct AVCSolution {
-    pub description: String,
-    pub module_content: String,
-    pub commands: Vec<String>,
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn path_from_cwd_and_path_records() {
+        let alerts = AVCManager::new().parse_avc_records(include_str!("../tests/fixtures/audit_path_event.log"));
+        assert_eq!(alerts.len(), 3);
+        // Относительное имя из PATH + каталог из CWD
+        assert_eq!(alerts[0].path, "/var/www/html/index.html");
+        // rename: PARENT-элементы пропускаются, выбирается элемент с именем из AVC
+        assert_eq!(alerts[1].path, "/var/www/html/uploads/new report.pdf");
+        // Событие без PATH — остается базовое имя
+        assert_eq!(alerts[2].path, "cache");
+    }
+
+    #[test]
+    fn moved_file_gets_restorecon_recommendation() {
+        let alerts = AVCManager::new().parse_avc_records(include_str!("../tests/fixtures/audit_path_event.log"));
+        let mut contexts = crate::file_contexts::FileContextManager::new();
+        contexts
+            .add_file_context("/var/www(/.*)?", "httpd_sys_content_t", crate::file_contexts::FcFileType::All, true)
+            .unwrap();
+        let mislabel = contexts.mislabel_of(&alerts[0], true).expect("user_home_t under /var/www is mislabeled");
+        assert_eq!(mislabel.path, "/var/www/html/index.html");
+        assert_eq!(mislabel.current, "user_home_t");
+        assert_eq!(mislabel.expected, "httpd_sys_content_t");
+    }
+
+    #[test]
+    fn audit_string_values() {
+        assert_eq!(audit_string("\"/etc/passwd\"").as_deref(), Some("/etc/passwd"));
+        assert_eq!(audit_string("2F746D702F612062").as_deref(), Some("/tmp/a b"));
+        assert_eq!(audit_string("(null)"), None);
+    }
 }
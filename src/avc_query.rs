@@ -0,0 +1,307 @@
+use chrono::{DateTime, Duration, Local, NaiveDate, NaiveDateTime, TimeZone};
+use anyhow::{Result, anyhow};
+use crate::avc::AVCAlert;
+use crate::severity_rules::{context_type, glob_match};
+
+/// Граница временного диапазона запроса.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TimeSpec {
+    Now,
+    /// Последние 10 минут (как `ausearch -ts recent`)
+    Recent,
+    Boot,
+    Today,
+    Yesterday,
+    WeekAgo,
+    /// Относительное смещение назад: `-30m`, `-2h`, `-3d`
+    Ago(Duration),
+    Absolute(DateTime<Local>),
+}
+
+impl TimeSpec {
+    pub fn parse(s: &str) -> Result<Self> {
+        let lower = s.trim().to_lowercase();
+        match lower.as_str() {
+            "now" => return Ok(Self::Now),
+            "recent" => return Ok(Self::Recent),
+            "boot" => return Ok(Self::Boot),
+            "today" => return Ok(Self::Today),
+            "yesterday" => return Ok(Self::Yesterday),
+            "week-ago" => return Ok(Self::WeekAgo),
+            _ => {}
+        }
+
+        if let Some(rel) = lower.strip_prefix('-') {
+            let (num, unit) = rel.split_at(rel.find(|c: char| !c.is_ascii_digit()).unwrap_or(rel.len()));
+            let n: i64 = num.parse().map_err(|_| anyhow!("Invalid relative time: {}", s))?;
+            let duration = match unit {
+                "s" => Duration::seconds(n),
+                "m" | "min" => Duration::minutes(n),
+                "h" => Duration::hours(n),
+                "d" => Duration::days(n),
+                "w" => Duration::weeks(n),
+                _ => return Err(anyhow!("Invalid time unit in {}", s)),
+            };
+            return Ok(Self::Ago(duration));
+        }
+
+        if let Some(epoch) = lower.strip_prefix('@') {
+            let secs: i64 = epoch.parse().map_err(|_| anyhow!("Invalid epoch time: {}", s))?;
+            let dt = Local.timestamp_opt(secs, 0).single().ok_or_else(|| anyhow!("Invalid epoch time: {}", s))?;
+            return Ok(Self::Absolute(dt));
+        }
+
+        for fmt in ["%Y-%m-%dT%H:%M:%S", "%Y-%m-%dT%H:%M", "%Y-%m-%d %H:%M:%S"] {
+            if let Ok(naive) = NaiveDateTime::parse_from_str(s.trim(), fmt) {
+                if let Some(dt) = Local.from_local_datetime(&naive).earliest() {
+                    return Ok(Self::Absolute(dt));
+                }
+            }
+        }
+        if let Ok(date) = NaiveDate::parse_from_str(s.trim(), "%Y-%m-%d") {
+            if let Some(dt) = date.and_hms_opt(0, 0, 0).and_then(|n| Local.from_local_datetime(&n).earliest()) {
+                return Ok(Self::Absolute(dt));
+            }
+        }
+
+        Err(anyhow!("Unrecognized time: {} (use now, recent, boot, today, yesterday, -2h, YYYY-MM-DD[THH:MM])", s))
+    }
+
+    /// Переводит границу в конкретный момент времени.
+    pub fn resolve(&self) -> DateTime<Local> {
+        let now = Local::now();
+        match self {
+            Self::Now => now,
+            Self::Recent => now - Duration::minutes(10),
+            Self::Boot => boot_time().unwrap_or(now),
+            Self::Today => start_of_day(now),
+            Self::Yesterday => start_of_day(now) - Duration::days(1),
+            Self::WeekAgo => now - Duration::days(7),
+            Self::Ago(d) => now - *d,
+            Self::Absolute(dt) => *dt,
+        }
+    }
+
+    /// Аргументы для `ausearch -ts/-te`. Ключевые слова передаются как есть,
+    /// остальное — датой и временем в C-локали.
+    pub fn ausearch_args(&self) -> Vec<String> {
+        match self {
+            Self::Now => vec!["now".to_string()],
+            Self::Recent => vec!["recent".to_string()],
+            Self::Boot => vec!["boot".to_string()],
+            Self::Today => vec!["today".to_string()],
+            Self::Yesterday => vec!["yesterday".to_string()],
+            Self::WeekAgo => vec!["week-ago".to_string()],
+            _ => {
+                let dt = self.resolve();
+                vec![dt.format("%m/%d/%y").to_string(), dt.format("%H:%M:%S").to_string()]
+            }
+        }
+    }
+
+    /// Значение для `journalctl --since/--until`.
+    pub fn journal_arg(&self) -> String {
+        match self {
+            Self::Now => "now".to_string(),
+            Self::Today => "today".to_string(),
+            Self::Yesterday => "yesterday".to_string(),
+            _ => self.resolve().format("%Y-%m-%d %H:%M:%S").to_string(),
+        }
+    }
+}
+
+fn start_of_day(dt: DateTime<Local>) -> DateTime<Local> {
+    dt.date_naive()
+        .and_hms_opt(0, 0, 0)
+        .and_then(|n| Local.from_local_datetime(&n).earliest())
+        .unwrap_or(dt)
+}
+
+fn boot_time() -> Option<DateTime<Local>> {
+    let stat = std::fs::read_to_string("/proc/stat").ok()?;
+    let btime: i64 = stat
+        .lines()
+        .find_map(|l| l.strip_prefix("btime "))?
+        .trim()
+        .parse()
+        .ok()?;
+    Local.timestamp_opt(btime, 0).single()
+}
+
+/// Время AVC-записи: `1700000000.123:456` (audit) или `2024-01-15 10:30:00`.
+pub fn alert_time(alert: &AVCAlert) -> Option<DateTime<Local>> {
+    let ts = alert.timestamp.trim();
+    let epoch_part = ts.split(':').next().unwrap_or(ts);
+    if let Ok(secs) = epoch_part.parse::<f64>() {
+        return Local.timestamp_opt(secs as i64, 0).single();
+    }
+    NaiveDateTime::parse_from_str(ts, "%Y-%m-%d %H:%M:%S")
+        .ok()
+        .and_then(|n| Local.from_local_datetime(&n).earliest())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum QueryField {
+    SourceType,
+    TargetType,
+    Class,
+    Permission,
+    Comm,
+    Path,
+    Pid,
+    Unit,
+    Container,
+}
+
+impl QueryField {
+    fn from_key(key: &str) -> Option<Self> {
+        match key {
+            "stype" | "scontext" | "source" => Some(Self::SourceType),
+            "ttype" | "tcontext" | "target" => Some(Self::TargetType),
+            "class" | "tclass" => Some(Self::Class),
+            "perm" | "permission" => Some(Self::Permission),
+            "comm" => Some(Self::Comm),
+            "path" => Some(Self::Path),
+            "pid" => Some(Self::Pid),
+            "unit" => Some(Self::Unit),
+            "container" => Some(Self::Container),
+            _ => None,
+        }
+    }
+}
+
+/// Запрос к истории AVC: временной диапазон плюс фильтры по полям.
+///
+/// Синтаксис: `since:today until:-1h stype:httpd_t perm:read path:/var/www/*`.
+/// Значения полей поддерживают `*` и `?`; слова без ключа ищутся как подстрока
+/// в comm, path и контекстах. Значение с пробелами берется в кавычки:
+/// `since:"2024-01-15 10:00:00"`.
+#[derive(Debug, Clone, Default)]
+pub struct AVCQuery {
+    pub text: String,
+    pub since: Option<TimeSpec>,
+    pub until: Option<TimeSpec>,
+    pub filters: Vec<(QueryField, String)>,
+    pub keywords: Vec<String>,
+}
+
+impl AVCQuery {
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut query = Self {
+            text: text.trim().to_string(),
+            ..Default::default()
+        };
+        for token in Self::split_tokens(text)? {
+            let token = token.replace('"', "");
+            match token.split_once(':') {
+                Some(("since", value)) => query.since = Some(TimeSpec::parse(value)?),
+                Some(("until", value)) => query.until = Some(TimeSpec::parse(value)?),
+                Some((key, value)) => match QueryField::from_key(key) {
+                    Some(field) => query.filters.push((field, value.to_string())),
+                    None => return Err(anyhow!("Unknown query field: {}", key)),
+                },
+                None => query.keywords.push(token),
+            }
+        }
+        Ok(query)
+    }
+
+    /// Делит текст запроса по пробелам вне кавычек; кавычки остаются в токенах
+    pub fn split_tokens(text: &str) -> Result<Vec<&str>> {
+        let mut tokens = Vec::new();
+        let mut start = None;
+        let mut quoted = false;
+        for (i, c) in text.char_indices() {
+            match c {
+                '"' => {
+                    quoted = !quoted;
+                    start.get_or_insert(i);
+                }
+                c if c.is_whitespace() && !quoted => {
+                    if let Some(s) = start.take() {
+                        tokens.push(&text[s..i]);
+                    }
+                }
+                _ => {
+                    start.get_or_insert(i);
+                }
+            }
+        }
+        if quoted {
+            return Err(anyhow!("Unterminated quote in query"));
+        }
+        if let Some(s) = start {
+            tokens.push(&text[s..]);
+        }
+        Ok(tokens)
+    }
+
+    pub fn has_time_range(&self) -> bool {
+        self.since.is_some() || self.until.is_some()
+    }
+
+    /// Проверка попадания во временной диапазон. Записи без разбираемого времени
+    /// не отбрасываются.
+    pub fn matches_time(&self, alert: &AVCAlert) -> bool {
+        if !self.has_time_range() {
+            return true;
+        }
+        let Some(time) = alert_time(alert) else {
+            return true;
+        };
+        self.since.as_ref().is_none_or(|s| time >= s.resolve())
+            && self.until.as_ref().is_none_or(|u| time <= u.resolve())
+    }
+
+    pub fn matches_fields(&self, alert: &AVCAlert) -> bool {
+        let fields_ok = self.filters.iter().all(|(field, pattern)| {
+            let value = match field {
+                QueryField::SourceType => context_type(&alert.source_context).to_string(),
+                QueryField::TargetType => context_type(&alert.target_context).to_string(),
+                QueryField::Class => alert.target_class.clone(),
+                QueryField::Permission => {
+                    // В одной записи может быть несколько разрешений: "read write"
+                    return alert.permission.split_whitespace().any(|p| glob_match(pattern, p));
+                }
+                QueryField::Comm => alert.comm.clone(),
+                QueryField::Path => alert.path.clone(),
+                QueryField::Pid => alert.pid.to_string(),
+                QueryField::Unit => alert.unit.clone().unwrap_or_default(),
+                QueryField::Container => alert.container.clone().unwrap_or_default(),
+            };
+            glob_match(pattern, &value)
+        });
+        let keywords_ok = self.keywords.iter().all(|kw| {
+            alert.comm.contains(kw.as_str())
+                || alert.path.contains(kw.as_str())
+                || alert.source_context.contains(kw.as_str())
+                || alert.target_context.contains(kw.as_str())
+        });
+        fields_ok && keywords_ok
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quoted_value_keeps_spaces() {
+        let query = AVCQuery::parse(r#"since:"2026-10-18 10:00:00" stype:httpd_t"#).unwrap();
+        let expected = NaiveDateTime::parse_from_str("2026-10-18 10:00:00", "%Y-%m-%d %H:%M:%S").unwrap();
+        assert_eq!(query.since.unwrap().resolve().naive_local(), expected);
+        assert_eq!(query.filters, vec![(QueryField::SourceType, "httpd_t".to_string())]);
+    }
+
+    #[test]
+    fn split_tokens_respects_quotes() {
+        let tokens = AVCQuery::split_tokens(r#"  since:"a b"  path:/x "c d" "#).unwrap();
+        assert_eq!(tokens, vec![r#"since:"a b""#, "path:/x", r#""c d""#]);
+        assert!(AVCQuery::split_tokens(r#"since:"a b"#).is_err());
+    }
+
+    #[test]
+    fn unquoted_datetime_is_rejected() {
+        assert!(AVCQuery::parse("since:2026-10-18 10:00:00").is_err());
+    }
+}
@@ -0,0 +1,266 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use anyhow::{Result, anyhow, Context};
+use regex::Regex;
+use crate::avc_query::TimeSpec;
+use crate::correlation::unit_from_cgroup;
+
+const AUDIT_LOG_PATH: &str = "/var/log/audit/audit.log";
+
+/// Источник AVC-отказов.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum AVCSource {
+    /// Выбрать первый доступный источник при каждой загрузке
+    Auto,
+    /// `ausearch -m avc` (требует работающий auditd)
+    Ausearch,
+    /// Прямое чтение журнала auditd
+    AuditLog(PathBuf),
+    /// `journalctl -k -o json` — для систем без auditd
+    Journal,
+    /// Произвольный файл с сырыми audit-строками или JSON-выводом journalctl
+    File(PathBuf),
+}
+
+impl AVCSource {
+    /// Разбирает значение `--avc-source`. Для `file` путь берется из `--logfile`.
+    pub fn from_cli(name: &str, logfile: Option<&str>) -> Result<Self> {
+        match name.to_lowercase().as_str() {
+            "auto" => Ok(match logfile {
+                Some(path) => Self::File(PathBuf::from(path)),
+                None => Self::Auto,
+            }),
+            "ausearch" => Ok(Self::Ausearch),
+            "audit-log" | "auditd" => Ok(Self::AuditLog(PathBuf::from(logfile.unwrap_or(AUDIT_LOG_PATH)))),
+            "journal" | "journald" => Ok(Self::Journal),
+            "file" => logfile
+                .map(|p| Self::File(PathBuf::from(p)))
+                .ok_or_else(|| anyhow!("--avc-source file requires --logfile")),
+            other => Err(anyhow!("Unknown AVC source: {}", other)),
+        }
+    }
+
+    pub fn label(&self) -> String {
+        match self {
+            Self::Auto => "auto".to_string(),
+            Self::Ausearch => "ausearch".to_string(),
+            Self::AuditLog(p) => format!("audit log {}", p.display()),
+            Self::Journal => "journal".to_string(),
+            Self::File(p) => format!("file {}", p.display()),
+        }
+    }
+
+    /// Определяет, какой источник реально доступен на этой системе.
+    pub fn detect() -> Self {
+        let audit_log = Path::new(AUDIT_LOG_PATH);
+        if command_exists("ausearch") && auditd_running() {
+            Self::Ausearch
+        } else if audit_log.exists() && std::fs::File::open(audit_log).is_ok() {
+            Self::AuditLog(audit_log.to_path_buf())
+        } else if command_exists("journalctl") {
+            Self::Journal
+        } else {
+            Self::Ausearch
+        }
+    }
+
+    /// Возвращает конкретный источник (для Auto — результат автоопределения).
+    pub fn resolve(&self) -> Self {
+        match self {
+            Self::Auto => Self::detect(),
+            other => other.clone(),
+        }
+    }
+
+    /// Читает сырые записи из источника. Формат строк — audit или JSON journalctl,
+    /// разбор выполняет `AVCManager`. Без `since` берутся последние 10 минут
+    /// для ausearch/journal и весь файл для файловых источников.
+    pub fn fetch(&self, since: Option<&TimeSpec>, until: Option<&TimeSpec>) -> Result<String> {
+        match self.resolve() {
+            Self::Auto => unreachable!("resolve() never returns Auto"),
+            Self::Ausearch => {
+                let mut cmd = Command::new("ausearch");
+                cmd.env("LC_TIME", "C").args(["-m", "avc", "--raw", "-ts"]);
+                cmd.args(since.unwrap_or(&TimeSpec::Recent).ausearch_args());
+                if let Some(until) = until {
+                    cmd.arg("-te").args(until.ausearch_args());
+                }
+                let output = cmd
+                    .output()
+                    .with_context(|| "Failed to run ausearch")?;
+                Ok(String::from_utf8_lossy(&output.stdout).to_string())
+            }
+            Self::Journal => {
+                let mut cmd = Command::new("journalctl");
+                cmd.args(["-k", "-o", "json", "--no-pager"]);
+                match since.unwrap_or(&TimeSpec::Recent) {
+                    TimeSpec::Boot => {
+                        cmd.arg("-b");
+                    }
+                    spec => {
+                        cmd.arg("--since").arg(spec.journal_arg());
+                    }
+                }
+                if let Some(until) = until {
+                    cmd.arg("--until").arg(until.journal_arg());
+                }
+                let output = cmd
+                    .output()
+                    .with_context(|| "Failed to run journalctl")?;
+                Ok(String::from_utf8_lossy(&output.stdout).to_string())
+            }
+            Self::AuditLog(path) | Self::File(path) => {
+                let data = std::fs::read(&path)
+                    .with_context(|| format!("Failed to read AVC log {:?}", path))?;
+                Ok(String::from_utf8_lossy(&data).to_string())
+            }
+        }
+    }
+}
+
+/// Извлекает текст сообщений из вывода источника. Строки JSON (journalctl -o json)
+/// разворачиваются в поле MESSAGE, остальные возвращаются как есть.
+pub fn extract_messages(raw: &str) -> Vec<String> {
+    raw.lines()
+        .filter_map(|line| {
+            let trimmed = line.trim();
+            if trimmed.starts_with('{') {
+                journal_message(trimmed)
+            } else if trimmed.is_empty() {
+                None
+            } else {
+                Some(trimmed.to_string())
+            }
+        })
+        .collect()
+}
+
+fn journal_message(json_line: &str) -> Option<String> {
+    let value: serde_json::Value = serde_json::from_str(json_line).ok()?;
+    match value.get("MESSAGE")? {
+        serde_json::Value::String(s) => Some(s.clone()),
+        // journald отдает не-UTF8 сообщения массивом байт
+        serde_json::Value::Array(bytes) => {
+            let data: Vec<u8> = bytes.iter().filter_map(|b| b.as_u64().map(|v| v as u8)).collect();
+            Some(String::from_utf8_lossy(&data).to_string())
+        }
+        _ => None,
+    }
+}
+
+/// Юниты событий аудита из метаданных журнала (`_SYSTEMD_UNIT`, `_SYSTEMD_CGROUP`):
+/// ID события `audit(...)` -> юнит
+pub fn journal_units(raw: &str) -> HashMap<String, String> {
+    lazy_static::lazy_static! {
+        static ref EVENT_RE: Regex = Regex::new(r"audit\(([^)]+)\)").unwrap();
+    }
+    let mut units = HashMap::new();
+    for line in raw.lines().map(str::trim).filter(|l| l.starts_with('{')) {
+        let Ok(value) = serde_json::from_str::<serde_json::Value>(line) else {
+            continue;
+        };
+        let unit = match (value.get("_SYSTEMD_UNIT"), value.get("_SYSTEMD_CGROUP")) {
+            (Some(serde_json::Value::String(unit)), _) => Some(unit.clone()),
+            (_, Some(serde_json::Value::String(cgroup))) => unit_from_cgroup(&format!("0::{}", cgroup)),
+            _ => None,
+        };
+        let event = journal_message(line).and_then(|m| EVENT_RE.captures(&m).map(|c| c[1].to_string()));
+        if let (Some(event), Some(unit)) = (event, unit) {
+            units.entry(event).or_insert(unit);
+        }
+    }
+    units
+}
+
+pub fn command_exists(name: &str) -> bool {
+    std::env::var_os("PATH")
+        .map(|paths| std::env::split_paths(&paths).any(|dir| dir.join(name).is_file()))
+        .unwrap_or(false)
+}
+
+fn auditd_running() -> bool {
+    if Path::new("/run/auditd.pid").exists() || Path::new("/var/run/auditd.pid").exists() {
+        return true;
+    }
+    Command::new("systemctl")
+        .args(["is-active", "--quiet", "auditd"])
+        .status()
+        .map(|s| s.success())
+        .unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::avc::AVCManager;
+
+    /// Вывод `journalctl -k -o json`: событие AVC со всеми записями (SYSCALL, CWD,
+    /// PATH, PROCTITLE), не-AVC запись и AVC с не-UTF8 comm, который journald
+    /// отдает массивом байт
+    const JOURNAL: &str = include_str!("../tests/fixtures/journal_avc.json");
+
+    #[test]
+    fn extracts_string_and_byte_array_messages() {
+        let messages = extract_messages(JOURNAL);
+        assert_eq!(messages.len(), 9);
+        assert!(messages[0].starts_with("audit: type=1400 audit(1760781000.123:456): avc:  denied  { read }"));
+        assert!(messages[1].starts_with("audit: type=1300 audit(1760781000.123:456): arch=c000003e syscall=257"));
+        assert_eq!(messages[2], "audit: type=1307 audit(1760781000.123:456): cwd=\"/\"");
+        assert!(messages[5].contains("type=1130"));
+        // Не-UTF8 байт заменяется, остальное сообщение сохраняется
+        assert!(messages[6].contains("comm=\"my\u{FFFD}app\""));
+        assert!(messages[6].ends_with("tclass=tcp_socket permissive=0"));
+    }
+
+    #[test]
+    fn parses_alerts_from_journal_json() {
+        let alerts = AVCManager::new().parse_avc_records(JOURNAL);
+        assert_eq!(alerts.len(), 2);
+
+        let read = &alerts[0];
+        assert_eq!(read.timestamp, "1760781000.123:456");
+        assert_eq!(read.permission, "read");
+        assert_eq!(read.comm, "httpd");
+        assert_eq!(read.pid, 1234);
+        // Полный путь — из записи PATH того же события
+        assert_eq!(read.path, "/var/www/html/index.html");
+        assert_eq!(read.source_context, "system_u:system_r:httpd_t:s0");
+        assert_eq!(read.target_context, "unconfined_u:object_r:user_home_t:s0");
+        assert_eq!(read.target_class, "file");
+        assert_eq!(read.port, None);
+
+        let bind = &alerts[1];
+        assert_eq!(bind.timestamp, "1760781002.500:458");
+        assert_eq!(bind.permission, "name_bind");
+        assert_eq!(bind.comm, "my\u{FFFD}app");
+        assert_eq!(bind.pid, 2201);
+        assert_eq!(bind.target_class, "tcp_socket");
+        assert_eq!(bind.port, Some(8888));
+    }
+
+    #[test]
+    fn unit_from_journal_metadata() {
+        let line = r#"{"_SYSTEMD_CGROUP":"/system.slice/php-fpm.service","MESSAGE":"audit: type=1400 audit(1760781003.000:460): avc:  denied  { write } for  pid=3301 comm=\"php-fpm\" name=\"uploads\" scontext=system_u:system_r:httpd_t:s0 tcontext=system_u:object_r:httpd_sys_content_t:s0 tclass=dir permissive=0"}"#;
+        let alerts = AVCManager::new().parse_avc_records(line);
+        assert_eq!(alerts[0].unit.as_deref(), Some("php-fpm.service"));
+    }
+
+    #[test]
+    fn unit_from_cgroup_of_same_event() {
+        let raw = "type=AVC msg=audit(1760781004.000:470): avc:  denied  { read } for  pid=4100 comm=\"backup\" scontext=system_u:system_r:rsync_t:s0 tcontext=system_u:object_r:shadow_t:s0 tclass=file permissive=0\n\
+                   type=SYSCALL msg=audit(1760781004.000:470): arch=c000003e syscall=257 success=no exit=-13 pid=4100 comm=\"backup\" cgroup=\"/system.slice/backup.service\"\n\
+                   type=AVC msg=audit(1760781005.000:471): avc:  denied  { read } for  pid=4200 comm=\"other\" scontext=system_u:system_r:rsync_t:s0 tcontext=system_u:object_r:shadow_t:s0 tclass=file permissive=0\n";
+        let alerts = AVCManager::new().parse_avc_records(raw);
+        assert_eq!(alerts.len(), 2);
+        assert_eq!(alerts[0].unit.as_deref(), Some("backup.service"));
+        assert_eq!(alerts[1].unit, None);
+    }
+
+    #[test]
+    fn plain_lines_pass_through() {
+        let raw = "type=AVC msg=audit(1760781000.123:456): avc:  denied  { write } for  pid=1 comm=\"x\"\n\n  \n";
+        assert_eq!(extract_messages(raw), vec![raw.lines().next().unwrap().to_string()]);
+    }
+}
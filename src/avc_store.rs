@@ -0,0 +1,197 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use chrono::{DateTime, Duration, Local, Utc};
+use anyhow::{Result, Context};
+use crate::avc::AVCAlert;
+use crate::avc_query::alert_time;
+use crate::severity_rules::context_type;
+
+/// Отказ, сохраненный в локальной базе.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StoredDenial {
+    pub key: String,
+    /// Время события (unix-секунды) — из audit-записи или момент сохранения
+    pub event_time: i64,
+    pub stored_at: String,
+    pub alert: AVCAlert,
+}
+
+/// Тренд по домену: число отказов за последние 7 дней и за 7 дней до этого.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DomainTrend {
+    pub domain: String,
+    pub this_week: usize,
+    pub last_week: usize,
+}
+
+/// Локальная база AVC-отказов (`~/.config/selab/avc_history.jsonl`).
+/// Каждая строка файла — один `StoredDenial`; новые записи дописываются в конец,
+/// очистка по сроку хранения переписывает файл целиком.
+pub struct AVCStore {
+    pub records: Vec<StoredDenial>,
+    pub retention_days: i64,
+    keys: HashSet<String>,
+    signatures: HashSet<String>,
+    store_path: PathBuf,
+}
+
+impl AVCStore {
+    pub fn new(retention_days: i64) -> Self {
+        let mut store = Self {
+            records: Vec::new(),
+            retention_days: retention_days.max(1),
+            keys: HashSet::new(),
+            signatures: HashSet::new(),
+            store_path: Self::default_store_path(),
+        };
+        let _ = store.load_from_disk(); // тихая попытка загрузки
+        let _ = store.prune();
+        store
+    }
+
+    fn default_store_path() -> PathBuf {
+        if let Some(mut dir) = dirs::config_dir() {
+            dir.push("selab");
+            let _ = fs::create_dir_all(&dir);
+            dir.push("avc_history.jsonl");
+            return dir;
+        }
+        let mut home = std::env::var_os("HOME").map(PathBuf::from).unwrap_or_else(|| PathBuf::from("."));
+        home.push(".selab_avc_history.jsonl");
+        home
+    }
+
+    fn load_from_disk(&mut self) -> Result<()> {
+        if !self.store_path.exists() {
+            return Ok(());
+        }
+        let data = fs::read_to_string(&self.store_path)
+            .with_context(|| format!("Failed to read AVC history at {:?}", self.store_path))?;
+        for line in data.lines().filter(|l| !l.trim().is_empty()) {
+            // Битые строки (например, оборванная запись) пропускаем
+            if let Ok(record) = serde_json::from_str::<StoredDenial>(line) {
+                if self.keys.insert(record.key.clone()) {
+                    self.signatures.insert(record.alert.signature());
+                    self.records.push(record);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn rewrite_to_disk(&self) -> Result<()> {
+        let mut data = String::new();
+        for record in &self.records {
+            data.push_str(&serde_json::to_string(record).with_context(|| "Failed to serialize AVC record")?);
+            data.push('\n');
+        }
+        fs::write(&self.store_path, data)
+            .with_context(|| format!("Failed to write AVC history at {:?}", self.store_path))?;
+        Ok(())
+    }
+
+    /// Ключ дедупликации: `timestamp` audit-записи содержит серийный номер события
+    /// (`1700000000.123:456`); разрешение и tcontext различают несколько AVC одного события.
+    pub fn record_key(alert: &AVCAlert) -> String {
+        format!("{}|{}|{}|{}", alert.timestamp, alert.target_context, alert.target_class, alert.permission)
+    }
+
+    pub fn contains(&self, alert: &AVCAlert) -> bool {
+        self.keys.contains(&Self::record_key(alert))
+    }
+
+    pub fn knows_signature(&self, alert: &AVCAlert) -> bool {
+        self.signatures.contains(&alert.signature())
+    }
+
+    /// Сохраняет новые отказы. Возвращает количество добавленных записей.
+    pub fn ingest(&mut self, alerts: &[AVCAlert]) -> Result<usize> {
+        let now = Utc::now();
+        let mut fresh = Vec::new();
+        for alert in alerts {
+            let key = Self::record_key(alert);
+            if self.keys.contains(&key) {
+                continue;
+            }
+            self.keys.insert(key.clone());
+            self.signatures.insert(alert.signature());
+            fresh.push(StoredDenial {
+                key,
+                event_time: alert_time(alert).map(|t| t.timestamp()).unwrap_or(now.timestamp()),
+                stored_at: now.to_rfc3339(),
+                alert: alert.clone(),
+            });
+        }
+        if fresh.is_empty() {
+            return Ok(0);
+        }
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.store_path)
+            .with_context(|| format!("Failed to open AVC history at {:?}", self.store_path))?;
+        for record in &fresh {
+            writeln!(file, "{}", serde_json::to_string(record)?)?;
+        }
+        let added = fresh.len();
+        self.records.extend(fresh);
+        Ok(added)
+    }
+
+    /// Удаляет записи старше срока хранения.
+    pub fn prune(&mut self) -> Result<()> {
+        let cutoff = (Utc::now() - Duration::days(self.retention_days)).timestamp();
+        let before = self.records.len();
+        self.records.retain(|r| r.event_time >= cutoff);
+        if self.records.len() != before {
+            self.keys = self.records.iter().map(|r| r.key.clone()).collect();
+            self.signatures = self.records.iter().map(|r| r.alert.signature()).collect();
+            self.rewrite_to_disk()?;
+        }
+        Ok(())
+    }
+
+    /// Сигнатуры, впервые встреченные после `cutoff` ("новое с прошлой недели").
+    pub fn new_signatures_since(&self, cutoff: DateTime<Local>) -> Vec<String> {
+        let ts = cutoff.timestamp();
+        let mut first_seen: HashMap<String, i64> = HashMap::new();
+        for r in &self.records {
+            let entry = first_seen.entry(r.alert.signature()).or_insert(r.event_time);
+            if r.event_time < *entry {
+                *entry = r.event_time;
+            }
+        }
+        let mut fresh: Vec<(String, i64)> = first_seen.into_iter().filter(|(_, t)| *t >= ts).collect();
+        fresh.sort_by_key(|f| std::cmp::Reverse(f.1));
+        fresh.into_iter().map(|(sig, _)| sig).collect()
+    }
+
+    /// Недельные тренды по доменам, отсортированные по активности за последнюю неделю.
+    pub fn domain_trends(&self) -> Vec<DomainTrend> {
+        let now = Local::now();
+        let week_ago = (now - Duration::days(7)).timestamp();
+        let two_weeks_ago = (now - Duration::days(14)).timestamp();
+
+        let mut by_domain: HashMap<String, (usize, usize)> = HashMap::new();
+        for r in self.records.iter().filter(|r| r.event_time >= two_weeks_ago) {
+            let entry = by_domain
+                .entry(context_type(&r.alert.source_context).to_string())
+                .or_insert((0, 0));
+            if r.event_time >= week_ago {
+                entry.0 += 1;
+            } else {
+                entry.1 += 1;
+            }
+        }
+        let mut trends: Vec<DomainTrend> = by_domain
+            .into_iter()
+            .map(|(domain, (this_week, last_week))| DomainTrend { domain, this_week, last_week })
+            .collect();
+        trends.sort_by(|a, b| b.this_week.cmp(&a.this_week).then(b.last_week.cmp(&a.last_week)));
+        trends
+    }
+}
@@ -0,0 +1,113 @@
+use std::collections::{BTreeMap, BTreeSet};
+use anyhow::{Result, anyhow};
+use crate::avc::AVCAlert;
+use crate::severity_rules::context_type;
+
+/// Минимальные allow-правила по отказам: (домен, цель, класс) -> разрешения.
+/// Цель, совпадающая с доменом, записывается как `self`, как у audit2allow.
+fn allow_rules(alerts: &[AVCAlert]) -> Result<BTreeMap<(String, String, String), BTreeSet<String>>> {
+    let mut rules: BTreeMap<(String, String, String), BTreeSet<String>> = BTreeMap::new();
+    for alert in alerts {
+        let source = context_type(&alert.source_context);
+        let target = match context_type(&alert.target_context) {
+            t if t == source => "self",
+            t => t,
+        };
+        // Поля берутся из журнала — в CIL пропускаем только идентификаторы
+        for ident in [source, target, alert.target_class.as_str()] {
+            if !is_identifier(ident) {
+                return Err(anyhow!("Refusing to emit CIL for invalid identifier {:?}", ident));
+            }
+        }
+        let perms = rules
+            .entry((source.to_string(), target.to_string(), alert.target_class.clone()))
+            .or_default();
+        for perm in alert.permission.split_whitespace() {
+            if !is_identifier(perm) {
+                return Err(anyhow!("Refusing to emit CIL for invalid permission {:?}", perm));
+            }
+            perms.insert(perm.to_string());
+        }
+    }
+    Ok(rules)
+}
+
+/// Текст модуля CIL для `semodule -i <name>.cil`. Вывод детерминирован:
+/// правила и разрешения отсортированы, повторы отказов сливаются.
+pub fn emit_cil(module_name: &str, alerts: &[AVCAlert]) -> Result<String> {
+    let rules = allow_rules(alerts)?;
+    if rules.is_empty() {
+        return Err(anyhow!("No AVC denials to build module {} from", module_name));
+    }
+    let mut cil = format!("; {} generated by selab from {} AVC denial(s)\n", module_name, alerts.len());
+    for ((source, target, class), perms) in &rules {
+        cil.push_str(&format!(
+            "(allow {} {} ({} ({})))\n",
+            source,
+            target,
+            class,
+            perms.iter().cloned().collect::<Vec<_>>().join(" ")
+        ));
+    }
+    Ok(cil)
+}
+
+fn is_identifier(s: &str) -> bool {
+    !s.is_empty() && s.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::avc::AVCManager;
+
+    /// Прогоняет `tests/fixtures/cil/<case>.log` через `emit_cil` и сравнивает
+    /// побайтно с `<case>.cil`
+    fn assert_golden(case: &str, log: &str, expected: &str) {
+        let alerts = AVCManager::new().parse_avc_records(log);
+        assert!(!alerts.is_empty(), "{}: fixture has no AVC records", case);
+        assert_eq!(emit_cil(case, &alerts).unwrap(), expected, "{}: output differs from {}.cil", case, case);
+    }
+
+    #[test]
+    fn merges_and_orders_rules() {
+        assert_golden(
+            "merge",
+            include_str!("../tests/fixtures/cil/merge.log"),
+            include_str!("../tests/fixtures/cil/merge.cil"),
+        );
+    }
+
+    #[test]
+    fn same_type_target_becomes_self() {
+        assert_golden(
+            "self",
+            include_str!("../tests/fixtures/cil/self.log"),
+            include_str!("../tests/fixtures/cil/self.cil"),
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_identifiers() {
+        let base = AVCManager::new().parse_avc_records(include_str!("../tests/fixtures/cil/self.log")).remove(0);
+
+        let mut alert = base.clone();
+        alert.target_class = "file)) (allow unconfined_t".to_string();
+        let err = emit_cil("bad", &[alert]).unwrap_err();
+        assert_eq!(err.to_string(), r#"Refusing to emit CIL for invalid identifier "file)) (allow unconfined_t""#);
+
+        let mut alert = base.clone();
+        alert.source_context = "system_u:system_r:my-app_t:s0".to_string();
+        assert!(emit_cil("bad", &[alert]).is_err());
+
+        let mut alert = base;
+        alert.permission = "read (write)".to_string();
+        let err = emit_cil("bad", &[alert]).unwrap_err();
+        assert_eq!(err.to_string(), r#"Refusing to emit CIL for invalid permission "(write)""#);
+    }
+
+    #[test]
+    fn no_alerts_is_an_error() {
+        assert!(emit_cil("empty", &[]).is_err());
+    }
+}
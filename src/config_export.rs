@@ -4,8 +4,11 @@ use std::path::PathBuf;
 use anyhow::Result;
 use crate::booleans::BooleanManager;
 use crate::modules::ModuleManager;
-use crate::file_contexts::FileContextManager;
-use crate::ports::PortManager;
+use crate::file_contexts::{FcEquivalence, FcFileType, FileContextManager};
+use crate::ports::{PortManager, PortRange};
+use crate::rollback::shell_quote;
+use crate::users::{LoginMapping, SELinuxUser, UserManager};
+use crate::network::{InterfaceContext, InterfaceManager, NodeContext, NodeManager};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ConfigProfile {
@@ -15,12 +18,32 @@ pub struct ConfigProfile {
     pub booleans: Vec<(String, bool)>,
     pub modules: Vec<String>,
     pub file_contexts: Vec<(String, String)>,
+    /// Тип файла для правила с тем же индексом в `file_contexts`;
+    /// в старых профилях поля нет — тогда правило для всех файлов
+    #[serde(default)]
+    pub file_context_types: Vec<FcFileType>,
+    /// Локальные эквивалентности путей (`semanage fcontext -e`)
+    #[serde(default)]
+    pub fc_equivalences: Vec<FcEquivalence>,
     pub ports: Vec<(String, String, String)>, // port, protocol, context
+    /// Локальные пользователи SELinux (`semanage user`)
+    #[serde(default)]
+    pub selinux_users: Vec<SELinuxUser>,
+    /// Локальные сопоставления логинов (`semanage login`)
+    #[serde(default)]
+    pub logins: Vec<LoginMapping>,
+    /// Локальные контексты интерфейсов (`semanage interface`)
+    #[serde(default)]
+    pub interfaces: Vec<InterfaceContext>,
+    /// Локальные контексты узлов (`semanage node`)
+    #[serde(default)]
+    pub nodes: Vec<NodeContext>,
 }
 
 pub struct ConfigExporter;
 
 impl ConfigExporter {
+    #[allow(clippy::too_many_arguments)]
     pub fn export_profile(
         name: &str,
         description: &str,
@@ -28,23 +51,38 @@ impl ConfigExporter {
         module_manager: &ModuleManager,
         file_context_manager: &FileContextManager,
         port_manager: &PortManager,
+        user_manager: &UserManager,
+        interface_manager: &InterfaceManager,
+        node_manager: &NodeManager,
     ) -> Result<ConfigProfile> {
         let booleans: Vec<(String, bool)> = boolean_manager.booleans.iter()
             .map(|b| (b.name.clone(), b.current_value))
             .collect();
         
-        let modules: Vec<String> = module_manager.modules.iter()
+        let modules: Vec<String> = module_manager.active_modules()
             .filter(|m| m.enabled)
             .map(|m| m.name.clone())
             .collect();
         
-        let file_contexts: Vec<(String, String)> = file_context_manager.contexts.iter()
+        // Только локальные настройки — правила политики есть на любом хосте
+        let file_contexts: Vec<(String, String)> = file_context_manager.customizations()
             .map(|c| (c.path.clone(), c.context.clone()))
             .collect();
+        let file_context_types: Vec<FcFileType> = file_context_manager.customizations()
+            .map(|c| c.file_type)
+            .collect();
+        let fc_equivalences: Vec<FcEquivalence> = file_context_manager.local_equivalences()
+            .cloned()
+            .collect();
         
         let ports: Vec<(String, String, String)> = port_manager.ports.iter()
-            .map(|p| (p.port.clone(), p.protocol.clone(), p.context.clone()))
+            .map(|p| (p.port.to_string(), p.protocol.clone(), p.context.clone()))
             .collect();
+
+        let selinux_users: Vec<SELinuxUser> = user_manager.local_users().cloned().collect();
+        let logins: Vec<LoginMapping> = user_manager.local_logins().cloned().collect();
+        let interfaces: Vec<InterfaceContext> = interface_manager.interfaces.iter().filter(|i| i.local).cloned().collect();
+        let nodes: Vec<NodeContext> = node_manager.nodes.iter().filter(|n| n.local).cloned().collect();
         
         Ok(ConfigProfile {
             name: name.to_string(),
@@ -53,7 +91,13 @@ impl ConfigExporter {
             booleans,
             modules,
             file_contexts,
+            file_context_types,
+            fc_equivalences,
             ports,
+            selinux_users,
+            logins,
+            interfaces,
+            nodes,
         })
     }
     
@@ -69,12 +113,16 @@ impl ConfigExporter {
         Ok(profile)
     }
     
+    #[allow(clippy::too_many_arguments)]
     pub fn apply_profile(
         profile: &ConfigProfile,
         boolean_manager: &mut BooleanManager,
         module_manager: &mut ModuleManager,
         file_context_manager: &mut FileContextManager,
         port_manager: &mut PortManager,
+        user_manager: &mut UserManager,
+        interface_manager: &mut InterfaceManager,
+        node_manager: &mut NodeManager,
         simulation: bool,
     ) -> Result<Vec<String>> {
         let mut rollback_commands = Vec::new();
@@ -101,19 +149,67 @@ impl ConfigExporter {
         }
         
         // Применяем файловые контексты
-        for (path, context) in &profile.file_contexts {
-            if !file_context_manager.contexts.iter().any(|c| &c.path == path) {
-                rollback_commands.push(format!("semanage fcontext -d {}", path));
-                file_context_manager.add_file_context(path, context, simulation)?;
+        for (i, (path, context)) in profile.file_contexts.iter().enumerate() {
+            let file_type = profile.file_context_types.get(i).copied().unwrap_or_default();
+            if !file_context_manager.customizations().any(|c| &c.path == path && c.file_type == file_type) {
+                rollback_commands.push(format!("semanage fcontext -d -f {} {}", file_type.semanage_flag(), shell_quote(path)));
+                file_context_manager.add_file_context(path, context, file_type, simulation)?;
+            }
+        }
+        
+        // Применяем эквивалентности путей
+        for eq in &profile.fc_equivalences {
+            if !file_context_manager.local_equivalences().any(|e| e.path == eq.path) {
+                rollback_commands.push(format!("semanage fcontext -d {}", shell_quote(&eq.path)));
+                file_context_manager.add_equivalence(&eq.path, &eq.equal, simulation)?;
             }
         }
         
         // Применяем порты
         for (port, protocol, context) in &profile.ports {
-            if !port_manager.ports.iter().any(|p| &p.port == port && &p.protocol == protocol) {
-                rollback_commands.push(format!("semanage port -d -p {} {}", protocol, port));
-                port_manager.add_port(port, protocol, context, simulation)?;
+            let range: PortRange = port.parse()?;
+            if port_manager.find(&range, protocol).is_none() {
+                rollback_commands.push(format!("semanage port -d -p {} {}", protocol, range));
+                port_manager.add_port(&range, protocol, context, simulation)?;
+            }
+        }
+
+        // Пользователи SELinux — до логинов, которые на них ссылаются
+        for user in &profile.selinux_users {
+            match user_manager.find_user(&user.name) {
+                Some(old) if old.roles == user.roles && old.range == user.range => continue,
+                Some(old) => rollback_commands.push(old.semanage_command("-m")),
+                None => rollback_commands.push(format!("semanage user -d {}", shell_quote(&user.name))),
+            }
+            user_manager.set_user(&user.name, &user.roles, &user.range, simulation)?;
+        }
+
+        for login in &profile.logins {
+            match user_manager.find_login(&login.login) {
+                Some(old) if old.seuser == login.seuser && old.range == login.range => continue,
+                Some(old) => rollback_commands.push(old.semanage_command("-m")),
+                None => rollback_commands.push(format!("semanage login -d {}", shell_quote(&login.login))),
+            }
+            user_manager.set_login(&login.login, &login.seuser, &login.range, simulation)?;
+        }
+
+        // Интерфейсы и узлы
+        for iface in &profile.interfaces {
+            match interface_manager.find(&iface.name) {
+                Some(old) if old.context == iface.context => continue,
+                Some(old) => rollback_commands.push(old.semanage_command("-m")),
+                None => rollback_commands.push(iface.delete_command()),
+            }
+            interface_manager.set_interface(&iface.name, iface.context_type(), iface.range(), simulation)?;
+        }
+
+        for node in &profile.nodes {
+            match node_manager.find(&node.address, &node.netmask, &node.protocol) {
+                Some(old) if old.context == node.context => continue,
+                Some(old) => rollback_commands.push(old.semanage_command("-m")),
+                None => rollback_commands.push(node.delete_command()),
             }
+            node_manager.set_node(&node.address, &node.netmask, &node.protocol, node.context_type(), node.range(), simulation)?;
         }
         
         Ok(rollback_commands)
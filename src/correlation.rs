@@ -0,0 +1,175 @@
+use std::collections::HashMap;
+use std::fs;
+use std::process::Command;
+
+/// Происхождение процесса: systemd-юнит и контейнер.
+#[derive(Debug, Clone, Default)]
+pub struct ProcessOrigin {
+    pub unit: Option<String>,
+    pub container: Option<String>,
+}
+
+/// Сопоставляет AVC-отказы с systemd-юнитами и контейнерами.
+///
+/// Юнит берется из `/proc/<pid>/cgroup`, пока процесс жив, а для завершившихся
+/// процессов — из cgroup в записях аудита того же события. Контейнер определяется
+/// по cgroup (libpod/docker/crio) либо по MCS-категориям домена: у каждого
+/// контейнера своя пара категорий, поэтому уровень `s0:c123,c456` однозначно
+/// указывает на запущенный контейнер.
+pub struct Correlator {
+    /// MCS-уровень -> ID контейнера (по живым процессам)
+    mcs_to_container: HashMap<String, String>,
+    /// ID контейнера -> имя (podman/docker ps)
+    container_names: HashMap<String, String>,
+}
+
+impl Correlator {
+    /// Снимает текущее состояние процессов и контейнеров.
+    pub fn snapshot() -> Self {
+        let mut correlator = Self {
+            mcs_to_container: HashMap::new(),
+            container_names: HashMap::new(),
+        };
+        correlator.scan_processes();
+        correlator.load_container_names();
+        correlator
+    }
+
+    fn scan_processes(&mut self) {
+        let entries = match fs::read_dir("/proc") {
+            Ok(e) => e,
+            Err(_) => return,
+        };
+        for entry in entries.flatten() {
+            let pid = entry.file_name().to_string_lossy().to_string();
+            if !pid.chars().all(|c| c.is_ascii_digit()) {
+                continue;
+            }
+            let Ok(cgroup) = fs::read_to_string(entry.path().join("cgroup")) else {
+                continue;
+            };
+            let Some(container) = container_from_cgroup(&cgroup) else {
+                continue;
+            };
+            if let Ok(label) = fs::read_to_string(entry.path().join("attr/current")) {
+                if let Some(level) = mcs_level(label.trim_end_matches('\0').trim()) {
+                    self.mcs_to_container.entry(level).or_insert(container);
+                }
+            }
+        }
+    }
+
+    fn load_container_names(&mut self) {
+        for runtime in ["podman", "docker"] {
+            let output = match Command::new(runtime)
+                .args(["ps", "--no-trunc", "--format", "{{.ID}} {{.Names}}"])
+                .output()
+            {
+                Ok(o) if o.status.success() => o.stdout,
+                _ => continue,
+            };
+            for line in String::from_utf8_lossy(&output).lines() {
+                if let Some((id, name)) = line.split_once(' ') {
+                    self.container_names.insert(id.to_string(), name.trim().to_string());
+                }
+            }
+        }
+    }
+
+    /// Определяет юнит и контейнер для отказа. `comm` сверяется с живым
+    /// процессом, чтобы не приписать отказ процессу с переиспользованным PID.
+    pub fn origin(
+        &self,
+        pid: u32,
+        comm: &str,
+        source_context: &str,
+        audit_container: Option<&str>,
+        audit_unit: Option<&str>,
+    ) -> ProcessOrigin {
+        let mut origin = ProcessOrigin::default();
+
+        if pid != 0 && self.process_matches(pid, comm) {
+            if let Ok(cgroup) = fs::read_to_string(format!("/proc/{}/cgroup", pid)) {
+                origin.unit = unit_from_cgroup(&cgroup);
+                origin.container = container_from_cgroup(&cgroup);
+            }
+        }
+        if origin.unit.is_none() {
+            origin.unit = audit_unit.map(|u| u.to_string());
+        }
+        if origin.container.is_none() {
+            origin.container = audit_container.map(|c| c.to_string());
+        }
+        if origin.container.is_none() {
+            origin.container = mcs_level(source_context).and_then(|level| self.mcs_to_container.get(&level).cloned());
+        }
+
+        origin.container = origin.container.map(|id| self.display_container(&id));
+        origin
+    }
+
+    fn process_matches(&self, pid: u32, comm: &str) -> bool {
+        match fs::read_to_string(format!("/proc/{}/comm", pid)) {
+            Ok(live) => comm.is_empty() || live.trim() == comm,
+            Err(_) => false,
+        }
+    }
+
+    /// `имя (короткий id)`, если имя известно
+    fn display_container(&self, id: &str) -> String {
+        let short: String = id.chars().take(12).collect();
+        match self.container_names.get(id) {
+            Some(name) => format!("{} ({})", name, short),
+            None => short,
+        }
+    }
+}
+
+/// Имя юнита из содержимого `/proc/<pid>/cgroup`, например
+/// `0::/system.slice/httpd.service` -> `httpd.service`.
+pub fn unit_from_cgroup(cgroup: &str) -> Option<String> {
+    const SUFFIXES: [&str; 5] = [".service", ".scope", ".socket", ".mount", ".timer"];
+    cgroup.lines().find_map(|line| {
+        let path = line.splitn(3, ':').nth(2)?;
+        path.split('/')
+            .rev()
+            .find(|part| SUFFIXES.iter().any(|s| part.ends_with(s)))
+            .map(|s| s.to_string())
+    })
+}
+
+/// ID контейнера из cgroup: `libpod-<id>.scope`, `docker-<id>.scope`,
+/// `crio-<id>.scope` или `/docker/<id>`.
+pub fn container_from_cgroup(cgroup: &str) -> Option<String> {
+    for line in cgroup.lines() {
+        for part in line.split('/') {
+            let part = part.trim_end_matches(".scope");
+            for prefix in ["libpod-", "docker-", "crio-", "cri-containerd-"] {
+                if let Some(id) = part.strip_prefix(prefix) {
+                    if is_container_id(id) {
+                        return Some(id.to_string());
+                    }
+                }
+            }
+            if is_container_id(part) && line.contains("/docker/") {
+                return Some(part.to_string());
+            }
+        }
+    }
+    None
+}
+
+fn is_container_id(s: &str) -> bool {
+    s.len() >= 12 && s.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+/// MCS-уровень контекста, если в нем есть категории: `...:s0:c123,c456` -> `s0:c123,c456`.
+pub fn mcs_level(context: &str) -> Option<String> {
+    let mut parts = context.splitn(4, ':');
+    let level = parts.nth(3)?;
+    if level.contains('c') {
+        Some(level.to_string())
+    } else {
+        None
+    }
+}
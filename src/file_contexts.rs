@@ -1,57 +1,364 @@
 use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::ffi::CString;
 use std::process::Command;
-use anyhow::Result;
+use anyhow::{Result, anyhow};
 use regex::Regex;
+use crate::avc::AVCAlert;
+use crate::severity_rules::context_type;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Тип файла, к которому относится правило (`semanage fcontext -f`).
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub enum FcFileType {
+    #[default]
+    All,
+    Regular,
+    Directory,
+    Symlink,
+    Socket,
+    Pipe,
+    Block,
+    Char,
+}
+
+impl FcFileType {
+    /// Из колонки `type` вывода `semanage fcontext -l`
+    pub fn from_label(label: &str) -> Option<Self> {
+        match label {
+            "all files" => Some(Self::All),
+            "regular file" => Some(Self::Regular),
+            "directory" => Some(Self::Directory),
+            "symbolic link" => Some(Self::Symlink),
+            "socket" => Some(Self::Socket),
+            "named pipe" => Some(Self::Pipe),
+            "block device" => Some(Self::Block),
+            "character device" => Some(Self::Char),
+            _ => None,
+        }
+    }
+
+    /// Из аргумента `-f` (`a`, `f`, `d`, `l`, `s`, `p`, `b`, `c`) или флага file_contexts (`--`, `-d`, ...)
+    pub fn from_flag(flag: &str) -> Option<Self> {
+        match flag.trim_start_matches('-') {
+            "a" | "" => Some(Self::All),
+            "f" => Some(Self::Regular),
+            "d" => Some(Self::Directory),
+            "l" => Some(Self::Symlink),
+            "s" => Some(Self::Socket),
+            "p" => Some(Self::Pipe),
+            "b" => Some(Self::Block),
+            "c" => Some(Self::Char),
+            _ => None,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::All => "all files",
+            Self::Regular => "regular file",
+            Self::Directory => "directory",
+            Self::Symlink => "symbolic link",
+            Self::Socket => "socket",
+            Self::Pipe => "named pipe",
+            Self::Block => "block device",
+            Self::Char => "character device",
+        }
+    }
+
+    /// Значение для `semanage fcontext -f`
+    pub fn semanage_flag(&self) -> &'static str {
+        match self {
+            Self::All => "a",
+            Self::Regular => "f",
+            Self::Directory => "d",
+            Self::Symlink => "l",
+            Self::Socket => "s",
+            Self::Pipe => "p",
+            Self::Block => "b",
+            Self::Char => "c",
+        }
+    }
+
+    /// Обозначение в file_contexts (`None` — любой тип)
+    pub fn fc_flag(&self) -> Option<&'static str> {
+        match self {
+            Self::All => None,
+            Self::Regular => Some("--"),
+            Self::Directory => Some("-d"),
+            Self::Symlink => Some("-l"),
+            Self::Socket => Some("-s"),
+            Self::Pipe => Some("-p"),
+            Self::Block => Some("-b"),
+            Self::Char => Some("-c"),
+        }
+    }
+}
+
+/// Правило `semanage fcontext`. `path` — регулярное выражение пути,
+/// `context` — тип; `<<None>>` означает, что метка пути не управляется.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct FileContext {
     pub path: String,
+    #[serde(default)]
+    pub file_type: FcFileType,
+    #[serde(default)]
+    pub user: String,
+    #[serde(default)]
+    pub role: String,
     pub context: String,
+    #[serde(default)]
+    pub range: String,
+    /// Локальная настройка (`semanage fcontext -l -C`), а не правило политики
+    #[serde(default)]
+    pub local: bool,
+}
+
+impl FileContext {
+    /// Полный контекст `user:role:type:range`
+    pub fn full_context(&self) -> String {
+        if self.user.is_empty() {
+            return self.context.clone();
+        }
+        let mut ctx = format!("{}:{}:{}", self.user, self.role, self.context);
+        if !self.range.is_empty() {
+            ctx.push(':');
+            ctx.push_str(&self.range);
+        }
+        ctx
+    }
+}
+
+/// Файл, метка которого отличается от ожидаемой политикой (например,
+/// контент перенесен `mv` из /home в /var/www и сохранил `user_home_t`).
+#[derive(Debug, Clone)]
+pub struct Mislabel {
+    pub path: String,
+    pub current: String,
+    pub expected: String,
+}
+
+/// Правило эквивалентности (`semanage fcontext -a -e <equal> <path>`): файлы
+/// под `path` получают те же метки, что и соответствующие файлы под `equal`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FcEquivalence {
+    pub path: String,
+    pub equal: String,
+    #[serde(default)]
+    pub local: bool,
+}
+
+impl FcEquivalence {
+    /// Путь, по правилам которого метится `path`, если он лежит под `self.path`
+    pub fn substitute(&self, path: &str) -> Option<String> {
+        let rest = path.strip_prefix(self.path.as_str())?;
+        if rest.is_empty() || rest.starts_with('/') {
+            Some(format!("{}{}", self.equal, rest))
+        } else {
+            None
+        }
+    }
+}
+
+/// Применяет эквивалентности как libselinux: сначала локальные, затем
+/// к результату — правила дистрибутива. `None` — путь не затронут.
+pub fn apply_equivalences(equivalences: &[FcEquivalence], path: &str) -> Option<String> {
+    let local = equivalences.iter().filter(|e| e.local).find_map(|e| e.substitute(path));
+    let dist_input = local.as_deref().unwrap_or(path);
+    let dist = equivalences.iter().filter(|e| !e.local).find_map(|e| e.substitute(dist_input));
+    dist.or(local)
 }
 
 #[derive(Clone)]
 pub struct FileContextManager {
+    /// Вся база: правила политики и локальные настройки (`local == true`)
     pub contexts: Vec<FileContext>,
+    pub equivalences: Vec<FcEquivalence>,
+    /// Ожидаемые типы по путям: matchpathcon запускается один раз на путь,
+    /// а не на каждый отказ при каждом обновлении
+    expected_cache: RefCell<HashMap<String, Option<String>>>,
 }
 
 impl FileContextManager {
     pub fn new() -> Self {
-        Self { contexts: Vec::new() }
+        Self {
+            contexts: Vec::new(),
+            equivalences: Vec::new(),
+            expected_cache: RefCell::new(HashMap::new()),
+        }
+    }
+
+    /// Сбрасывает кэш ожидаемых типов — после любого изменения правил
+    pub fn invalidate_expected(&mut self) {
+        self.expected_cache.get_mut().clear();
     }
 
     pub fn load_file_contexts(&mut self) -> Result<()> {
         let output = Command::new("semanage")
-        .args(&["fcontext", "-l"])
+        .args(["fcontext", "-l"])
+        .output()?
+        .stdout;
+        let local_output = Command::new("semanage")
+        .args(["fcontext", "-l", "-C"])
         .output()?
         .stdout;
 
-        let logs = String::from_utf8_lossy(&output);
-        let re = Regex::new(r"^(\S+)\s+all files\s+system_u:object_r:(\S+):s0$")?;
+        let mut local = Self::parse_fcontext_list(&String::from_utf8_lossy(&local_output));
+        let mut contexts = Self::parse_fcontext_list(&String::from_utf8_lossy(&output));
+        for c in &mut contexts {
+            if let Some(i) = local.iter().position(|l| l.path == c.path && l.file_type == c.file_type) {
+                local.remove(i);
+                c.local = true;
+            }
+        }
+        // Локальные правила, которых нет в общем списке (например, переопределения <<None>>)
+        for mut c in local {
+            c.local = true;
+            contexts.push(c);
+        }
+        let equivalences = Self::parse_equivalences(&String::from_utf8_lossy(&output));
+        // Загрузка идет при каждом обновлении — кэш сбрасываем, только если база изменилась
+        if contexts != self.contexts || equivalences != self.equivalences {
+            self.contexts = contexts;
+            self.equivalences = equivalences;
+            self.invalidate_expected();
+        }
+        Ok(())
+    }
 
-        self.contexts.clear();
-        for line in logs.lines() {
-            if let Some(cap) = re.captures(line) {
-                let path = cap[1].to_string();
-                let context = cap[2].to_string();
+    /// Локальные настройки — правила, добавленные на этом хосте
+    pub fn customizations(&self) -> impl Iterator<Item = &FileContext> {
+        self.contexts.iter().filter(|c| c.local)
+    }
 
-                self.contexts.push(FileContext { path, context });
+    pub fn local_equivalences(&self) -> impl Iterator<Item = &FcEquivalence> {
+        self.equivalences.iter().filter(|e| e.local)
+    }
+
+    /// Секции `SELinux Distribution / Local fcontext Equivalence`: строки `path = equal`.
+    pub fn parse_equivalences(output: &str) -> Vec<FcEquivalence> {
+        let mut equivalences = Vec::new();
+        let mut local = false;
+        for line in output.lines() {
+            if line.contains("fcontext Equivalence") {
+                local = line.contains("Local");
+                continue;
+            }
+            if let [path, "=", equal] = line.split_whitespace().collect::<Vec<_>>().as_slice() {
+                equivalences.push(FcEquivalence {
+                    path: path.to_string(),
+                    equal: equal.to_string(),
+                    local,
+                });
             }
         }
+        equivalences
+    }
+
+    /// Добавляет эквивалентность: `path` метится так же, как `equal`.
+    pub fn add_equivalence(&mut self, path: &str, equal: &str, simulation: bool) -> Result<()> {
+        self.invalidate_expected();
+        if !path.starts_with('/') || !equal.starts_with('/') || path == "/" || path == equal {
+            return Err(anyhow!("Equivalence needs two different absolute paths other than /"));
+        }
+        if simulation {
+            self.equivalences.push(FcEquivalence {
+                path: path.to_string(),
+                equal: equal.to_string(),
+                local: true,
+            });
+            return Ok(());
+        }
+
+        let output = Command::new("semanage")
+        .args(["fcontext", "-a", "-e", equal, path])
+        .output()?;
+        if !output.status.success() {
+            return Err(anyhow!("semanage fcontext failed: {}", String::from_utf8_lossy(&output.stderr).trim()));
+        }
+
+        Command::new("restorecon")
+        .arg("-v")
+        .arg(path)
+        .output()?;
+
+        self.load_file_contexts()?;
+        Ok(())
+    }
+
+    pub fn remove_equivalence(&mut self, path: &str, simulation: bool) -> Result<()> {
+        self.invalidate_expected();
+        if simulation {
+            self.equivalences.retain(|e| !e.local || e.path != path);
+            return Ok(());
+        }
+
+        let output = Command::new("semanage")
+        .args(["fcontext", "-d", path])
+        .output()?;
+        if !output.status.success() {
+            return Err(anyhow!("semanage fcontext failed: {}", String::from_utf8_lossy(&output.stderr).trim()));
+        }
+
+        self.load_file_contexts()?;
         Ok(())
     }
 
-    pub fn add_file_context(&mut self, path: &str, context: &str, simulation: bool) -> Result<()> {
+    /// Разбирает вывод `semanage fcontext -l`: `regex  <тип файла>  user:role:type:range`.
+    /// Секции эквивалентностей (`/run = /var/run`) пропускаются.
+    pub fn parse_fcontext_list(output: &str) -> Vec<FileContext> {
+        lazy_static::lazy_static! {
+            static ref LINE_RE: Regex = Regex::new(
+                r"^(\S+)\s+(all files|regular file|directory|symbolic link|socket|named pipe|block device|character device)\s+(\S+)\s*$"
+            ).unwrap();
+        }
+        let mut contexts = Vec::new();
+        for line in output.lines() {
+            let Some(cap) = LINE_RE.captures(line) else {
+                continue;
+            };
+            let file_type = FcFileType::from_label(&cap[2]).unwrap_or_default();
+            let mut parts = cap[3].splitn(4, ':');
+            let (user, role, context, range) = match (parts.next(), parts.next(), parts.next(), parts.next()) {
+                (Some(u), Some(r), Some(t), range) => (u, r, t, range.unwrap_or("")),
+                // <<None>>
+                (Some(other), None, None, None) => ("", "", other, ""),
+                _ => continue,
+            };
+            contexts.push(FileContext {
+                path: cap[1].to_string(),
+                file_type,
+                user: user.to_string(),
+                role: role.to_string(),
+                context: context.to_string(),
+                range: range.to_string(),
+                local: false,
+            });
+        }
+        contexts
+    }
+
+    pub fn add_file_context(&mut self, path: &str, context: &str, file_type: FcFileType, simulation: bool) -> Result<()> {
+        self.invalidate_expected();
         if simulation {
             self.contexts.push(FileContext {
                 path: path.to_string(),
-                               context: context.to_string(),
+                file_type,
+                user: "system_u".to_string(),
+                role: "object_r".to_string(),
+                context: context.to_string(),
+                range: "s0".to_string(),
+                local: true,
             });
             return Ok(());
         }
 
-        Command::new("semanage")
-        .args(&["fcontext", "-a", "-t", context, path])
+        let output = Command::new("semanage")
+        .args(["fcontext", "-a", "-f", file_type.semanage_flag(), "-t", context, path])
         .output()?;
+        if !output.status.success() {
+            return Err(anyhow!("semanage fcontext failed: {}", String::from_utf8_lossy(&output.stderr).trim()));
+        }
 
         Command::new("restorecon")
         .arg("-v")
@@ -62,17 +369,181 @@ impl FileContextManager {
         Ok(())
     }
 
-    pub fn remove_file_context(&mut self, path: &str, simulation: bool) -> Result<()> {
+    /// Восстанавливает метки по базе file_contexts (`restorecon`).
+    pub fn restore(&self, path: &str, recursive: bool, simulation: bool) -> Result<()> {
         if simulation {
-            self.contexts.retain(|c| c.path != path);
             return Ok(());
         }
+        let mut cmd = Command::new("restorecon");
+        if recursive {
+            cmd.arg("-R");
+        }
+        let output = cmd.arg("-v").arg(path).output()?;
+        if !output.status.success() {
+            return Err(anyhow!("restorecon failed: {}", String::from_utf8_lossy(&output.stderr).trim()));
+        }
+        Ok(())
+    }
+
+    /// Тип, который политика ожидает для пути (`matchpathcon`). Если утилита
+    /// недоступна (или режим симуляции) — поиск по загруженным правилам.
+    pub fn expected_type(&self, path: &str, simulation: bool) -> Option<String> {
+        if let Some(cached) = self.expected_cache.borrow().get(path) {
+            return cached.clone();
+        }
+        let expected = self.lookup_expected(path, simulation);
+        self.expected_cache.borrow_mut().insert(path.to_string(), expected.clone());
+        expected
+    }
 
-        Command::new("semanage")
-        .args(&["fcontext", "-d", path])
+    fn lookup_expected(&self, path: &str, simulation: bool) -> Option<String> {
+        if !simulation {
+            if let Ok(output) = Command::new("matchpathcon").arg("-n").arg(path).output() {
+                let context = String::from_utf8_lossy(&output.stdout).trim().to_string();
+                if output.status.success() && context.contains(':') {
+                    return Some(context_type(&context).to_string());
+                }
+            }
+        }
+        self.lookup_local(path)
+    }
+
+    /// Как и в file_contexts, из подходящих правил побеждает самое конкретное —
+    /// с самым длинным префиксом без метасимволов.
+    fn lookup_local(&self, path: &str) -> Option<String> {
+        let path = apply_equivalences(&self.equivalences, path).unwrap_or_else(|| path.to_string());
+        let path = path.as_str();
+        let stem_len = |spec: &str| spec.find(['(', '[', '.', '*', '+', '?', '^', '$', '|', '\\']).unwrap_or(spec.len());
+        self.contexts
+            .iter()
+            .filter(|c| Regex::new(&format!("^(?:{})$", c.path)).is_ok_and(|re| re.is_match(path)))
+            .max_by_key(|c| stem_len(&c.path))
+            .map(|c| c.context.clone())
+    }
+
+    /// Сравнивает текущую метку пути из отказа с ожидаемой.
+    pub fn mislabel_of(&self, alert: &AVCAlert, simulation: bool) -> Option<Mislabel> {
+        if !alert.path.starts_with('/') {
+            return None;
+        }
+        // Если файл недоступен, tcontext отказа и есть его метка
+        let label = if simulation { None } else { file_label(&alert.path) };
+        let current = context_type(label.as_deref().unwrap_or(&alert.target_context)).to_string();
+        let expected = self.expected_type(&alert.path, simulation)?;
+        if expected == current {
+            return None;
+        }
+        Some(Mislabel {
+            path: alert.path.clone(),
+            current,
+            expected,
+        })
+    }
+
+    pub fn remove_file_context(&mut self, path: &str, file_type: FcFileType, simulation: bool) -> Result<()> {
+        self.invalidate_expected();
+        if simulation {
+            self.contexts.retain(|c| c.path != path || c.file_type != file_type);
+            return Ok(());
+        }
+
+        let output = Command::new("semanage")
+        .args(["fcontext", "-d", "-f", file_type.semanage_flag(), path])
         .output()?;
+        if !output.status.success() {
+            return Err(anyhow!("semanage fcontext failed: {}", String::from_utf8_lossy(&output.stderr).trim()));
+        }
 
         self.load_file_contexts()?;
         Ok(())
     }
 }
+
+/// Текущая метка файла из xattr `security.selinux` (ссылки не разыменовываются).
+pub fn file_label(path: &str) -> Option<String> {
+    let c_path = CString::new(path).ok()?;
+    let mut buf = vec![0u8; 256];
+    let len = unsafe {
+        libc::lgetxattr(
+            c_path.as_ptr(),
+            c"security.selinux".as_ptr(),
+            buf.as_mut_ptr() as *mut libc::c_void,
+            buf.len(),
+        )
+    };
+    if len <= 0 {
+        return None;
+    }
+    buf.truncate(len as usize);
+    Some(String::from_utf8_lossy(&buf).trim_end_matches('\0').to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Вывод `semanage fcontext -l`: все типы файлов, `<<None>>`, MLS-диапазоны,
+    /// контекст без диапазона и секции эквивалентностей
+    const LIST: &str = include_str!("../tests/fixtures/fcontext_list.txt");
+
+    #[test]
+    fn parses_every_file_type() {
+        let contexts = FileContextManager::parse_fcontext_list(LIST);
+        let types: Vec<(&str, FcFileType)> = contexts.iter().map(|c| (c.path.as_str(), c.file_type)).collect();
+        assert_eq!(
+            types,
+            [
+                ("/", FcFileType::Directory),
+                ("/etc/shadow.*", FcFileType::Regular),
+                ("/usr/lib/systemd/system(/.*)?", FcFileType::All),
+                ("/etc/localtime", FcFileType::Symlink),
+                ("/dev/log", FcFileType::Socket),
+                ("/dev/initctl", FcFileType::Pipe),
+                ("/dev/sd[^/]*", FcFileType::Block),
+                ("/dev/mem", FcFileType::Char),
+                ("/dev/pts/[0-9]+", FcFileType::Char),
+                ("/proc", FcFileType::Directory),
+                ("/var/www/html(/.*)?", FcFileType::All),
+            ]
+        );
+        assert!(contexts.iter().all(|c| !c.local));
+    }
+
+    #[test]
+    fn splits_context_and_mls_range() {
+        let contexts = FileContextManager::parse_fcontext_list(LIST);
+        let find = |path: &str| contexts.iter().find(|c| c.path == path).unwrap();
+
+        let shadow = find("/etc/shadow.*");
+        assert_eq!((shadow.user.as_str(), shadow.role.as_str()), ("system_u", "object_r"));
+        assert_eq!((shadow.context.as_str(), shadow.range.as_str()), ("shadow_t", "s0"));
+        assert_eq!(find("/dev/mem").range, "s15:c0.c1023");
+        assert_eq!(find("/dev/pts/[0-9]+").range, "s0-s15:c0.c1023");
+        // Политика без MLS — диапазона нет
+        let www = find("/var/www/html(/.*)?");
+        assert_eq!((www.user.as_str(), www.context.as_str(), www.range.as_str()), ("unconfined_u", "httpd_sys_content_t", ""));
+    }
+
+    #[test]
+    fn none_context_has_no_user_or_range() {
+        let contexts = FileContextManager::parse_fcontext_list(LIST);
+        let proc_ = contexts.iter().find(|c| c.path == "/proc").unwrap();
+        assert_eq!(proc_.context, "<<None>>");
+        assert_eq!((proc_.user.as_str(), proc_.role.as_str(), proc_.range.as_str()), ("", "", ""));
+    }
+
+    #[test]
+    fn header_and_equivalences_are_not_rules() {
+        let contexts = FileContextManager::parse_fcontext_list(LIST);
+        assert!(!contexts.iter().any(|c| c.path.starts_with("SELinux") || c.path == "/run"));
+        let equivalences = FileContextManager::parse_equivalences(LIST);
+        assert_eq!(
+            equivalences,
+            [
+                FcEquivalence { path: "/run".into(), equal: "/var/run".into(), local: false },
+                FcEquivalence { path: "/bin".into(), equal: "/usr/bin".into(), local: false },
+                FcEquivalence { path: "/srv/www".into(), equal: "/var/www".into(), local: true },
+            ]
+        );
+    }
+}
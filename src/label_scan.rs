@@ -0,0 +1,649 @@
+use std::collections::HashMap;
+use std::ffi::CString;
+use std::fs;
+use std::os::unix::fs::{FileTypeExt, MetadataExt};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Arc;
+use std::thread;
+use anyhow::{Result, anyhow};
+use regex::Regex;
+use crate::file_contexts::{apply_equivalences, file_label, FcEquivalence, FileContext};
+use crate::rollback::shell_quote;
+use crate::severity_rules::context_type;
+
+/// Метка для файлов без xattr — так их видит ядро.
+const UNLABELED: &str = "system_u:object_r:unlabeled_t:s0";
+
+/// Запись базы file_contexts: регулярное выражение пути, тип файла и контекст.
+#[derive(Debug, Clone)]
+pub struct FcSpec {
+    pub spec: String,
+    /// `--`, `-d`, `-l`, `-s`, `-p`, `-b`, `-c`; `None` — любой тип
+    pub file_type: Option<String>,
+    pub context: String,
+    regex: Regex,
+    /// Литеральный префикс выражения — быстрый отсев неподходящих правил
+    stem: String,
+}
+
+/// Сколько файлов проверяет предпросмотр нового правила, прежде чем остановиться
+const PREVIEW_LIMIT: usize = 50_000;
+
+/// База file_contexts активной политики. Порядок разрешения как в libselinux:
+/// правила без метасимволов важнее, среди остальных побеждает последнее подходящее.
+#[derive(Clone)]
+pub struct FileContextsDb {
+    regex_specs: Vec<FcSpec>,
+    exact_specs: HashMap<String, Vec<FcSpec>>,
+    /// Эквивалентности путей (`file_contexts.subs`, `.subs_dist`)
+    equivalences: Vec<FcEquivalence>,
+}
+
+impl FileContextsDb {
+    /// Читает файлы политики (`file_contexts`, `.homedirs`, `.local`, `.subs*`). Если их нет
+    /// (или режим симуляции) — строит базу из правил `semanage fcontext`.
+    pub fn load(rules: &[FileContext], equivalences: &[FcEquivalence], simulation: bool) -> Self {
+        let mut db = Self {
+            regex_specs: Vec::new(),
+            exact_specs: HashMap::new(),
+            equivalences: Vec::new(),
+        };
+        if !simulation {
+            let dir = policy_dir().join("contexts/files");
+            for name in ["file_contexts", "file_contexts.homedirs", "file_contexts.local"] {
+                if let Ok(data) = fs::read_to_string(dir.join(name)) {
+                    for line in data.lines() {
+                        db.add_line(line);
+                    }
+                }
+            }
+            for (name, local) in [("file_contexts.subs", true), ("file_contexts.subs_dist", false)] {
+                if let Ok(data) = fs::read_to_string(dir.join(name)) {
+                    db.equivalences.extend(data.lines().filter_map(|line| match line.split_whitespace().collect::<Vec<_>>().as_slice() {
+                        [path, equal] if !path.starts_with('#') => Some(FcEquivalence {
+                            path: path.to_string(),
+                            equal: equal.to_string(),
+                            local,
+                        }),
+                        _ => None,
+                    }));
+                }
+            }
+        }
+        if db.equivalences.is_empty() {
+            db.equivalences = equivalences.to_vec();
+        }
+        if db.is_empty() {
+            for rule in rules {
+                let context = if rule.context.eq_ignore_ascii_case("<<none>>") {
+                    "<<none>>".to_string()
+                } else if rule.user.is_empty() {
+                    format!("system_u:object_r:{}:s0", rule.context)
+                } else {
+                    rule.full_context()
+                };
+                db.add_spec(&rule.path, rule.file_type.fc_flag(), &context);
+            }
+        }
+        db
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.regex_specs.is_empty() && self.exact_specs.is_empty()
+    }
+
+    fn add_line(&mut self, line: &str) {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            return;
+        }
+        let parts: Vec<&str> = line.split_whitespace().collect();
+        match parts.as_slice() {
+            [spec, context] => self.add_spec(spec, None, context),
+            [spec, file_type, context] => self.add_spec(spec, Some(file_type), context),
+            _ => {}
+        }
+    }
+
+    fn add_spec(&mut self, spec: &str, file_type: Option<&str>, context: &str) {
+        let Ok(regex) = Regex::new(&format!("^(?:{})$", spec)) else {
+            return;
+        };
+        let entry = FcSpec {
+            spec: spec.to_string(),
+            file_type: file_type.map(|t| t.to_string()),
+            context: context.to_string(),
+            regex,
+            stem: literal_stem(spec),
+        };
+        if entry.stem.len() == spec.len() {
+            self.exact_specs.entry(spec.to_string()).or_default().push(entry);
+        } else {
+            self.regex_specs.push(entry);
+        }
+    }
+
+    /// Копия базы с новым локальным правилом — оно добавляется последним,
+    /// как правила из `file_contexts.local`.
+    pub fn with_rule(&self, spec: &str, file_type: Option<&str>, context: &str) -> Self {
+        let mut db = self.clone();
+        db.add_spec(spec, file_type, context);
+        db
+    }
+
+    /// Правило, которое определяет метку пути. `file_type` — как в file_contexts
+    /// (`--` для обычного файла, `-d` для каталога и т.д.).
+    pub fn lookup(&self, path: &str, file_type: &str) -> Option<&FcSpec> {
+        let substituted = apply_equivalences(&self.equivalences, path);
+        let path = substituted.as_deref().unwrap_or(path);
+        let type_ok = |s: &&FcSpec| s.file_type.as_deref().is_none_or(|t| t == file_type);
+        if let Some(spec) = self.exact_specs.get(path).and_then(|v| v.iter().rev().find(type_ok)) {
+            return Some(spec);
+        }
+        self.regex_specs
+            .iter()
+            .rev()
+            .filter(type_ok)
+            .find(|s| path.starts_with(&s.stem) && s.regex.is_match(path))
+    }
+}
+
+/// `SELINUXTYPE` из /etc/selinux/config (по умолчанию targeted)
+fn policy_dir() -> PathBuf {
+    let policy = fs::read_to_string("/etc/selinux/config")
+        .ok()
+        .and_then(|c| {
+            c.lines()
+                .find_map(|l| l.trim().strip_prefix("SELINUXTYPE=").map(|v| v.trim().to_string()))
+        })
+        .unwrap_or_else(|| "targeted".to_string());
+    PathBuf::from("/etc/selinux").join(policy)
+}
+
+/// Литеральная часть выражения до первого метасимвола. Символ перед `?`, `*`
+/// и `{` может отсутствовать в пути, поэтому в префикс он не входит.
+pub fn literal_stem(spec: &str) -> String {
+    let Some(pos) = spec.find(['.', '^', '$', '?', '*', '+', '|', '[', '(', '{', '\\']) else {
+        return spec.to_string();
+    };
+    let mut stem = spec[..pos].to_string();
+    if matches!(spec.as_bytes()[pos], b'?' | b'*' | b'{') {
+        stem.pop();
+    }
+    stem
+}
+
+/// Тип файла в обозначениях file_contexts
+pub fn fc_file_type(meta: &fs::Metadata) -> &'static str {
+    let ft = meta.file_type();
+    if ft.is_dir() {
+        "-d"
+    } else if ft.is_symlink() {
+        "-l"
+    } else if ft.is_socket() {
+        "-s"
+    } else if ft.is_fifo() {
+        "-p"
+    } else if ft.is_block_device() {
+        "-b"
+    } else if ft.is_char_device() {
+        "-c"
+    } else {
+        "--"
+    }
+}
+
+/// Файл, чья метка расходится с базой file_contexts.
+#[derive(Debug, Clone)]
+pub struct LabelMismatch {
+    pub path: String,
+    pub current: String,
+    pub expected: String,
+    /// Правило file_contexts, из которого взята ожидаемая метка
+    pub rule: String,
+    /// Выбран для восстановления
+    pub marked: bool,
+}
+
+impl LabelMismatch {
+    /// Новая метка как у restorecon без `-F`: меняется только тип
+    pub fn restored_label(&self) -> String {
+        let mut parts: Vec<&str> = self.current.splitn(4, ':').collect();
+        let expected_type = context_type(&self.expected);
+        if parts.len() >= 3 {
+            parts[2] = expected_type;
+            parts.join(":")
+        } else {
+            self.expected.clone()
+        }
+    }
+}
+
+enum ScanEvent {
+    Progress { done: usize, current: String },
+    Mismatch(LabelMismatch),
+    Restored { path: String, old: String },
+    Failed(String),
+    Finished,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ScanPhase {
+    Idle,
+    Scanning,
+    Restoring,
+}
+
+/// Итог восстановления меток — для записи в историю изменений
+pub struct RestoreSummary {
+    pub description: String,
+    pub rollback_commands: Vec<String>,
+}
+
+/// Поиск файлов с неверными метками и их выборочное восстановление.
+/// Обход и восстановление идут в фоновом потоке и могут быть прерваны.
+pub struct LabelScanner {
+    pub root: Option<String>,
+    pub mismatches: Vec<LabelMismatch>,
+    pub phase: ScanPhase,
+    /// Проверено файлов при последнем обходе
+    pub scanned: usize,
+    pub done: usize,
+    pub total: usize,
+    pub current: String,
+    pub errors: Vec<String>,
+    pub cancelled: bool,
+    restored: Vec<(String, String)>,
+    cancel: Arc<AtomicBool>,
+    rx: Option<Receiver<ScanEvent>>,
+}
+
+impl LabelScanner {
+    pub fn new() -> Self {
+        Self {
+            root: None,
+            mismatches: Vec::new(),
+            phase: ScanPhase::Idle,
+            scanned: 0,
+            done: 0,
+            total: 0,
+            current: String::new(),
+            errors: Vec::new(),
+            cancelled: false,
+            restored: Vec::new(),
+            cancel: Arc::new(AtomicBool::new(false)),
+            rx: None,
+        }
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.phase != ScanPhase::Idle
+    }
+
+    fn begin(&mut self, phase: ScanPhase, total: usize) -> (Sender<ScanEvent>, Arc<AtomicBool>) {
+        let (tx, rx) = mpsc::channel();
+        self.rx = Some(rx);
+        self.phase = phase;
+        self.done = 0;
+        self.total = total;
+        self.current.clear();
+        self.errors.clear();
+        self.cancelled = false;
+        self.cancel = Arc::new(AtomicBool::new(false));
+        (tx, self.cancel.clone())
+    }
+
+    /// Запускает обход дерева `root` (без выхода за пределы файловой системы).
+    pub fn start_scan(&mut self, root: &str, db: FileContextsDb) -> Result<()> {
+        if self.is_running() {
+            return Err(anyhow!("Label scan is already running"));
+        }
+        let root_meta = fs::symlink_metadata(root).map_err(|e| anyhow!("Cannot scan {}: {}", root, e))?;
+        self.root = Some(root.to_string());
+        self.mismatches.clear();
+        let (tx, cancel) = self.begin(ScanPhase::Scanning, 0);
+        let root = PathBuf::from(root);
+        thread::spawn(move || {
+            let mut done = 0;
+            scan_tree(&root, root_meta.dev(), &db, &tx, &cancel, &mut done);
+            let _ = tx.send(ScanEvent::Finished);
+        });
+        Ok(())
+    }
+
+    /// Восстанавливает метки отмеченных файлов. Старые метки запоминаются для отката.
+    pub fn start_restore(&mut self, simulation: bool) -> Result<()> {
+        if self.is_running() {
+            return Err(anyhow!("Label scan is already running"));
+        }
+        let items: Vec<LabelMismatch> = self.mismatches.iter().filter(|m| m.marked).cloned().collect();
+        if items.is_empty() {
+            return Err(anyhow!("No files marked for restore"));
+        }
+        self.restored.clear();
+        let (tx, cancel) = self.begin(ScanPhase::Restoring, items.len());
+        thread::spawn(move || {
+            for (i, item) in items.iter().enumerate() {
+                if cancel.load(Ordering::Relaxed) {
+                    break;
+                }
+                match set_label(&item.path, &item.restored_label(), simulation) {
+                    Ok(()) => {
+                        let _ = tx.send(ScanEvent::Restored {
+                            path: item.path.clone(),
+                            old: item.current.clone(),
+                        });
+                    }
+                    Err(e) => {
+                        let _ = tx.send(ScanEvent::Failed(format!("{}: {}", item.path, e)));
+                    }
+                }
+                let _ = tx.send(ScanEvent::Progress { done: i + 1, current: item.path.clone() });
+            }
+            let _ = tx.send(ScanEvent::Finished);
+        });
+        Ok(())
+    }
+
+    pub fn cancel(&mut self) {
+        if self.is_running() {
+            self.cancel.store(true, Ordering::Relaxed);
+            self.cancelled = true;
+        }
+    }
+
+    pub fn toggle_mark(&mut self, index: usize) {
+        if let Some(m) = self.mismatches.get_mut(index) {
+            m.marked = !m.marked;
+        }
+    }
+
+    /// Отмечает все, если хотя бы один не отмечен; иначе снимает отметки
+    pub fn toggle_all(&mut self) {
+        let mark = self.mismatches.iter().any(|m| !m.marked);
+        for m in &mut self.mismatches {
+            m.marked = mark;
+        }
+    }
+
+    /// Забирает события фонового потока. Возвращает итог, когда восстановление
+    /// завершилось (в том числе прерванное) и хотя бы одна метка изменена.
+    pub fn poll(&mut self) -> Option<RestoreSummary> {
+        let rx = self.rx.as_ref()?;
+        let mut finished = false;
+        for event in rx.try_iter() {
+            match event {
+                ScanEvent::Progress { done, current } => {
+                    if self.phase == ScanPhase::Scanning {
+                        self.scanned = done;
+                    }
+                    self.done = done;
+                    self.current = current;
+                }
+                ScanEvent::Mismatch(m) => self.mismatches.push(m),
+                ScanEvent::Restored { path, old } => self.restored.push((path, old)),
+                ScanEvent::Failed(e) => self.errors.push(e),
+                ScanEvent::Finished => finished = true,
+            }
+        }
+        if !finished {
+            return None;
+        }
+        let phase = self.phase;
+        self.phase = ScanPhase::Idle;
+        self.rx = None;
+        if phase != ScanPhase::Restoring || self.restored.is_empty() {
+            return None;
+        }
+
+        let restored: Vec<String> = self.restored.iter().map(|(p, _)| p.clone()).collect();
+        self.mismatches.retain(|m| !restored.contains(&m.path));
+        let rollback_commands = self
+            .restored
+            .iter()
+            .map(|(path, old)| format!("chcon -h {} {}", shell_quote(old), shell_quote(path)))
+            .collect();
+        Some(RestoreSummary {
+            description: format!(
+                "Restored labels of {} file(s) under {}{}",
+                restored.len(),
+                self.root.as_deref().unwrap_or("?"),
+                if self.cancelled { " (cancelled)" } else { "" }
+            ),
+            rollback_commands,
+        })
+    }
+}
+
+/// Предпросмотр нового правила fcontext: какое правило побеждает для пробного
+/// пути и сколько существующих файлов правило переметит.
+#[derive(Debug, Clone, Default)]
+pub struct RulePreview {
+    /// Правило, которое определит метку пробного пути после добавления
+    pub winner: Option<String>,
+    /// Файлы, для которых новое правило станет определяющим
+    pub matched: usize,
+    /// Из них — файлы, чья метка изменится
+    pub relabel: usize,
+    pub scanned: usize,
+    /// Обход остановлен на `PREVIEW_LIMIT` файлах
+    pub truncated: bool,
+    pub finished: bool,
+}
+
+/// Фоновый подсчет для предпросмотра правила. Новый запуск прерывает предыдущий.
+pub struct RulePreviewer {
+    /// Правило, для которого получен (или считается) результат
+    pub key: String,
+    pub result: Option<RulePreview>,
+    cancel: Arc<AtomicBool>,
+    rx: Option<Receiver<RulePreview>>,
+}
+
+impl RulePreviewer {
+    pub fn new() -> Self {
+        Self {
+            key: String::new(),
+            result: None,
+            cancel: Arc::new(AtomicBool::new(false)),
+            rx: None,
+        }
+    }
+
+    /// Считает правило `spec` (`file_type` — флаг file_contexts) с типом `context`.
+    /// `test_path` — путь, для которого показывается победившее правило.
+    pub fn start(&mut self, key: String, db: &FileContextsDb, spec: &str, file_type: Option<&str>, context: &str, test_path: &str) {
+        self.clear();
+        self.key = key;
+        let full_context = format!("system_u:object_r:{}:s0", context);
+        let db = db.with_rule(spec, file_type, &full_context);
+        let (tx, rx) = mpsc::channel();
+        self.rx = Some(rx);
+        let cancel = self.cancel.clone();
+        let spec = spec.to_string();
+        let file_type = file_type.map(|t| t.to_string());
+        let context = context.to_string();
+        let test_path = test_path.to_string();
+        thread::spawn(move || {
+            let test_type = fs::symlink_metadata(&test_path)
+                .map(|m| fc_file_type(&m))
+                .unwrap_or_else(|_| file_type.as_deref().unwrap_or("--"))
+                .to_string();
+            let mut preview = RulePreview {
+                winner: db.lookup(&test_path, &test_type).map(|s| s.spec.clone()),
+                ..Default::default()
+            };
+            let stem = literal_stem(&spec);
+            let root = match stem.rfind('/') {
+                Some(0) | None => "/".to_string(),
+                Some(pos) => stem[..pos].to_string(),
+            };
+            let Ok(root_meta) = fs::symlink_metadata(&root) else {
+                preview.finished = true;
+                let _ = tx.send(preview);
+                return;
+            };
+            let mut stack = vec![PathBuf::from(&root)];
+            while let Some(path) = stack.pop() {
+                if cancel.load(Ordering::Relaxed) {
+                    return;
+                }
+                if preview.scanned >= PREVIEW_LIMIT {
+                    preview.truncated = true;
+                    break;
+                }
+                let Ok(meta) = fs::symlink_metadata(&path) else {
+                    continue;
+                };
+                if meta.dev() != root_meta.dev() {
+                    continue;
+                }
+                let path_str = path.to_string_lossy().to_string();
+                preview.scanned += 1;
+                if path_str.starts_with(&stem) {
+                    let winner = db.lookup(&path_str, fc_file_type(&meta));
+                    if winner.is_some_and(|s| s.spec == spec && s.file_type == file_type) {
+                        preview.matched += 1;
+                        let current = file_label(&path_str).unwrap_or_else(|| UNLABELED.to_string());
+                        if context_type(&current) != context {
+                            preview.relabel += 1;
+                        }
+                    }
+                }
+                if preview.scanned.is_multiple_of(1000) {
+                    let _ = tx.send(preview.clone());
+                }
+                // В каталоги, которые не могут содержать подходящих путей, не заходим
+                if meta.is_dir() && (stem.starts_with(path_str.as_str()) || path_str.starts_with(&stem)) {
+                    if let Ok(entries) = fs::read_dir(&path) {
+                        stack.extend(entries.flatten().map(|e| e.path()));
+                    }
+                }
+            }
+            preview.finished = true;
+            let _ = tx.send(preview);
+        });
+    }
+
+    /// Прерывает подсчет и сбрасывает результат
+    pub fn clear(&mut self) {
+        self.cancel.store(true, Ordering::Relaxed);
+        self.cancel = Arc::new(AtomicBool::new(false));
+        self.rx = None;
+        self.key.clear();
+        self.result = None;
+    }
+
+    pub fn poll(&mut self) {
+        if let Some(rx) = &self.rx {
+            if let Some(last) = rx.try_iter().last() {
+                if last.finished {
+                    self.rx = None;
+                }
+                self.result = Some(last);
+            }
+        }
+    }
+}
+
+/// Файлы, чьи метки расходятся с базой (как `restorecon -n`). Без `recursive`
+/// проверяется только сам `root`.
+pub fn find_mismatches(root: &Path, db: &FileContextsDb, recursive: bool, cancel: &AtomicBool) -> Vec<LabelMismatch> {
+    let (tx, rx) = mpsc::channel();
+    match fs::symlink_metadata(root) {
+        Ok(meta) if recursive => {
+            let mut done = 0;
+            scan_tree(root, meta.dev(), db, &tx, cancel, &mut done);
+        }
+        Ok(meta) => check_path(&root.to_string_lossy(), &meta, db, &tx),
+        Err(_) => {}
+    }
+    drop(tx);
+    rx.into_iter()
+        .filter_map(|event| match event {
+            ScanEvent::Mismatch(m) => Some(m),
+            _ => None,
+        })
+        .collect()
+}
+
+fn scan_tree(
+    dir: &Path,
+    dev: u64,
+    db: &FileContextsDb,
+    tx: &Sender<ScanEvent>,
+    cancel: &AtomicBool,
+    done: &mut usize,
+) {
+    let mut stack = vec![dir.to_path_buf()];
+    while let Some(path) = stack.pop() {
+        if cancel.load(Ordering::Relaxed) {
+            return;
+        }
+        let Ok(meta) = fs::symlink_metadata(&path) else {
+            continue;
+        };
+        // Как restorecon -x: не переходим на другие файловые системы
+        if meta.dev() != dev {
+            continue;
+        }
+        let path_str = path.to_string_lossy().to_string();
+        check_path(&path_str, &meta, db, tx);
+
+        *done += 1;
+        if done.is_multiple_of(200) {
+            let _ = tx.send(ScanEvent::Progress { done: *done, current: path_str.clone() });
+        }
+        if meta.is_dir() {
+            if let Ok(entries) = fs::read_dir(&path) {
+                let mut children: Vec<PathBuf> = entries.flatten().map(|e| e.path()).collect();
+                children.sort_by(|a, b| b.cmp(a));
+                stack.extend(children);
+            }
+        }
+    }
+    let _ = tx.send(ScanEvent::Progress { done: *done, current: String::new() });
+}
+
+fn check_path(path: &str, meta: &fs::Metadata, db: &FileContextsDb, tx: &Sender<ScanEvent>) {
+    let Some(spec) = db.lookup(path, fc_file_type(meta)) else {
+        return;
+    };
+    // <<none>> — политика не управляет меткой этого пути
+    if spec.context == "<<none>>" {
+        return;
+    }
+    let current = file_label(path).unwrap_or_else(|| UNLABELED.to_string());
+    if context_type(&current) != context_type(&spec.context) {
+        let _ = tx.send(ScanEvent::Mismatch(LabelMismatch {
+            path: path.to_string(),
+            current,
+            expected: spec.context.clone(),
+            rule: spec.spec.clone(),
+            marked: true,
+        }));
+    }
+}
+
+fn set_label(path: &str, label: &str, simulation: bool) -> Result<()> {
+    if simulation {
+        return Ok(());
+    }
+    let c_path = CString::new(path)?;
+    let c_label = CString::new(label)?;
+    let rc = unsafe {
+        libc::lsetxattr(
+            c_path.as_ptr(),
+            c"security.selinux".as_ptr(),
+            c_label.as_ptr() as *const libc::c_void,
+            label.len() + 1,
+            0,
+        )
+    };
+    if rc != 0 {
+        return Err(anyhow!("{}", std::io::Error::last_os_error()));
+    }
+    Ok(())
+}
@@ -0,0 +1,263 @@
+use std::collections::HashMap;
+use std::fs;
+use std::net::{Ipv4Addr, Ipv6Addr};
+use anyhow::{Result, anyhow};
+use crate::ports::PortManager;
+use crate::remedy::port_type_for_domain;
+use crate::severity_rules::context_type;
+
+/// Типы "по умолчанию" — порт не закреплен ни за одной службой
+const GENERIC_PORT_TYPES: [&str; 5] = ["unreserved_port_t", "reserved_port_t", "hi_reserved_port_t", "ephemeral_port_t", "port_t"];
+
+/// Штатные типы портов, на которых служба слушает помимо "своего": named —
+/// канал управления rndc, sshd — проброс X11, httpd — прокси-порты вроде 8080
+const ALLOWED_SERVICE_PORTS: [(&str, &str); 3] = [
+    ("named_t", "rndc_port_t"),
+    ("sshd_t", "xserver_port_t"),
+    ("httpd_t", "http_cache_port_t"),
+];
+
+/// Домены, которым политика не ограничивает bind
+const UNCONFINED_DOMAINS: [&str; 5] = ["unconfined_t", "unconfined_service_t", "initrc_t", "kernel_t", "spc_t"];
+
+/// Слушающий сокет и процесс-владелец.
+#[derive(Debug, Clone)]
+pub struct Listener {
+    pub protocol: String,
+    pub address: String,
+    pub port: u16,
+    pub pid: Option<u32>,
+    pub process: String,
+    /// Домен процесса (`/proc/<pid>/attr/current`), пустой — неизвестен
+    pub context: String,
+}
+
+impl Listener {
+    pub fn domain(&self) -> &str {
+        context_type(&self.context)
+    }
+}
+
+/// Слушатель, чей порт помечен не так, как ожидает служба.
+#[derive(Debug, Clone)]
+pub struct PortFinding {
+    pub listener: Listener,
+    /// Текущий тип порта по политике
+    pub current: Option<String>,
+    /// Тип, который политика отводит домену службы
+    pub expected: Option<String>,
+    pub issue: String,
+}
+
+impl PortFinding {
+    /// Порт без типа или с общим типом — его можно отдать службе одним действием.
+    /// Порт другой службы так не переназначаем: это решение администратора.
+    pub fn reassignable(&self) -> bool {
+        self.current.as_deref().is_none_or(|t| GENERIC_PORT_TYPES.contains(&t))
+    }
+}
+
+/// Сокеты из `/proc/net/{tcp,tcp6,udp,udp6}`: TCP в состоянии LISTEN и
+/// несоединенные UDP вне эфемерного диапазона.
+pub fn read_listeners() -> Result<Vec<Listener>> {
+    let owners = socket_owners();
+    let ephemeral = ephemeral_range();
+    let mut listeners = Vec::new();
+    let mut readable = false;
+    for (file, protocol, state) in [("tcp", "tcp", "0A"), ("tcp6", "tcp", "0A"), ("udp", "udp", "07"), ("udp6", "udp", "07")] {
+        let Ok(data) = fs::read_to_string(format!("/proc/net/{}", file)) else {
+            continue;
+        };
+        readable = true;
+        for line in data.lines().skip(1) {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            if fields.len() < 10 || fields[3] != state {
+                continue;
+            }
+            let Some((address, port)) = parse_address(fields[1]) else {
+                continue;
+            };
+            if port == 0 || (protocol == "udp" && ephemeral.0 <= port && port <= ephemeral.1) {
+                continue;
+            }
+            let (pid, process, context) = match fields[9].parse::<u64>().ok().and_then(|inode| owners.get(&inode)) {
+                Some(&pid) => (Some(pid), read_proc(pid, "comm"), read_proc(pid, "attr/current")),
+                None => (None, String::new(), String::new()),
+            };
+            let listener = Listener { protocol: protocol.to_string(), address, port, pid, process, context };
+            // Сокет, открытый на v4 и v6, показываем один раз
+            if !listeners.iter().any(|l: &Listener| l.protocol == listener.protocol && l.port == port && l.pid == pid) {
+                listeners.push(listener);
+            }
+        }
+    }
+    if !readable {
+        return Err(anyhow!("/proc/net is not readable"));
+    }
+    listeners.sort_by(|a, b| a.protocol.cmp(&b.protocol).then(a.port.cmp(&b.port)));
+    Ok(listeners)
+}
+
+/// Слушатели на портах без типа, с общим типом (`unreserved_port_t` и т.п.)
+/// или с типом чужой службы. Известные штатные сочетания (`ALLOWED_SERVICE_PORTS`:
+/// named на `rndc_port_t`, sshd на `xserver_port_t`) не считаются ошибкой.
+pub fn check_listeners(listeners: &[Listener], port_manager: &PortManager) -> Vec<PortFinding> {
+    let mut findings = Vec::new();
+    for l in listeners {
+        let domain = l.domain();
+        if domain.is_empty() || UNCONFINED_DOMAINS.contains(&domain) {
+            continue;
+        }
+        let current = port_manager.type_of(l.port, &l.protocol).map(|p| p.context.clone());
+        let expected = port_type_for_domain(domain).map(|t| t.to_string());
+        let issue = match (&current, &expected) {
+            (Some(cur), Some(exp)) if cur == exp => continue,
+            (Some(cur), _) if ALLOWED_SERVICE_PORTS.contains(&(domain, cur.as_str())) => continue,
+            (Some(cur), Some(exp)) => format!("{} listens on {}, expected {}", domain, cur, exp),
+            (Some(cur), None) if GENERIC_PORT_TYPES.contains(&cur.as_str()) => {
+                format!("{} listens on {}, no service type assigned", domain, cur)
+            }
+            // Тип службы для домена неизвестен — о чужом типе судить не можем
+            (Some(_), None) => continue,
+            (None, _) => format!("{} listens on a port without type", domain),
+        };
+        findings.push(PortFinding { listener: l.clone(), current, expected, issue });
+    }
+    findings
+}
+
+/// `0100007F:1F90` -> ("127.0.0.1", 8080). Адрес записан 32-битными словами
+/// в порядке байт хоста.
+fn parse_address(field: &str) -> Option<(String, u16)> {
+    let (addr, port) = field.split_once(':')?;
+    let port = u16::from_str_radix(port, 16).ok()?;
+    let words: Vec<u32> = (0..addr.len() / 8)
+        .map(|i| u32::from_str_radix(&addr[i * 8..i * 8 + 8], 16))
+        .collect::<Result<_, _>>()
+        .ok()?;
+    let address = match words.as_slice() {
+        [v4] => Ipv4Addr::from(v4.to_ne_bytes()).to_string(),
+        [a, b, c, d] => {
+            let mut bytes = [0u8; 16];
+            for (i, w) in [a, b, c, d].iter().enumerate() {
+                bytes[i * 4..i * 4 + 4].copy_from_slice(&w.to_ne_bytes());
+            }
+            Ipv6Addr::from(bytes).to_string()
+        }
+        _ => return None,
+    };
+    Some((address, port))
+}
+
+/// inode сокета -> pid владельца (по ссылкам `/proc/<pid>/fd/*` = `socket:[inode]`)
+fn socket_owners() -> HashMap<u64, u32> {
+    let mut owners = HashMap::new();
+    let Ok(procs) = fs::read_dir("/proc") else {
+        return owners;
+    };
+    for entry in procs.flatten() {
+        let Some(pid) = entry.file_name().to_str().and_then(|s| s.parse::<u32>().ok()) else {
+            continue;
+        };
+        let Ok(fds) = fs::read_dir(entry.path().join("fd")) else {
+            continue;
+        };
+        for fd in fds.flatten() {
+            if let Ok(target) = fs::read_link(fd.path()) {
+                let target = target.to_string_lossy();
+                if let Some(inode) = target.strip_prefix("socket:[").and_then(|s| s.strip_suffix(']')) {
+                    if let Ok(inode) = inode.parse() {
+                        owners.entry(inode).or_insert(pid);
+                    }
+                }
+            }
+        }
+    }
+    owners
+}
+
+fn read_proc(pid: u32, name: &str) -> String {
+    fs::read_to_string(format!("/proc/{}/{}", pid, name))
+        .map(|s| s.trim_end_matches(['\0', '\n']).trim().to_string())
+        .unwrap_or_default()
+}
+
+/// Эфемерные порты (`ip_local_port_range`) — их занимают клиенты, а не службы
+fn ephemeral_range() -> (u16, u16) {
+    fs::read_to_string("/proc/sys/net/ipv4/ip_local_port_range")
+        .ok()
+        .and_then(|s| {
+            let mut it = s.split_whitespace().filter_map(|v| v.parse().ok());
+            Some((it.next()?, it.next()?))
+        })
+        .unwrap_or((32768, 60999))
+}
+
+/// Слушатели для режима симуляции
+pub fn simulation_listeners() -> Vec<Listener> {
+    [
+        ("tcp", "0.0.0.0", 22, 812, "sshd", "system_u:system_r:sshd_t:s0-s0:c0.c1023"),
+        ("tcp", "::", 8888, 1204, "httpd", "system_u:system_r:httpd_t:s0"),
+        ("tcp", "0.0.0.0", 3307, 1377, "mysqld", "system_u:system_r:mysqld_t:s0"),
+        ("tcp", "127.0.0.1", 9100, 1490, "node_exporter", "system_u:system_r:unconfined_service_t:s0"),
+        ("udp", "0.0.0.0", 53, 901, "named", "system_u:system_r:named_t:s0"),
+    ]
+    .into_iter()
+    .map(|(protocol, address, port, pid, process, context)| Listener {
+        protocol: protocol.to_string(),
+        address: address.to_string(),
+        port,
+        pid: Some(pid),
+        process: process.to_string(),
+        context: context.to_string(),
+    })
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn listener(port: u16, process: &str, domain: &str) -> Listener {
+        Listener {
+            protocol: "tcp".to_string(),
+            address: "0.0.0.0".to_string(),
+            port,
+            pid: Some(1000),
+            process: process.to_string(),
+            context: format!("system_u:system_r:{}:s0", domain),
+        }
+    }
+
+    #[test]
+    fn flags_untyped_generic_and_foreign_ports() {
+        let mut ports = PortManager::new();
+        ports.ports = PortManager::parse_port_list(
+            "http_port_t                    tcp      80, 443\n\
+             mysqld_port_t                  tcp      1186, 3306\n\
+             rndc_port_t                    tcp      953\n\
+             xserver_port_t                 tcp      6000-6020\n\
+             unreserved_port_t              tcp      1024-32767\n",
+        );
+        let listeners = [
+            listener(953, "named", "named_t"),
+            listener(6010, "sshd", "sshd_t"),
+            listener(80, "httpd", "httpd_t"),
+            listener(8888, "httpd", "httpd_t"),
+            listener(3306, "httpd", "httpd_t"),
+            listener(3306, "myapp", "myapp_t"),
+        ];
+        let findings = check_listeners(&listeners, &ports);
+        let flagged: Vec<(u16, &str)> = findings.iter().map(|f| (f.listener.port, f.issue.as_str())).collect();
+        assert_eq!(
+            flagged,
+            [
+                (8888, "httpd_t listens on unreserved_port_t, expected http_port_t"),
+                (3306, "httpd_t listens on mysqld_port_t, expected http_port_t"),
+            ]
+        );
+        // Порт службы mysqld одним действием не переназначается
+        assert!(findings[0].reassignable());
+        assert!(!findings[1].reassignable());
+    }
+}
@@ -9,14 +9,15 @@ use crossterm::{
 use ratatui::{
     backend::{Backend, CrosstermBackend},
     layout::{Alignment, Constraint, Direction, Layout, Rect},
-    style::{Color, Style},
+    style::{Color, Modifier, Style},
     text::{Line, Span},
-    widgets::{Block, Borders, Clear, List, ListItem, Paragraph, Tabs, Wrap},
+    widgets::{BarChart, Block, Borders, Clear, Gauge, List, ListItem, Paragraph, Sparkline, Tabs, Wrap},
     Frame, Terminal,
 };
 use std::{
     io,
-    path::PathBuf,
+    path::{Path, PathBuf},
+    process::Command,
     sync::mpsc::{self, Receiver},
     thread,
     time::{Duration, Instant},
@@ -25,31 +26,61 @@ use std::{
 // --- МОДУЛИ ---
 mod advisor;
 mod avc;
+mod avc_query;
+mod avc_source;
+mod avc_store;
 mod booleans;
 mod file_contexts;
 mod modules;
+mod cil_module;
+mod module_source;
+mod module_project;
+mod notifier;
 mod ports;
 mod rollback;
 mod safe_config;
 mod state;
+mod label_scan;
+mod listeners;
+mod users;
+mod network;
+mod relabel;
+mod remedy;
 mod stats;
+mod triage;
 mod config_export;
+mod correlation;
 mod logging;
 mod selinux_mode;
+mod severity_rules;
 
 use advisor::{Advisor, AutoRecommendation};
 use avc::AVCManager;
+use avc_query::AVCQuery;
+use avc_source::AVCSource;
+use avc_store::AVCStore;
 use booleans::BooleanManager;
-use file_contexts::{FileContext, FileContextManager};
-use modules::ModuleManager;
-use ports::{PortContext, PortManager};
-use rollback::{RollbackManager, SystemState};
+use file_contexts::{apply_equivalences, file_label, FcEquivalence, FcFileType, FileContext, FileContextManager};
+use modules::{ModuleFormat, ModuleManager, DEFAULT_PRIORITY};
+use module_source::ModuleSource;
+use module_project::{ModuleProject, Problem};
+use notifier::Notifier;
+use ports::{PortManager, PortPreflight, PortRange, PROTOCOLS};
+use rollback::{shell_quote, RollbackManager, SystemState};
 use safe_config::SafeModeConfig;
 use state::{AppState, CurrentView, InputMode, PopupType};
-use stats::{StatsManager, SystemStats};
+use stats::{StatsManager, SystemStats, TIMELINE_HOURS};
+use triage::{TriageManager, TriageState};
+use relabel::{RelabelPhase, Relabeler};
+use remedy::{Remedy, RemedyOption, RemedyPlanner};
+use listeners::{check_listeners, Listener};
+use users::{LoginMapping, SELinuxUser, UserEntry, UserManager};
+use network::{parse_cidr, InterfaceManager, NodeManager};
+use label_scan::{literal_stem, FileContextsDb, LabelScanner, RulePreviewer, ScanPhase};
 use config_export::ConfigExporter;
 use logging::Logger;
 use selinux_mode::{SELinuxMode, SELinuxModeManager};
+use severity_rules::context_type;
 
 // --- CLI ARGUMENTS ---
 #[derive(Parser)]
@@ -59,6 +90,27 @@ struct Cli {
     simulate: bool,
     #[arg(short, long)]
     logfile: Option<String>,
+    /// Источник AVC: auto, ausearch, audit-log, journal, file
+    #[arg(long, default_value = "auto")]
+    avc_source: String,
+    /// Начало диапазона AVC: boot, today, yesterday, -2h, 2024-01-15T10:00
+    #[arg(long)]
+    since: Option<String>,
+    /// Конец диапазона AVC (формат как у --since)
+    #[arg(long)]
+    until: Option<String>,
+    /// Фильтр AVC, например "stype:httpd_t perm:write path:/var/www/*"
+    #[arg(long)]
+    avc_query: Option<String>,
+    /// Экспортировать список игнорируемых/ожидаемых AVC в файл и выйти
+    #[arg(long)]
+    export_triage: Option<PathBuf>,
+    /// Импортировать список игнорируемых/ожидаемых AVC из файла и выйти
+    #[arg(long)]
+    import_triage: Option<PathBuf>,
+    /// Сколько дней хранить историю AVC в локальной базе
+    #[arg(long, default_value_t = 30)]
+    retention_days: i64,
     #[arg(short, long)]
     debug: bool,
     #[arg(long, default_value_t = 2)]
@@ -80,6 +132,9 @@ struct TaskResult {
 struct App {
     state: AppState,
     avc_manager: AVCManager,
+    avc_store: AVCStore,
+    notifier: Notifier,
+    triage_manager: TriageManager,
     module_manager: ModuleManager,
     boolean_manager: BooleanManager,
     rollback_manager: RollbackManager,
@@ -94,6 +149,39 @@ struct App {
     system_stats: SystemStats,
     avc_recommendations: Vec<AutoRecommendation>,
     avc_severity_filter: Option<avc::AVCSeverity>,
+    avc_grouping: avc::AVCGrouping,
+    avc_show_suppressed: bool,
+    /// Показывать всю базу fcontext, а не только локальные настройки
+    fc_show_all: bool,
+    /// Отказ и варианты его устранения для открытого окна выбора
+    remedy_alert: Option<avc::AVCAlert>,
+    user_manager: UserManager,
+    interface_manager: InterfaceManager,
+    node_manager: NodeManager,
+    /// Назначение типа порту, ожидающее подтверждения
+    pending_port: Option<PortPreflight>,
+    /// Открытый в окне исходник модуля и прокрутка
+    module_source: Option<ModuleSource>,
+    module_source_scroll: u16,
+    /// Извлечение модуля (`semodule -E`) в фоне
+    module_source_rx: Option<Receiver<Result<ModuleSource, String>>>,
+    /// Открытый проект модуля и найденные в нем ошибки
+    module_project: Option<ModuleProject>,
+    project_problems: Vec<Problem>,
+    /// Файл, который главный цикл откроет в $EDITOR
+    pending_edit: Option<PathBuf>,
+    remedy_options: Vec<RemedyOption>,
+    remedy_selected: usize,
+    label_scanner: LabelScanner,
+    /// База file_contexts для проверки пути в окне добавления правила
+    fc_db: Option<FileContextsDb>,
+    rule_preview: RulePreviewer,
+    relabeler: Relabeler,
+    /// Слушающие сокеты (вкладка портов в режиме 'L')
+    listeners: Vec<Listener>,
+    ports_show_listeners: bool,
+    /// Вкладка, для которой последний раз подгружались данные (`load_view_data`)
+    loaded_view: Option<CurrentView>,
 
     last_update: Instant,
     update_interval: Duration,
@@ -113,7 +201,7 @@ struct App {
 // --- ЛОГИКА ПРИЛОЖЕНИЯ ---
 
 impl App {
-    fn new(simulation: bool, debug: bool, update_interval_secs: u64, ascii_mode: bool) -> Result<Self> {
+    fn new(simulation: bool, debug: bool, update_interval_secs: u64, ascii_mode: bool, avc_source: AVCSource, avc_query: AVCQuery, retention_days: i64) -> Result<Self> {
         let logger = Logger::new();
         let log_path = logger.get_log_path().clone();
         let _ = logger.info(&format!("SELab started (simulation: {})", simulation));
@@ -131,6 +219,9 @@ impl App {
         let mut app = Self {
             state: AppState::new(),
             avc_manager: AVCManager::new(),
+            avc_store: AVCStore::new(retention_days),
+            notifier: Notifier::new(),
+            triage_manager: TriageManager::new(),
             module_manager: ModuleManager::new(),
             boolean_manager: BooleanManager::new(),
             rollback_manager: RollbackManager::new(),
@@ -146,6 +237,8 @@ impl App {
                 avc_by_severity: std::collections::HashMap::new(),
                 avc_by_permission: std::collections::HashMap::new(),
                 avc_by_source: std::collections::HashMap::new(),
+                avc_by_target: std::collections::HashMap::new(),
+                avc_timeline: Vec::new(),
                 total_booleans: 0,
                 booleans_changed: 0,
                 total_modules: 0,
@@ -153,9 +246,36 @@ impl App {
                 total_changes: 0,
                 recent_changes: Vec::new(),
                 risk_score: 0.0,
+                history_total: 0,
+                avc_suppressed: 0,
+                new_signatures_week: Vec::new(),
+                domain_trends: Vec::new(),
             },
             avc_recommendations: Vec::new(),
             avc_severity_filter: None,
+            avc_grouping: avc::AVCGrouping::None,
+            avc_show_suppressed: false,
+            fc_show_all: false,
+            remedy_alert: None,
+            user_manager: UserManager::new(),
+            interface_manager: InterfaceManager::new(),
+            node_manager: NodeManager::new(),
+            pending_port: None,
+            module_source: None,
+            module_source_scroll: 0,
+            module_source_rx: None,
+            module_project: None,
+            project_problems: Vec::new(),
+            pending_edit: None,
+            remedy_options: Vec::new(),
+            remedy_selected: 0,
+            label_scanner: LabelScanner::new(),
+            fc_db: None,
+            rule_preview: RulePreviewer::new(),
+            relabeler: Relabeler::new(),
+            listeners: Vec::new(),
+            ports_show_listeners: false,
+            loaded_view: None,
 
             last_update: Instant::now(),
             update_interval: Duration::from_secs(update_interval_secs.max(1)),
@@ -174,6 +294,8 @@ impl App {
         if debug {
             app.logfile_path = Some(PathBuf::from("selab_debug.log"));
         }
+        app.avc_manager.set_source(avc_source);
+        app.avc_manager.query = avc_query;
 
         app.refresh_data()?;
         app.update_stats();
@@ -187,11 +309,29 @@ impl App {
             &self.boolean_manager,
             &self.module_manager,
             &self.rollback_manager,
+            &self.avc_store,
+            &self.triage_manager,
         );
     }
     
     fn update_recommendations(&mut self) {
-        self.avc_recommendations = self.advisor.analyze_avc_alerts(&self.avc_manager.alerts);
+        let active: Vec<avc::AVCAlert> = self
+            .avc_manager
+            .alerts
+            .iter()
+            .filter(|a| !self.triage_manager.is_suppressed(a))
+            .cloned()
+            .collect();
+        let mut mislabels: Vec<file_contexts::Mislabel> = Vec::new();
+        for alert in &active {
+            if mislabels.iter().any(|m| m.path == alert.path) {
+                continue;
+            }
+            if let Some(m) = self.file_context_manager.mislabel_of(alert, self.simulation_mode) {
+                mislabels.push(m);
+            }
+        }
+        self.avc_recommendations = self.advisor.analyze_avc_alerts(&active, &mislabels);
     }
 
     // Запуск задачи в отдельном потоке (чтобы UI не зависал)
@@ -232,34 +372,213 @@ impl App {
         });
     }
 
+    /// Данные, которые нужны только одной вкладке: их не перечитываем на каждом
+    /// такте, а загружаем при входе на вкладку, по 'R' и после изменений.
+    fn load_view_data(&mut self) {
+        self.loaded_view = Some(self.state.current_view);
+        if self.simulation_mode {
+            return;
+        }
+        let result = match self.state.current_view {
+            CurrentView::Users => self.user_manager.load(),
+            CurrentView::Network => self
+                .interface_manager
+                .load_interfaces()
+                .and_then(|_| self.node_manager.load_nodes()),
+            _ => Ok(()),
+        };
+        if let Err(e) = result {
+            let _ = self.logger.error(&format!("Failed to load view data: {}", e));
+            self.set_status(format!("Error: {}", e), Color::Red);
+        }
+    }
+
+    /// Экспорт и импорт работают со всей конфигурацией, а не с одной вкладкой
+    fn load_all_view_data(&mut self) -> Result<()> {
+        if !self.simulation_mode {
+            self.user_manager.load()?;
+            self.interface_manager.load_interfaces()?;
+            self.node_manager.load_nodes()?;
+        }
+        Ok(())
+    }
+
     fn refresh_data(&mut self) -> Result<()> {
         if self.simulation_mode {
             self.load_simulation_data()?;
         } else {
             // В реальном режиме загрузка логов может занимать время.
             // Для простоты инициализация остается синхронной.
-            let _ = self.avc_manager.load_avc_logs();
+            if self.avc_manager.load_avc_logs().is_ok() {
+                self.notify_new_denials();
+                // Симуляционные данные в базу не пишем — только реальные отказы
+                if let Err(e) = self.avc_store.ingest(&self.avc_manager.alerts) {
+                    let _ = self.logger.error(&format!("Failed to store AVC history: {}", e));
+                }
+                let _ = self.avc_store.prune();
+            }
             let _ = self.module_manager.load_modules();
             let _ = self.boolean_manager.load_booleans();
             let _ = self.file_context_manager.load_file_contexts();
             let _ = self.port_manager.load_ports();
         }
+        if let Some(err) = self.avc_manager.severity_rules.take_error() {
+            let _ = self.logger.error(&format!("Severity rules: {}", err));
+            self.set_status(format!("Severity rules not loaded: {}", err), Color::Red);
+        }
+        if self.ports_show_listeners {
+            self.refresh_listeners();
+        }
         Ok(())
     }
 
+    fn refresh_listeners(&mut self) {
+        if self.simulation_mode {
+            self.listeners = listeners::simulation_listeners();
+            return;
+        }
+        match listeners::read_listeners() {
+            Ok(list) => self.listeners = list,
+            Err(e) => self.set_status(format!("Error: {}", e), Color::Red),
+        }
+    }
+
+    /// Присваивает порту слушателя тип, который политика отводит его службе
+    fn label_listener_port(&mut self, listener: Listener) {
+        let findings = check_listeners(std::slice::from_ref(&listener), &self.port_manager);
+        let Some(finding) = findings.first() else {
+            self.set_status(format!("{}/{} already has the expected type", listener.port, listener.protocol), Color::Green);
+            return;
+        };
+        let Some(expected) = finding.expected.clone() else {
+            self.set_status(
+                format!("No known port type for {}; use the AVC remedy chooser for its name_bind denial", listener.domain()),
+                Color::Yellow,
+            );
+            return;
+        };
+        if !finding.reassignable() {
+            self.set_status(
+                format!(
+                    "{}/{} belongs to {}; move the service or change the type in Ports (E)",
+                    listener.port,
+                    listener.protocol,
+                    finding.current.as_deref().unwrap_or("another service")
+                ),
+                Color::Yellow,
+            );
+            return;
+        }
+        let preflight = self
+            .port_manager
+            .preflight(&PortRange::single(listener.port), &listener.protocol, &expected);
+        self.assign_port_type(preflight);
+    }
+
+    /// Назначает тип порту после проверки: если порт уже описан или это
+    /// стандартный порт службы, сначала спрашиваем подтверждение
+    fn assign_port_type(&mut self, preflight: PortPreflight) {
+        if preflight.is_noop() {
+            self.set_status(
+                format!("{}/{} is already {}", preflight.port, preflight.protocol, preflight.context),
+                Color::Green,
+            );
+        } else if preflight.needs_confirmation() {
+            self.confirm_port_assignment(preflight);
+        } else {
+            self.run_port_assignment(preflight);
+        }
+    }
+
+    fn confirm_port_assignment(&mut self, preflight: PortPreflight) {
+        self.pending_port = Some(preflight);
+        self.state.popup_type = PopupType::PortConfirm;
+        self.state.input_mode = InputMode::Editing;
+    }
+
+    fn run_port_assignment(&mut self, preflight: PortPreflight) {
+        let mut mgr = self.port_manager.clone();
+        let sim = self.simulation_mode;
+        let message = if preflight.existing.is_some() { "Modifying Port..." } else { "Adding Port..." };
+        self.spawn_task(message, move || mgr.assign(&preflight, sim));
+    }
+
+    /// Уведомляет о High-отказах и новых сигнатурах, которых еще нет в базе.
+    /// Вызывается до сохранения алертов в базу.
+    fn notify_new_denials(&mut self) {
+        if !self.notifier.is_enabled() {
+            return;
+        }
+        // При пустой базе (первый запуск) все сигнатуры "новые" — не спамим
+        let has_history = !self.avc_store.records.is_empty();
+        let denials: Vec<_> = self
+            .avc_manager
+            .alerts
+            .iter()
+            .filter(|a| !self.avc_store.contains(a))
+            .filter_map(|a| {
+                let new_signature = has_history && !self.avc_store.knows_signature(a);
+                self.notifier.classify(a, new_signature).map(|r| (a.clone(), r))
+            })
+            .collect();
+        if !denials.is_empty() {
+            let _ = self.logger.info(&format!("Notifying about {} new denial(s)", denials.len()));
+            self.notifier.dispatch(&denials);
+        }
+    }
+
     fn load_simulation_data(&mut self) -> Result<()> {
         self.avc_manager.load_simulation_data();
         self.module_manager.load_simulation_data();
         self.boolean_manager.load_simulation_data();
-        self.file_context_manager.contexts = vec![FileContext {
-            path: "/var/www".into(),
-            context: "httpd_sys_content_t".into(),
-        }];
-        self.port_manager.ports = vec![PortContext {
-            port: "80".into(),
-            protocol: "tcp".into(),
-            context: "http_port_t".into(),
-        }];
+        let contexts: Vec<FileContext> = [
+            ("/etc(/.*)?", FcFileType::All, "etc_t", false),
+            ("/home/[^/]+(/.*)?", FcFileType::All, "user_home_t", false),
+            ("/var/www(/.*)?", FcFileType::All, "httpd_sys_content_t", true),
+        ]
+        .into_iter()
+        .map(|(path, file_type, context, local)| FileContext {
+            path: path.into(),
+            file_type,
+            user: "system_u".into(),
+            role: "object_r".into(),
+            context: context.into(),
+            range: "s0".into(),
+            local,
+        })
+        .collect();
+        let equivalences = vec![
+            FcEquivalence { path: "/run".into(), equal: "/var/run".into(), local: false },
+            FcEquivalence { path: "/srv/www".into(), equal: "/var/www".into(), local: true },
+        ];
+        if contexts != self.file_context_manager.contexts || equivalences != self.file_context_manager.equivalences {
+            self.file_context_manager.contexts = contexts;
+            self.file_context_manager.equivalences = equivalences;
+            self.file_context_manager.invalidate_expected();
+        }
+        self.port_manager.ports = PortManager::parse_port_list(
+            "http_port_t                    tcp      80, 81, 443, 488, 8008, 8009, 8443, 9000\n\
+             ssh_port_t                     tcp      22\n\
+             dns_port_t                     udp      53, 853\n\
+             unreserved_port_t              tcp      61000-65535, 1024-32767\n\
+             unreserved_port_t              sctp     1024-65535\n",
+        );
+        self.user_manager.load_simulation_data();
+        self.interface_manager.interfaces = InterfaceManager::parse_interface_list(
+            "eth1                           system_u:object_r:netif_t:s0\n",
+        );
+        self.node_manager.nodes = NodeManager::parse_node_list(
+            "127.0.0.1          255.255.255.255    ipv4  system_u:object_r:lo_node_t:s0\n\
+             ::1                ffff:ffff:ffff:ffff:ffff:ffff:ffff:ffff ipv6  system_u:object_r:lo_node_t:s0\n\
+             224.0.0.0          240.0.0.0          ipv4  system_u:object_r:multicast_node_t:s0\n\
+             10.20.0.0          255.255.0.0        ipv4  system_u:object_r:node_t:s0\n",
+        );
+        for i in &mut self.interface_manager.interfaces {
+            i.local = true;
+        }
+        if let Some(n) = self.node_manager.nodes.iter_mut().find(|n| n.address == "10.20.0.0") {
+            n.local = true;
+        }
         Ok(())
     }
 
@@ -276,11 +595,62 @@ impl App {
             return Ok(());
         }
 
+        // restorecon можно только прервать
+        if self.relabeler.is_running() {
+            match key {
+                KeyCode::Char('q') => self.should_quit = true,
+                KeyCode::Char('c') | KeyCode::Esc => {
+                    self.relabeler.cancel();
+                    self.set_status("Cancelling restorecon...".into(), Color::Yellow);
+                }
+                _ => {}
+            }
+            return Ok(());
+        }
+
         // 2. Режим ввода текста (Add / Search)
         if self.state.input_mode != InputMode::Normal {
             match key {
                 KeyCode::Enter => self.submit_input()?,
+                KeyCode::Esc if self.state.popup_type == PopupType::PortConfirm => {
+                    self.pending_port = None;
+                    self.state.reset_mode();
+                    self.set_status("Port change cancelled".into(), Color::Yellow);
+                }
+                KeyCode::Esc if self.state.popup_type == PopupType::RelabelConfirm => {
+                    self.relabeler.discard_preview();
+                    self.state.reset_mode();
+                    self.set_status("Relabel cancelled".into(), Color::Yellow);
+                }
                 KeyCode::Esc => self.state.reset_mode(),
+                KeyCode::Up if self.state.popup_type == PopupType::RemedyChooser => {
+                    self.remedy_selected = self.remedy_selected.saturating_sub(1);
+                }
+                KeyCode::Down if self.state.popup_type == PopupType::RemedyChooser => {
+                    self.remedy_selected = (self.remedy_selected + 1).min(self.remedy_options.len().saturating_sub(1));
+                }
+                KeyCode::Char(c) if self.state.popup_type == PopupType::ModuleProject => match c {
+                    'e' => self.edit_project_file("te"),
+                    'f' => self.edit_project_file("fc"),
+                    'i' => self.edit_project_file("if"),
+                    c if c.is_ascii_digit() => {
+                        self.state.input_buffer.push(c);
+                        self.state.input_cursor_position += 1;
+                    }
+                    _ => {}
+                },
+                KeyCode::Up if self.state.popup_type == PopupType::ModuleSource => {
+                    self.module_source_scroll = self.module_source_scroll.saturating_sub(1);
+                }
+                KeyCode::Down if self.state.popup_type == PopupType::ModuleSource => {
+                    self.module_source_scroll = self.module_source_scroll.saturating_add(1);
+                }
+                KeyCode::PageUp if self.state.popup_type == PopupType::ModuleSource => {
+                    self.module_source_scroll = self.module_source_scroll.saturating_sub(20);
+                }
+                KeyCode::PageDown if self.state.popup_type == PopupType::ModuleSource => {
+                    self.module_source_scroll = self.module_source_scroll.saturating_add(20);
+                }
                 KeyCode::Char(c) => {
                     self.state.input_buffer.push(c);
                     self.state.input_cursor_position += 1;
@@ -301,7 +671,13 @@ impl App {
         match key {
             KeyCode::Char('q') => self.should_quit = true,
             KeyCode::Char('?') => self.show_help_popup(),
-            KeyCode::Char('/') => self.state.enter_search_mode(),
+            KeyCode::Char('/') => {
+                if self.state.current_view == CurrentView::AVCAlerts {
+                    self.show_avc_query_popup();
+                } else {
+                    self.state.enter_search_mode();
+                }
+            }
             KeyCode::Char('a') => self.show_add_popup(),
             KeyCode::Enter => self.execute_current_selection()?,
 
@@ -314,6 +690,7 @@ impl App {
             KeyCode::Char('s') => self.apply_safe_settings_async()?,
             KeyCode::Char('R') => {
                 self.refresh_data()?;
+                self.load_view_data();
                 self.update_stats();
                 self.update_recommendations();
                 self.set_status("Data refreshed".into(), Color::Green);
@@ -322,6 +699,91 @@ impl App {
             KeyCode::Char('i') => self.show_import_popup(),
             KeyCode::Char('v') => self.show_detail_view(),
             KeyCode::Char('f') => self.toggle_avc_filter(),
+            KeyCode::Char('g') => self.toggle_avc_grouping(),
+            KeyCode::Char('t') => self.cycle_avc_triage(),
+            KeyCode::Char('T') if self.state.current_view == CurrentView::AVCAlerts => {
+                self.state.enter_input_mode(PopupType::IgnoreReason);
+            }
+            KeyCode::Char('H') if self.state.current_view == CurrentView::AVCAlerts => {
+                self.avc_show_suppressed = !self.avc_show_suppressed;
+                let text = if self.avc_show_suppressed { "shown" } else { "hidden" };
+                self.set_status(format!("Ignored/expected AVC {}", text), Color::Cyan);
+            }
+            KeyCode::Char('H') if self.state.current_view == CurrentView::FileContexts => {
+                self.fc_show_all = !self.fc_show_all;
+                self.state.list_state.select(Some(0));
+                let text = if self.fc_show_all { "full database" } else { "local customizations" };
+                self.set_status(format!("File contexts: {}", text), Color::Cyan);
+            }
+            KeyCode::Char('*') if self.state.current_view == CurrentView::LabelScan => {
+                self.label_scanner.toggle_all();
+            }
+            KeyCode::Char('x') if self.state.current_view == CurrentView::FileContexts => self.show_relabel_popup(),
+            KeyCode::Char('L') if self.state.current_view == CurrentView::Ports => {
+                self.ports_show_listeners = !self.ports_show_listeners;
+                self.state.list_state.select(Some(0));
+                if self.ports_show_listeners {
+                    self.refresh_listeners();
+                }
+            }
+            KeyCode::Char('E') if self.state.current_view == CurrentView::Ports && !self.ports_show_listeners => {
+                let selected = self.state.selected_index.unwrap_or(0);
+                let prefill = self
+                    .port_manager
+                    .ports
+                    .get(selected)
+                    .map(|p| format!("{} {} {}", p.port, p.protocol, p.context))
+                    .unwrap_or_default();
+                self.state.enter_input_mode(PopupType::ModifyPort);
+                self.state.input_buffer = prefill;
+                self.state.input_cursor_position = self.state.input_buffer.len();
+            }
+            KeyCode::Char('E') if self.state.current_view == CurrentView::Users => {
+                let selected = self.state.selected_index.unwrap_or(0);
+                let logins = &self.user_manager.logins;
+                let prefill = match logins.get(selected) {
+                    Some(l) => format!("{} {} {}", l.login, l.seuser, l.range),
+                    None => self
+                        .user_manager
+                        .users
+                        .get(selected - logins.len())
+                        .map(|u| format!("user {} {} {}", u.name, u.roles.join(","), u.range))
+                        .unwrap_or_default(),
+                };
+                self.state.enter_input_mode(PopupType::UserMapping);
+                self.state.input_buffer = prefill.trim_end().to_string();
+                self.state.input_cursor_position = self.state.input_buffer.len();
+            }
+            KeyCode::Char('E') if self.state.current_view == CurrentView::Network => {
+                let selected = self.state.selected_index.unwrap_or(0);
+                let interfaces = &self.interface_manager.interfaces;
+                let prefill = match interfaces.get(selected) {
+                    Some(i) => format!("{} {} {}", i.name, i.context_type(), i.range()),
+                    None => self
+                        .node_manager
+                        .nodes
+                        .get(selected - interfaces.len())
+                        .map(|n| format!("{} {} {}", n.cidr(), n.context_type(), n.range()))
+                        .unwrap_or_default(),
+                };
+                self.state.enter_input_mode(PopupType::NetworkContext);
+                self.state.input_buffer = prefill.trim_end().to_string();
+                self.state.input_cursor_position = self.state.input_buffer.len();
+            }
+            KeyCode::Char('D') if self.state.current_view == CurrentView::ModuleManager => self.remove_selected_module(),
+            KeyCode::Char('P') if self.state.current_view == CurrentView::ModuleManager => {
+                self.state.enter_input_mode(PopupType::ModuleProjectName);
+            }
+            KeyCode::Char('x') if self.state.current_view == CurrentView::LabelScan => {
+                match self.label_scanner.start_restore(self.simulation_mode) {
+                    Ok(()) => self.set_status("Restoring labels... ('c' to cancel)".into(), Color::Cyan),
+                    Err(e) => self.set_status(format!("Error: {}", e), Color::Red),
+                }
+            }
+            KeyCode::Char('c') if self.state.current_view == CurrentView::LabelScan && self.label_scanner.is_running() => {
+                self.label_scanner.cancel();
+                self.set_status("Cancelling...".into(), Color::Yellow);
+            }
             KeyCode::Char('A') => self.show_avc_recommendations(),
             KeyCode::Char('m') => self.show_create_module_popup(),
             KeyCode::Char('M') => self.toggle_selinux_mode(),
@@ -349,16 +811,89 @@ impl App {
         Ok(())
     }
 
+    /// Окно restorecon: путь берется из выбранного правила или эквивалентности
+    fn show_relabel_popup(&mut self) {
+        let selected = self.state.selected_index.unwrap_or(0);
+        let equivalences = self.get_visible_equivalences();
+        let path = match equivalences.get(selected) {
+            Some(e) => e.path.clone(),
+            None => self
+                .get_visible_file_contexts()
+                .get(selected - equivalences.len())
+                .map(|c| rule_test_path(&c.path))
+                .unwrap_or_default(),
+        };
+        self.state.enter_input_mode(PopupType::RelabelPath);
+        self.state.input_buffer = format!("-R {}", path);
+        self.state.input_cursor_position = self.state.input_buffer.len();
+    }
+
     // --- ФУНКЦИИ ВВОДА (ADD) ---
     fn show_add_popup(&mut self) {
         match self.state.current_view {
             CurrentView::Ports => self.state.enter_input_mode(PopupType::AddPort),
-            CurrentView::FileContexts => self.state.enter_input_mode(PopupType::AddFileContext),
+            CurrentView::FileContexts => {
+                self.fc_db = Some(FileContextsDb::load(
+                    &self.file_context_manager.contexts,
+                    &self.file_context_manager.equivalences,
+                    self.simulation_mode,
+                ));
+                self.rule_preview.clear();
+                self.state.enter_input_mode(PopupType::AddFileContext);
+            }
             CurrentView::AVCAlerts => self.state.enter_input_mode(PopupType::CreateModule),
+            CurrentView::Users => self.state.enter_input_mode(PopupType::UserMapping),
+            CurrentView::Network => self.state.enter_input_mode(PopupType::NetworkContext),
+            CurrentView::ModuleManager => self.state.enter_input_mode(PopupType::InstallModule),
+            CurrentView::LabelScan => {
+                self.state.enter_input_mode(PopupType::ScanRoot);
+                self.state.input_buffer = self.label_scanner.root.clone().unwrap_or_default();
+                self.state.input_cursor_position = self.state.input_buffer.len();
+            }
             _ => self.set_status("Add option not available here".into(), Color::Yellow),
         }
     }
     
+    /// Снимает выбранную запись модуля с ее приоритетом. Перед удалением модуль
+    /// извлекается в кэш, чтобы откат мог установить его обратно.
+    fn remove_selected_module(&mut self) {
+        let selected = self.state.selected_index.unwrap_or(0);
+        let Some(module) = self.module_manager.modules.get(selected).cloned() else {
+            return;
+        };
+        let lower = self.module_manager.overrides(&module);
+        let mut mgr = self.module_manager.clone();
+        let sim = self.simulation_mode;
+        let change_id = self.rollback_manager.reserve_change_id();
+        self.spawn_task(&format!("Removing module {} at priority {}...", module.name, module.priority), move || {
+            // Копия — в каталоге записи истории, а не в кэше, который перезаписывается
+            let dir = RollbackManager::backup_dir(&change_id)?;
+            let backup = if sim {
+                dir.join(format!("{}.{}", module.name, module.language))
+            } else {
+                ModuleManager::extract_module(&module, false, &dir)?
+            };
+            mgr.remove_module(&module.name, module.priority, sim)?;
+            let rb = format!(
+                "semodule -X {} -i {}",
+                module.priority,
+                shell_quote(&backup.to_string_lossy())
+            );
+            let note = match lower.first() {
+                Some(p) => format!(", priority {} is active now", p),
+                None => String::new(),
+            };
+            Ok((format!("Removed module {} at priority {}{}", module.name, module.priority, note), vec![rb]))
+        });
+    }
+
+    fn show_avc_query_popup(&mut self) {
+        self.state.enter_input_mode(PopupType::AVCQuery);
+        // Начинаем с текущего запроса, чтобы его можно было уточнить
+        self.state.input_buffer = self.avc_manager.query.text.clone();
+        self.state.input_cursor_position = self.state.input_buffer.len();
+    }
+
     fn show_create_module_popup(&mut self) {
         if self.state.current_view == CurrentView::AVCAlerts {
             self.state.enter_input_mode(PopupType::CreateModule);
@@ -406,40 +941,279 @@ impl App {
                 self.state.search_query = input;
                 self.state.reset_mode();
             }
-            PopupType::AddPort => {
-                let parts: Vec<String> = input.split_whitespace().map(|s| s.to_string()).collect();
-                if parts.len() == 3 {
-                    let mut mgr = self.port_manager.clone();
-                    let (port, proto, ctx) = (parts[0].clone(), parts[1].clone(), parts[2].clone());
-                    self.state.reset_mode();
-
-                    // Запускаем добавление в фоне
-                    self.spawn_task("Adding Port...", move || {
-                        mgr.add_port(&port, &proto, &ctx, simulation)?;
-                        let rb = vec![format!("semanage port -d -p {} {}", proto, port)];
-                        Ok((format!("Added port {}/{}", port, proto), rb))
+            PopupType::ScanRoot => {
+                let root = if input.trim().is_empty() { "/".to_string() } else { input.trim().to_string() };
+                self.state.reset_mode();
+                let db = FileContextsDb::load(&self.file_context_manager.contexts, &self.file_context_manager.equivalences, simulation);
+                if db.is_empty() {
+                    self.set_status("Error: file_contexts database not found".into(), Color::Red);
+                    return Ok(());
+                }
+                match self.label_scanner.start_scan(&root, db) {
+                    Ok(()) => {
+                        let _ = self.logger.info(&format!("Scanning labels under {}", root));
+                        self.set_status(format!("Scanning {}... ('c' to cancel)", root), Color::Cyan);
+                    }
+                    Err(e) => self.set_status(format!("Error: {}", e), Color::Red),
+                }
+            }
+            PopupType::RelabelPath => {
+                self.state.reset_mode();
+                let mut parts: Vec<&str> = input.split_whitespace().collect();
+                let recursive = parts.first() == Some(&"-R");
+                if recursive {
+                    parts.remove(0);
+                }
+                let path = parts.join(" ");
+                let db = FileContextsDb::load(&self.file_context_manager.contexts, &self.file_context_manager.equivalences, simulation);
+                match self.relabeler.start_preview(&path, recursive, db, simulation) {
+                    Ok(()) => {
+                        let _ = self.logger.info(&format!("restorecon dry run for {}", path));
+                    }
+                    Err(e) => self.set_status(format!("Error: {}", e), Color::Red),
+                }
+            }
+            PopupType::RelabelConfirm => {
+                self.state.reset_mode();
+                match self.relabeler.start_relabel(simulation) {
+                    Ok(()) => {
+                        let _ = self.logger.info(&format!("restorecon for {}", self.relabeler.path));
+                    }
+                    Err(e) => self.set_status(format!("Error: {}", e), Color::Red),
+                }
+            }
+            PopupType::RemedyChooser => {
+                let option = self.remedy_options.get(self.remedy_selected).cloned();
+                let alert = self.remedy_alert.take();
+                self.state.reset_mode();
+                if let Some(Remedy::PortType { port, protocol, port_type }) = option.as_ref().map(|o| &o.remedy) {
+                    // Смена типа порта — через ту же проверку и подтверждение, что и в Ports
+                    let preflight = self.port_manager.preflight(port, protocol, port_type);
+                    self.assign_port_type(preflight);
+                } else if let (Some(option), Some(alert)) = (option, alert) {
+                    let mut boolean_mgr = self.boolean_manager.clone();
+                    let mut module_mgr = self.module_manager.clone();
+                    let mut file_ctx_mgr = self.file_context_manager.clone();
+                    let mut port_mgr = self.port_manager.clone();
+                    let _ = self.logger.info(&format!("Applying remedy for {}: {}", alert.signature(), option.description));
+                    self.spawn_task("Applying AVC remedy...", move || {
+                        let rb = RemedyPlanner::apply(
+                            &option.remedy,
+                            &alert,
+                            &mut boolean_mgr,
+                            &mut module_mgr,
+                            &mut file_ctx_mgr,
+                            &mut port_mgr,
+                            simulation,
+                        )?;
+                        Ok((option.description, rb))
                     });
-                } else {
-                    self.set_status("Error: Use format 'PORT PROTO TYPE'".into(), Color::Red);
                 }
             }
-            PopupType::AddFileContext => {
-                let parts: Vec<String> = input.split_whitespace().map(|s| s.to_string()).collect();
-                if parts.len() >= 2 {
-                    let ctx = parts.last().unwrap().clone();
-                    let path = parts[0..parts.len() - 1].join(" ");
-                    let mut mgr = self.file_context_manager.clone();
+            PopupType::IgnoreReason => {
+                if input.trim().is_empty() {
+                    self.set_status("Error: Ignore reason required".into(), Color::Red);
+                    return Ok(());
+                }
+                let selected = self.state.selected_index.and_then(|i| self.get_filtered_avc_alerts().get(i).cloned());
+                self.state.reset_mode();
+                if let Some(alert) = selected {
+                    self.apply_triage(&alert, TriageState::Ignored, input.trim());
+                }
+            }
+            PopupType::AVCQuery => match AVCQuery::parse(&input) {
+                Ok(query) => {
+                    let range_changed = query.since != self.avc_manager.query.since
+                        || query.until != self.avc_manager.query.until;
+                    self.avc_manager.query = query;
+                    self.state.reset_mode();
+                    self.state.list_state.select(Some(0));
+                    self.state.selected_index = Some(0);
+                    if range_changed {
+                        self.refresh_data()?;
+                        self.update_stats();
+                        self.update_recommendations();
+                    }
+                    let shown = self.get_filtered_avc_alerts().len();
+                    self.set_status(format!("AVC query applied: {} alerts", shown), Color::Cyan);
+                }
+                Err(e) => self.set_status(format!("Query error: {}", e), Color::Red),
+            },
+            PopupType::AddPort => match parse_port_input(&input) {
+                Some((port, proto, ctx)) => {
+                    let preflight = self.port_manager.preflight(&port, &proto, &ctx);
+                    self.state.reset_mode();
+                    self.assign_port_type(preflight);
+                }
+                None => self.set_status("Error: Use format 'PORT[-PORT] PROTO TYPE'".into(), Color::Red),
+            },
+            PopupType::ModifyPort => match parse_port_input(&input) {
+                Some((port, proto, ctx)) => {
+                    let preflight = self.port_manager.preflight(&port, &proto, &ctx);
+                    if preflight.existing.is_none() {
+                        self.set_status(format!("Error: {}/{} is not defined, use 'a' to add it", port, proto), Color::Red);
+                        return Ok(());
+                    }
                     self.state.reset_mode();
+                    // Смена типа уже выбрана явно — подтверждаем только стандартные порты
+                    if preflight.warnings.is_empty() {
+                        self.run_port_assignment(preflight);
+                    } else {
+                        self.confirm_port_assignment(preflight);
+                    }
+                }
+                None => self.set_status("Error: Use format 'PORT[-PORT] PROTO TYPE'".into(), Color::Red),
+            },
+            PopupType::PortConfirm => {
+                self.state.reset_mode();
+                if let Some(preflight) = self.pending_port.take() {
+                    self.run_port_assignment(preflight);
+                }
+            }
+            PopupType::AddFileContext if input.contains(" = ") => {
+                // "PATH = EQUAL" — эквивалентность путей
+                let (path, equal) = input.split_once(" = ").unwrap();
+                let (path, equal) = (path.trim().to_string(), equal.trim().to_string());
+                let mut mgr = self.file_context_manager.clone();
+                self.state.reset_mode();
 
-                    self.spawn_task("Adding File Context...", move || {
-                        mgr.add_file_context(&path, &ctx, simulation)?;
-                        let rb = vec![format!("semanage fcontext -d {}", path)];
-                        Ok((format!("Added context for {}", path), rb))
-                    });
-                } else {
-                    self.set_status("Error: Use format 'PATH TYPE'".into(), Color::Red);
+                self.spawn_task("Adding Equivalence...", move || {
+                    mgr.add_equivalence(&path, &equal, simulation)?;
+                    let rb = vec![format!("semanage fcontext -d {}", shell_quote(&path))];
+                    Ok((format!("Added equivalence {} = {}", path, equal), rb))
+                });
+            }
+            PopupType::AddFileContext => {
+                match parse_context_input(&input) {
+                    Some((path, Some(ctx), file_type)) => {
+                        let mut mgr = self.file_context_manager.clone();
+                        self.state.reset_mode();
+
+                        self.spawn_task("Adding File Context...", move || {
+                            mgr.add_file_context(&path, &ctx, file_type, simulation)?;
+                            let rb = vec![format!(
+                                "semanage fcontext -d -f {} {}",
+                                file_type.semanage_flag(),
+                                shell_quote(&path)
+                            )];
+                            Ok((format!("Added context for {} ({}), 'x' relabels existing files", path, file_type.label()), rb))
+                        });
+                    }
+                    _ => self.set_status("Error: Use format 'PATH TYPE [-f a|f|d|l|s|p|b|c]'".into(), Color::Red),
+                }
+            }
+            PopupType::InstallModule => {
+                let mut words = input.split_whitespace();
+                let path = words.next().unwrap_or("").to_string();
+                let priority = match words.next() {
+                    Some(p) => p.parse::<i32>().ok(),
+                    None => Some(DEFAULT_PRIORITY),
+                };
+                let Some(priority) = priority.filter(|_| !path.is_empty()) else {
+                    self.set_status("Error: Use 'PATH [PRIORITY]' (PRIORITY 1-999)".into(), Color::Red);
+                    return Ok(());
+                };
+                let name = match ModuleManager::module_file_name(&path) {
+                    Ok((name, _)) => name,
+                    Err(e) => {
+                        self.set_status(format!("Error: {}", e), Color::Red);
+                        return Ok(());
+                    }
+                };
+                self.state.reset_mode();
+                let message = format!("Installing module {} at priority {}...", name, priority);
+                self.spawn_module_install(&message, name, priority, move || Ok(PathBuf::from(path)));
+            }
+            PopupType::ModuleProjectName => match ModuleProject::open(input.trim()) {
+                Ok(project) => {
+                    self.project_problems = project.validate();
+                    self.module_project = Some(project);
+                    self.state.enter_input_mode(PopupType::ModuleProject);
+                    self.state.input_buffer = DEFAULT_PRIORITY.to_string();
+                    self.state.input_cursor_position = self.state.input_buffer.len();
+                }
+                Err(e) => self.set_status(format!("Error: {}", e), Color::Red),
+            },
+            PopupType::ModuleProject => {
+                let Some(project) = self.module_project.clone() else {
+                    self.state.reset_mode();
+                    return Ok(());
+                };
+                let Some(priority) = input.trim().parse::<i32>().ok().filter(|p| (1..=999).contains(p)) else {
+                    self.set_status("Error: Priority must be between 1 and 999".into(), Color::Red);
+                    return Ok(());
+                };
+                self.project_problems = project.validate();
+                if let Some(problem) = self.project_problems.first() {
+                    self.set_status(format!("Fix {} first: {}", project.name, problem), Color::Red);
+                    return Ok(());
                 }
+                self.state.reset_mode();
+                let message = format!("Building module {} ({})...", project.name, project.build_method().label());
+                self.spawn_module_install(&message, project.name.clone(), priority, move || project.build(simulation));
             }
+            PopupType::NetworkContext => match parse_network_input(&input) {
+                Some((target, ctx, range)) => {
+                    self.state.reset_mode();
+                    if let Some((address, netmask, proto)) = parse_cidr(&target) {
+                        let rb = match self.node_manager.find(&address, &netmask, &proto) {
+                            Some(old) => old.semanage_command("-m"),
+                            None => format!(
+                                "semanage node -d -M {} -p {} {}",
+                                shell_quote(&netmask),
+                                shell_quote(&proto),
+                                shell_quote(&address)
+                            ),
+                        };
+                        let mut mgr = self.node_manager.clone();
+                        self.spawn_task("Labeling node...", move || {
+                            mgr.set_node(&address, &netmask, &proto, &ctx, &range, simulation)?;
+                            Ok((format!("Node {} labeled {}", target, ctx), vec![rb]))
+                        });
+                    } else {
+                        let rb = match self.interface_manager.find(&target) {
+                            Some(old) => old.semanage_command("-m"),
+                            None => format!("semanage interface -d {}", shell_quote(&target)),
+                        };
+                        let mut mgr = self.interface_manager.clone();
+                        self.spawn_task("Labeling interface...", move || {
+                            mgr.set_interface(&target, &ctx, &range, simulation)?;
+                            Ok((format!("Interface {} labeled {}", target, ctx), vec![rb]))
+                        });
+                    }
+                }
+                None => self.set_status("Error: Use 'IFACE TYPE [RANGE]' or 'ADDR[/PREFIX] TYPE [RANGE]'".into(), Color::Red),
+            },
+            PopupType::UserMapping => match parse_user_input(&input) {
+                Some(UserEntry::User(user)) => {
+                    let rb = match self.user_manager.find_user(&user.name) {
+                        Some(old) => old.semanage_command("-m"),
+                        None => format!("semanage user -d {}", shell_quote(&user.name)),
+                    };
+                    let mut mgr = self.user_manager.clone();
+                    self.state.reset_mode();
+                    self.spawn_task("Saving SELinux user...", move || {
+                        mgr.set_user(&user.name, &user.roles, &user.range, simulation)?;
+                        Ok((format!("SELinux user {}: roles {}", user.name, user.roles.join(" ")), vec![rb]))
+                    });
+                }
+                Some(UserEntry::Login(login)) => {
+                    let rb = match self.user_manager.find_login(&login.login) {
+                        Some(old) => old.semanage_command("-m"),
+                        None => format!("semanage login -d {}", shell_quote(&login.login)),
+                    };
+                    let mut mgr = self.user_manager.clone();
+                    self.state.reset_mode();
+                    self.spawn_task("Saving login mapping...", move || {
+                        mgr.set_login(&login.login, &login.seuser, &login.range, simulation)?;
+                        Ok((format!("Mapped login {} to {}", login.login, login.seuser), vec![rb]))
+                    });
+                }
+                None => self.set_status(
+                    "Error: Use 'LOGIN SEUSER [RANGE]' or 'user NAME ROLE[,ROLE] [RANGE]'".into(),
+                    Color::Red,
+                ),
+            },
             PopupType::ExportConfig => {
                 let filename = if input.is_empty() {
                     format!("selab_config_{}.json", chrono::Utc::now().format("%Y%m%d_%H%M%S"))
@@ -447,6 +1221,7 @@ impl App {
                     input
                 };
                 let path = PathBuf::from(&filename);
+                self.load_all_view_data()?;
                 let profile = ConfigExporter::export_profile(
                     "Current Configuration",
                     "Exported configuration",
@@ -454,6 +1229,9 @@ impl App {
                     &self.module_manager,
                     &self.file_context_manager,
                     &self.port_manager,
+                    &self.user_manager,
+                    &self.interface_manager,
+                    &self.node_manager,
                 )?;
                 ConfigExporter::save_to_file(&profile, &path)?;
                 self.state.reset_mode();
@@ -462,10 +1240,14 @@ impl App {
             PopupType::ImportConfig => {
                 let path = PathBuf::from(&input);
                 let profile = ConfigExporter::load_from_file(&path)?;
+                self.load_all_view_data()?;
                 let mut boolean_mgr = self.boolean_manager.clone();
                 let mut module_mgr = self.module_manager.clone();
                 let mut file_ctx_mgr = self.file_context_manager.clone();
                 let mut port_mgr = self.port_manager.clone();
+                let mut user_mgr = self.user_manager.clone();
+                let mut iface_mgr = self.interface_manager.clone();
+                let mut node_mgr = self.node_manager.clone();
                 let sim = self.simulation_mode;
                 self.state.reset_mode();
                 
@@ -476,21 +1258,22 @@ impl App {
                         &mut module_mgr,
                         &mut file_ctx_mgr,
                         &mut port_mgr,
+                        &mut user_mgr,
+                        &mut iface_mgr,
+                        &mut node_mgr,
                         sim,
                     )?;
                     Ok((format!("Imported configuration from {}", input), rb))
                 });
             }
             PopupType::CreateModule => {
-                if input.is_empty() {
-                    self.set_status("Error: Module name required".into(), Color::Red);
+                let Some((module_name, format)) = parse_module_name_input(&input) else {
+                    self.set_status("Error: Use 'NAME [pp|cil]'".into(), Color::Red);
                     return Ok(());
-                }
-                
-                let module_name = input.clone();
+                };
                 let alerts: Vec<_> = if let Some(idx) = self.state.selected_index {
                     // Создаем модуль из выбранного алерта
-                    if let Some(alert) = self.avc_manager.alerts.get(idx) {
+                    if let Some(alert) = self.get_filtered_avc_alerts().get(idx) {
                         vec![alert.clone()]
                     } else {
                         vec![]
@@ -508,11 +1291,11 @@ impl App {
                 let alert_count = alerts.len();
                 let mut module_mgr = self.module_manager.clone();
                 let sim = self.simulation_mode;
-                let log_msg = format!("Creating module {} from {} alerts", module_name, alert_count);
+                let log_msg = format!("Creating module {} ({}) from {} alerts", module_name, format.label(), alert_count);
                 self.state.reset_mode();
                 
                 self.spawn_task(&format!("Creating module {}...", module_name), move || {
-                    let result = module_mgr.create_module_from_alerts(&module_name, &alerts, sim)?;
+                    let result = module_mgr.create_module_from_alerts(&module_name, &alerts, format, sim)?;
                     let rb = vec![format!("semodule -r {}", module_name)];
                     Ok((result, rb))
                 });
@@ -542,11 +1325,20 @@ impl App {
                 6 => self.state.current_view = CurrentView::Ports,
                 7 => self.state.current_view = CurrentView::Statistics,
                 8 => self.state.current_view = CurrentView::SELinuxMode,
+                9 => self.state.current_view = CurrentView::LabelScan,
+                10 => self.state.current_view = CurrentView::Users,
+                11 => self.state.current_view = CurrentView::Network,
                 _ => {}
             },
             CurrentView::SELinuxMode => {
                 self.toggle_selinux_mode();
             }
+            CurrentView::LabelScan => self.label_scanner.toggle_mark(selected),
+            CurrentView::Statistics => {
+                if let Some((field, value, _)) = self.get_stats_drilldown().get(selected).cloned() {
+                    self.drill_down_to_avc(&field, &value);
+                }
+            }
             CurrentView::ModuleManager => {
                 if let Some(module) = self.module_manager.modules.get(selected).cloned() {
                     // Показываем рекомендацию если есть
@@ -596,10 +1388,15 @@ impl App {
                     });
                 }
             }
+            CurrentView::Ports if self.ports_show_listeners => {
+                if let Some(l) = self.listeners.get(selected).cloned() {
+                    self.label_listener_port(l);
+                }
+            }
             CurrentView::Ports => {
                 if let Some(p) = self.port_manager.ports.get(selected).cloned() {
                     // Показываем рекомендацию если есть
-                    if let Some(advice) = self.advisor.get_port_advice(&p.port, &p.protocol) {
+                    if let Some(advice) = self.advisor.get_port_advice(&p.port.to_string(), &p.protocol) {
                         let detail = format!(
                             "Port: {}/{}\nCurrent context: {}\n\n{}\n\nRisk: {}\nSuggestion: {}\n\nPress Enter again to remove.",
                             p.port, p.protocol, p.context, advice.description, advice.risk, advice.suggestion
@@ -619,33 +1416,90 @@ impl App {
                 }
             }
             CurrentView::FileContexts => {
-                if let Some(c) = self.file_context_manager.contexts.get(selected).cloned() {
+                let equivalences = self.get_visible_equivalences();
+                if let Some(e) = equivalences.get(selected).cloned() {
+                    if !e.local {
+                        self.set_status(format!("{} = {} is a policy equivalence, only local ones can be removed", e.path, e.equal), Color::Red);
+                        return Ok(());
+                    }
+                    let mut mgr = self.file_context_manager.clone();
+                    let sim = self.simulation_mode;
+                    self.spawn_task(&format!("Removing equivalence {}...", e.path), move || {
+                        mgr.remove_equivalence(&e.path, sim)?;
+                        let rb = format!("semanage fcontext -a -e {} {}", shell_quote(&e.equal), shell_quote(&e.path));
+                        Ok((format!("Removed equivalence {} = {}", e.path, e.equal), vec![rb]))
+                    });
+                } else if let Some(c) = self.get_visible_file_contexts().get(selected - equivalences.len()).cloned() {
+                    if !c.local {
+                        self.set_status(format!("{} is a policy rule, only local customizations can be removed", c.path), Color::Red);
+                        return Ok(());
+                    }
                     let mut mgr = self.file_context_manager.clone();
                     let sim = self.simulation_mode;
                     self.spawn_task(&format!("Removing context {}...", c.path), move || {
-                        mgr.remove_file_context(&c.path, sim)?;
-                        let rb = format!("semanage fcontext -a -t {} {}", c.context, c.path);
+                        mgr.remove_file_context(&c.path, c.file_type, sim)?;
+                        let rb = format!(
+                            "semanage fcontext -a -f {} -t {} {}",
+                            c.file_type.semanage_flag(),
+                            c.context,
+                            shell_quote(&c.path)
+                        );
                         Ok((format!("Removed context {}", c.path), vec![rb]))
                     });
                 }
             }
             CurrentView::AVCAlerts => {
-                if let Some(alert) = self.avc_manager.alerts.get(selected).cloned() {
-                    if let Some(sol) = self.avc_manager.analyze_avc(&alert) {
-                        let mgr = self.avc_manager.clone();
-                        let sim = self.simulation_mode;
-                        let sol_clone = sol.clone();
-
-                        self.spawn_task("Applying AVC Fix...", move || {
-                            mgr.apply_solution(&sol_clone, sim)?;
-                            let rb = sol_clone
-                            .commands
-                            .iter()
-                            .map(|c| format!("# undo: {}", c))
-                            .collect();
-                            Ok((format!("Applied: {}", sol_clone.description), rb))
-                        });
+                if let Some(alert) = self.get_filtered_avc_alerts().get(selected).cloned() {
+                    self.show_remedy_chooser(alert);
+                }
+            }
+            CurrentView::Network => {
+                let interfaces_len = self.interface_manager.interfaces.len();
+                let sim = self.simulation_mode;
+                if let Some(i) = self.interface_manager.interfaces.get(selected).cloned() {
+                    if !i.local {
+                        self.set_status(format!("{} is labeled by the policy, only local entries can be removed", i.name), Color::Red);
+                        return Ok(());
+                    }
+                    let mut mgr = self.interface_manager.clone();
+                    self.spawn_task(&format!("Removing interface {}...", i.name), move || {
+                        mgr.remove_interface(&i.name, sim)?;
+                        Ok((format!("Removed interface context {}", i.name), vec![i.semanage_command("-a")]))
+                    });
+                } else if let Some(n) = self.node_manager.nodes.get(selected - interfaces_len).cloned() {
+                    if !n.local {
+                        self.set_status(format!("{} is labeled by the policy, only local entries can be removed", n.cidr()), Color::Red);
+                        return Ok(());
+                    }
+                    let mut mgr = self.node_manager.clone();
+                    self.spawn_task(&format!("Removing node {}...", n.cidr()), move || {
+                        mgr.remove_node(&n.address, &n.netmask, &n.protocol, sim)?;
+                        Ok((format!("Removed node context {}", n.cidr()), vec![n.semanage_command("-a")]))
+                    });
+                }
+            }
+            CurrentView::Users => {
+                let logins_len = self.user_manager.logins.len();
+                let mut mgr = self.user_manager.clone();
+                let sim = self.simulation_mode;
+                if let Some(l) = self.user_manager.logins.get(selected).cloned() {
+                    if !l.local {
+                        self.set_status(format!("{} is a policy default, use 'E' to change it", l.login), Color::Red);
+                        return Ok(());
+                    }
+                    self.spawn_task(&format!("Removing login mapping {}...", l.login), move || {
+                        mgr.remove_login(&l.login, sim)?;
+                        Ok((format!("Removed login mapping {} -> {}", l.login, l.seuser), vec![l.semanage_command("-a")]))
+                    });
+                } else if let Some(u) = self.user_manager.users.get(selected - logins_len).cloned() {
+                    if !u.local {
+                        self.set_status(format!("{} is defined by the policy, only local users can be removed", u.name), Color::Red);
+                        return Ok(());
                     }
+                    self.spawn_task(&format!("Removing SELinux user {}...", u.name), move || {
+                        mgr.remove_user(&u.name, sim)?;
+                        Ok((format!("Removed SELinux user {}", u.name), vec![u.semanage_command("-a")]))
+                    });
                 }
             }
             _ => {}
@@ -692,7 +1546,7 @@ impl App {
                 self.set_status("No specific advice found".into(), Color::Yellow);
             }
         } else {
-            let text = "Global Keys:\n?: Context Help\n/: Search\na: Add Item\nm: Create Module from AVC\nM: Toggle SELinux Mode\nr: Undo Last\ns: Auto-Secure\nR: Refresh Data\ne: Export Config\ni: Import Config\nv: View Details\nf: Filter AVC\nA: AVC Recommendations\n0: SELinux Mode View".to_string();
+            let text = "Global Keys:\n?: Context Help\n/: Search\na: Add Item\nm: Create Module from AVC\nM: Toggle SELinux Mode\nr: Undo Last\ns: Auto-Secure\nR: Refresh Data\ne: Export Config\ni: Import Config\nv: View Details\nf: Filter AVC\ng: Group AVC by unit/container\nt: Triage AVC (ack/expected/new)\nT: Ignore AVC with reason\nH: Show ignored/expected AVC\nH (File Contexts view): Local customizations / full database\nx (File Contexts view): restorecon with dry-run preview\nE (Ports view): Change port type (semanage port -m)\nL (Ports view): Listening sockets on untyped, generic or another service's ports\na / D (Modules view): Install at priority / remove selected priority\nv (Modules view): Module source (CIL) with types, allow rules, booleans\nP (Modules view): Module project from .te/.fc/.if: edit, validate, build, install\na / E (Users view): Add or modify login mapping / SELinux user\na / E (Net view): Add or modify interface / node context\n/ (AVC view): AVC Query\nA: AVC Recommendations\n0: SELinux Mode View\na (Labels view): Scan directory for mislabeled files\n* / x / c (Labels view): Mark all, restore marked, cancel".to_string();
             self.state.popup_type = PopupType::Help(text);
             self.state.input_mode = InputMode::Editing;
         }
@@ -730,18 +1584,32 @@ impl App {
         let detail = match self.state.current_view {
             CurrentView::AVCAlerts => {
                 if let Some(idx) = self.state.selected_index {
-                    if let Some(alert) = self.avc_manager.alerts.get(idx) {
+                    if let Some(alert) = self.get_filtered_avc_alerts().get(idx) {
                         let advice = self.advisor.get_avc_advice(alert);
+                        let triage = match self.triage_manager.entry_of(alert) {
+                            Some(e) if e.reason.is_empty() => e.state.label().to_string(),
+                            Some(e) => format!("{} ({})", e.state.label(), e.reason),
+                            None => TriageState::New.label().to_string(),
+                        };
+                        let label_check = match self.file_context_manager.mislabel_of(alert, self.simulation_mode) {
+                            Some(m) => format!("\nLabel: {} but policy expects {} -> run restorecon -v {}", m.current, m.expected, m.path),
+                            None => String::new(),
+                        };
                         format!(
-                            "AVC Alert Details:\n\nTimestamp: {}\nCommand: {}\nPath: {}\nPermission: {}\nSource: {}\nTarget: {}\nClass: {}\nSeverity: {:?}\n\n{}\n\n{}",
+                            "AVC Alert Details:\n\nTimestamp: {}\nCommand: {} (pid {})\nUnit: {}\nContainer: {}\nPath: {}\nPermission: {}\nSource: {}\nTarget: {}\nClass: {}\nSeverity: {:?}\nTriage: {}{}\n\n{}\n\n{}",
                             alert.timestamp,
                             alert.comm,
+                            alert.pid,
+                            alert.unit.as_deref().unwrap_or("-"),
+                            alert.container.as_deref().unwrap_or("-"),
                             alert.path,
                             alert.permission,
                             alert.source_context,
                             alert.target_context,
                             alert.target_class,
                             alert.severity,
+                            triage,
+                            label_check,
                             advice.as_ref().map(|a| a.description.as_str()).unwrap_or("No advice available"),
                             advice.as_ref().map(|a| a.suggestion.as_str()).unwrap_or("")
                         )
@@ -774,13 +1642,136 @@ impl App {
                     "No boolean selected".to_string()
                 }
             }
-            _ => "Detail view not available for this view".to_string(),
-        };
-        self.state.popup_type = PopupType::DetailView(detail);
-        self.state.input_mode = InputMode::Editing;
-    }
-    
-    fn toggle_avc_filter(&mut self) {
+            CurrentView::RollbackHistory => {
+                match self.state.selected_index.and_then(|i| self.rollback_manager.change_history.get(i)) {
+                    Some(c) => {
+                        let mut text = format!(
+                            "Change Details:\n\nAction: {}\nTime: {}\nDescription: {}\n\nRollback commands ({}):\n",
+                            c.action,
+                            c.timestamp,
+                            c.description,
+                            c.rollback_commands.len()
+                        );
+                        for cmd in c.rollback_commands.iter().take(10) {
+                            text.push_str(&format!("  {}\n", cmd));
+                        }
+                        if !c.changed_files.is_empty() {
+                            text.push_str(&format!("\nChanged files ({}):\n", c.changed_files.len()));
+                            for file in c.changed_files.iter().take(20) {
+                                text.push_str(&format!("  {}\n", file));
+                            }
+                            if c.changed_files.len() > 20 {
+                                text.push_str(&format!("  ... and {} more\n", c.changed_files.len() - 20));
+                            }
+                        }
+                        text
+                    }
+                    None => "No change selected".to_string(),
+                }
+            }
+            CurrentView::ModuleManager => return self.show_module_source(),
+            _ => "Detail view not available for this view".to_string(),
+        };
+        self.state.popup_type = PopupType::DetailView(detail);
+        self.state.input_mode = InputMode::Editing;
+    }
+
+    /// Ставит модуль с приоритетом в фоне; `build` возвращает путь к файлу модуля.
+    /// Откат снимает запись или, если она заменена, ставит сохраненную копию.
+    fn spawn_module_install<F>(&mut self, message: &str, name: String, priority: i32, build: F)
+    where
+    F: FnOnce() -> Result<PathBuf> + Send + 'static,
+    {
+        let replaced = self
+            .module_manager
+            .modules
+            .iter()
+            .find(|m| m.name == name && m.priority == priority)
+            .cloned();
+        let mut mgr = self.module_manager.clone();
+        let simulation = self.simulation_mode;
+        let change_id = self.rollback_manager.reserve_change_id();
+        self.spawn_task(message, move || {
+            let path = build()?;
+            // Заменяемую запись сохраняем, чтобы откат вернул ее
+            let rb = match replaced {
+                Some(old) if !simulation => {
+                    let backup = ModuleManager::extract_module(&old, false, &RollbackManager::backup_dir(&change_id)?)?;
+                    format!("semodule -X {} -i {}", priority, shell_quote(&backup.to_string_lossy()))
+                }
+                _ => format!("semodule -X {} -r {}", priority, name),
+            };
+            mgr.install_module(&path.to_string_lossy(), priority, simulation)?;
+            Ok((format!("Installed module {} at priority {}", name, priority), vec![rb]))
+        });
+    }
+
+    /// Открывает файл проекта модуля во внешнем редакторе (см. `run_app`)
+    fn edit_project_file(&mut self, ext: &str) {
+        if let Some(project) = &self.module_project {
+            self.pending_edit = Some(project.file(ext));
+        }
+    }
+
+    /// После выхода из редактора: перепроверяем проект
+    fn finish_edit(&mut self, result: Result<()>) {
+        match result {
+            Ok(()) => {
+                if let Some(project) = &self.module_project {
+                    self.project_problems = project.validate();
+                    match self.project_problems.len() {
+                        0 => self.set_status(format!("{}: no problems found", project.name), Color::Green),
+                        n => self.set_status(format!("{}: {} problem(s)", project.name, n), Color::Yellow),
+                    }
+                }
+            }
+            Err(e) => self.set_status(format!("Editor failed: {}", e), Color::Red),
+        }
+    }
+
+    /// Извлекает выбранный модуль в CIL и открывает окно с исходником и сводкой
+    fn show_module_source(&mut self) {
+        if self.module_source_rx.is_some() {
+            return;
+        }
+        let selected = self.state.selected_index.unwrap_or(0);
+        let Some(module) = self.module_manager.modules.get(selected).cloned() else {
+            return;
+        };
+        // semodule -E на большом модуле занимает секунды — не держим UI
+        let (tx, rx) = mpsc::channel();
+        self.module_source_rx = Some(rx);
+        self.set_status(format!("Extracting module {}...", module.name), Color::Yellow);
+        let simulation = self.simulation_mode;
+        thread::spawn(move || {
+            let result = ModuleSource::load(&module, simulation)
+                .map_err(|e| format!("Cannot extract module {}: {}", module.name, e));
+            let _ = tx.send(result);
+        });
+    }
+
+    fn poll_module_source(&mut self) {
+        let Some(rx) = &self.module_source_rx else {
+            return;
+        };
+        let result = match rx.try_recv() {
+            Ok(result) => result,
+            Err(mpsc::TryRecvError::Empty) => return,
+            Err(mpsc::TryRecvError::Disconnected) => Err("Module extraction failed".to_string()),
+        };
+        self.module_source_rx = None;
+        match result {
+            Ok(source) => {
+                self.set_status(format!("Module {} extracted", source.module.name), Color::Green);
+                self.module_source = Some(source);
+                self.module_source_scroll = 0;
+                self.state.enter_input_mode(PopupType::ModuleSource);
+            }
+            Err(e) => self.set_status(e, Color::Red),
+        }
+    }
+    
+    fn toggle_avc_filter(&mut self) {
         self.avc_severity_filter = match self.avc_severity_filter {
             None => Some(avc::AVCSeverity::High),
             Some(avc::AVCSeverity::High) => Some(avc::AVCSeverity::Medium),
@@ -796,6 +1787,68 @@ impl App {
         self.set_status(format!("AVC Filter: {}", filter_text), Color::Cyan);
     }
     
+    fn toggle_avc_grouping(&mut self) {
+        if self.state.current_view != CurrentView::AVCAlerts {
+            self.set_status("Grouping only available in AVC Alerts view".into(), Color::Yellow);
+            return;
+        }
+        self.avc_grouping = self.avc_grouping.next();
+        self.state.list_state.select(Some(0));
+        self.state.selected_index = Some(0);
+        self.set_status(format!("AVC grouping: {}", self.avc_grouping.label()), Color::Cyan);
+    }
+
+    /// Переключает отметку выбранной сигнатуры: new -> ack -> expected -> new
+    fn cycle_avc_triage(&mut self) {
+        if self.state.current_view != CurrentView::AVCAlerts {
+            return;
+        }
+        let Some(alert) = self.state.selected_index.and_then(|i| self.get_filtered_avc_alerts().get(i).cloned()) else {
+            return;
+        };
+        let next = match self.triage_manager.state_of(&alert) {
+            TriageState::New => TriageState::Acknowledged,
+            TriageState::Acknowledged => TriageState::Expected,
+            TriageState::Ignored | TriageState::Expected => TriageState::New,
+        };
+        self.apply_triage(&alert, next, "");
+    }
+
+    fn apply_triage(&mut self, alert: &avc::AVCAlert, state: TriageState, reason: &str) {
+        match self.triage_manager.set_state(alert, state, reason) {
+            Ok(()) => {
+                let _ = self.logger.info(&format!("Triage {}: {}", state.label(), alert.signature()));
+                self.update_stats();
+                self.update_recommendations();
+                self.set_status(format!("Marked {}: {}", state.label(), alert.signature()), Color::Cyan);
+            }
+            Err(e) => self.set_status(format!("Triage error: {}", e), Color::Red),
+        }
+    }
+
+    /// Открывает выбор способа устранения отказа
+    fn show_remedy_chooser(&mut self, alert: avc::AVCAlert) {
+        let mislabel = self.file_context_manager.mislabel_of(&alert, self.simulation_mode);
+        let options = RemedyPlanner::options(
+            &alert,
+            mislabel.as_ref(),
+            &self.advisor,
+            &self.boolean_manager,
+            &self.module_manager,
+            &self.file_context_manager,
+            &self.port_manager,
+        );
+        if options.is_empty() {
+            self.set_status("No applicable remedies for this denial".into(), Color::Yellow);
+            return;
+        }
+        self.remedy_alert = Some(alert);
+        self.remedy_options = options;
+        self.remedy_selected = 0;
+        self.state.popup_type = PopupType::RemedyChooser;
+        self.state.input_mode = InputMode::Editing;
+    }
+
     fn show_avc_recommendations(&mut self) {
         if self.avc_recommendations.is_empty() {
             self.set_status("No recommendations available".into(), Color::Yellow);
@@ -805,15 +1858,77 @@ impl App {
         self.state.input_mode = InputMode::Editing;
     }
     
-    fn get_filtered_avc_alerts(&self) -> Vec<avc::AVCAlert> {
-        if let Some(severity) = &self.avc_severity_filter {
-            self.avc_manager.alerts.iter()
-                .filter(|a| std::mem::discriminant(&a.severity) == std::mem::discriminant(severity))
-                .cloned()
-                .collect()
-        } else {
-            self.avc_manager.alerts.clone()
+    /// Элементы детализации статистики: (поле запроса, значение, количество)
+    fn get_stats_drilldown(&self) -> Vec<(String, String, usize)> {
+        let mut items = Vec::new();
+        for (field, map) in [
+            ("stype", &self.system_stats.avc_by_source),
+            ("ttype", &self.system_stats.avc_by_target),
+            ("perm", &self.system_stats.avc_by_permission),
+        ] {
+            for (value, count) in StatsManager::top_entries(map, 5) {
+                items.push((field.to_string(), value, count));
+            }
         }
+        items
+    }
+
+    /// Переходит к списку AVC, отфильтрованному по выбранному элементу статистики.
+    /// Временной диапазон текущего запроса сохраняется.
+    fn drill_down_to_avc(&mut self, field: &str, value: &str) {
+        // В записи может быть несколько разрешений ("read write") — фильтруем по первому
+        let value = value.split_whitespace().next().unwrap_or(value);
+        let mut tokens: Vec<String> = AVCQuery::split_tokens(&self.avc_manager.query.text)
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|t| t.starts_with("since:") || t.starts_with("until:"))
+            .map(|t| t.to_string())
+            .collect();
+        tokens.push(format!("{}:{}", field, value));
+        match AVCQuery::parse(&tokens.join(" ")) {
+            Ok(query) => {
+                self.avc_manager.query = query;
+                self.state.current_view = CurrentView::AVCAlerts;
+                self.state.list_state.select(Some(0));
+                self.state.selected_index = Some(0);
+                self.set_status(format!("AVC filtered by {}:{}", field, value), Color::Cyan);
+            }
+            Err(e) => self.set_status(format!("Query error: {}", e), Color::Red),
+        }
+    }
+
+    fn get_visible_file_contexts(&self) -> Vec<FileContext> {
+        self.file_context_manager
+            .contexts
+            .iter()
+            .filter(|c| self.fc_show_all || c.local)
+            .cloned()
+            .collect()
+    }
+
+    fn get_visible_equivalences(&self) -> Vec<FcEquivalence> {
+        self.file_context_manager
+            .equivalences
+            .iter()
+            .filter(|e| self.fc_show_all || e.local)
+            .cloned()
+            .collect()
+    }
+
+    fn get_filtered_avc_alerts(&self) -> Vec<avc::AVCAlert> {
+        let query = &self.avc_manager.query;
+        let mut alerts: Vec<avc::AVCAlert> = self.avc_manager.alerts.iter()
+            .filter(|a| match &self.avc_severity_filter {
+                Some(severity) => std::mem::discriminant(&a.severity) == std::mem::discriminant(severity),
+                None => true,
+            })
+            .filter(|a| query.matches_fields(a))
+            .filter(|a| self.avc_show_suppressed || !self.triage_manager.is_suppressed(a))
+            .cloned()
+            .collect();
+        // Стабильная сортировка сохраняет порядок отказов внутри группы
+        alerts.sort_by_key(|a| self.avc_grouping.key(a));
+        alerts
     }
 
     fn get_current_system_state(&self) -> Result<SystemState> {
@@ -829,9 +1944,13 @@ impl App {
            modules: self.module_manager.modules.clone(),
            file_contexts: self
            .file_context_manager
-           .contexts
-           .iter()
-           .map(|c| format!("{}:{}", c.path, c.context))
+           .customizations()
+           .map(|c| format!("{} [{}]:{}", c.path, c.file_type.semanage_flag(), c.full_context()))
+           .collect(),
+           fc_equivalences: self
+           .file_context_manager
+           .local_equivalences()
+           .map(|e| format!("{} = {}", e.path, e.equal))
            .collect(),
            ports: self
            .port_manager
@@ -839,11 +1958,98 @@ impl App {
            .iter()
            .map(|p| format!("{}/{}:{}", p.port, p.protocol, p.context))
            .collect(),
+           logins: self.user_manager.local_logins().map(|l| l.state_line()).collect(),
+           selinux_users: self.user_manager.local_users().map(|u| u.state_line()).collect(),
+           interfaces: self.interface_manager.interfaces.iter().filter(|i| i.local).map(|i| i.state_line()).collect(),
+           nodes: self.node_manager.nodes.iter().filter(|n| n.local).map(|n| n.state_line()).collect(),
         })
     }
 
+    /// Пересчитывает предпросмотр правила, когда в окне добавления меняется ввод
+    fn update_rule_preview(&mut self) {
+        let editing = self.state.input_mode == InputMode::Editing && self.state.popup_type == PopupType::AddFileContext;
+        let parsed = if editing { parse_context_input(&self.state.input_buffer) } else { None };
+        match (parsed, &self.fc_db) {
+            (Some((spec, Some(ctx), file_type)), Some(db)) => {
+                let key = format!("{}\t{}\t{}", spec, ctx, file_type.semanage_flag());
+                if key != self.rule_preview.key {
+                    let test_path = rule_test_path(&spec);
+                    self.rule_preview.start(key, db, &spec, file_type.fc_flag(), &ctx, &test_path);
+                }
+                self.rule_preview.poll();
+            }
+            _ if !self.rule_preview.key.is_empty() => self.rule_preview.clear(),
+            _ => {}
+        }
+    }
+
+    /// Итоги restorecon: после пробного прогона — подтверждение, после применения — запись в историю
+    fn poll_relabel(&mut self) -> Result<()> {
+        let phase = self.relabeler.phase;
+        if let Some(summary) = self.relabeler.poll() {
+            for err in &self.relabeler.errors {
+                let _ = self.logger.error(&format!("restorecon: {}", err));
+            }
+            let _ = self.logger.info(&summary.description);
+            self.set_status(summary.description.clone(), Color::Green);
+            let state = self.get_current_system_state()?;
+            self.rollback_manager.record_change_with_files(
+                "Relabel files".to_string(),
+                summary.description,
+                state.clone(),
+                state,
+                summary.rollback_commands,
+                summary.changed_files,
+            );
+        } else if phase != RelabelPhase::Idle && !self.relabeler.is_running() {
+            if let Some(err) = self.relabeler.errors.first() {
+                self.set_status(format!("Error: {}", err), Color::Red);
+            } else if self.relabeler.cancelled {
+                self.set_status("restorecon cancelled".into(), Color::Yellow);
+            } else if phase == RelabelPhase::Previewing && self.relabeler.preview.is_empty() {
+                self.set_status(format!("Labels under {} already match the policy", self.relabeler.path), Color::Green);
+            } else if phase == RelabelPhase::Previewing {
+                self.state.popup_type = PopupType::RelabelConfirm;
+                self.state.input_mode = InputMode::Editing;
+            } else {
+                self.set_status("restorecon changed nothing".into(), Color::Yellow);
+            }
+        }
+        Ok(())
+    }
+
     // --- ЦИКЛ ОБНОВЛЕНИЯ (TICK) ---
     fn tick(&mut self) -> Result<()> {
+        if self.loaded_view != Some(self.state.current_view) {
+            self.load_view_data();
+        }
+        self.update_rule_preview();
+        self.poll_module_source();
+        self.poll_relabel()?;
+        for err in self.notifier.take_errors() {
+            let _ = self.logger.error(&format!("Notification failed: {}", err));
+        }
+        let scan_phase = self.label_scanner.phase;
+        if let Some(summary) = self.label_scanner.poll() {
+            for err in &self.label_scanner.errors {
+                let _ = self.logger.error(&format!("Label restore failed: {}", err));
+            }
+            let _ = self.logger.info(&summary.description);
+            self.set_status(summary.description.clone(), Color::Green);
+            let state = self.get_current_system_state()?;
+            self.rollback_manager.record_change(
+                "Restore labels".to_string(),
+                summary.description,
+                state.clone(),
+                state,
+                summary.rollback_commands,
+            );
+        } else if scan_phase == ScanPhase::Scanning && !self.label_scanner.is_running() {
+            self.set_status(
+                format!("Label scan finished: {} mismatch(es)", self.label_scanner.mismatches.len()),
+                Color::Green,
+            );
+        }
         if self.is_busy {
             self.spinner_idx = (self.spinner_idx + 1) % 4;
             if let Some(rx) = &self.task_rx {
@@ -852,6 +2058,7 @@ impl App {
                     self.task_rx = None;
 
                     if let Some(err) = res.error {
+                        self.rollback_manager.release_change_id();
                         let _ = self.logger.error(&format!("Task failed: {}", err));
                         self.set_status(format!("Error: {}", err), Color::Red);
                     } else {
@@ -864,6 +2071,7 @@ impl App {
                         }
                         
                         self.refresh_data()?;
+                        self.load_view_data();
                         self.update_stats();
                         self.update_recommendations();
                         let state = self.get_current_system_state()?;
@@ -899,20 +2107,24 @@ impl App {
 
         let list_len = match self.state.current_view {
             CurrentView::BooleanManager => self.get_filtered_booleans().len(),
-            CurrentView::Dashboard => 9,
+            CurrentView::Dashboard => 12,
             CurrentView::AVCAlerts => self.get_filtered_avc_alerts().len(),
             CurrentView::ModuleManager => self.module_manager.modules.len(),
             CurrentView::RollbackHistory => self.rollback_manager.change_history.len(),
             CurrentView::SafeSettings => 2,
-            CurrentView::FileContexts => self.file_context_manager.contexts.len(),
+            CurrentView::FileContexts => self.get_visible_equivalences().len() + self.get_visible_file_contexts().len(),
+            CurrentView::Ports if self.ports_show_listeners => self.listeners.len(),
             CurrentView::Ports => self.port_manager.ports.len(),
-            CurrentView::Statistics => 10,
+            CurrentView::Statistics => self.get_stats_drilldown().len(),
             CurrentView::SELinuxMode => 3,
+            CurrentView::LabelScan => self.label_scanner.mismatches.len(),
+            CurrentView::Users => self.user_manager.logins.len() + self.user_manager.users.len(),
+            CurrentView::Network => self.interface_manager.interfaces.len() + self.node_manager.nodes.len(),
         };
         self.state.set_current_len(list_len);
 
         let tabs = Tabs::new(vec![
-            "1:Dash", "2:AVC", "3:Mod", "4:Bool", "5:Roll", "6:Safe", "7:File", "8:Port", "9:Stats", "0:Mode",
+            "1:Dash", "2:AVC", "3:Mod", "4:Bool", "5:Roll", "6:Safe", "7:File", "8:Port", "9:Stats", "0:Mode", "Labels", "Users", "Net",
         ])
         .block(Block::default().borders(Borders::ALL).title("SELab"))
         .select(self.state.current_view as usize)
@@ -930,11 +2142,14 @@ impl App {
             CurrentView::SafeSettings => self.render_safe(f, chunks[1]),
             CurrentView::Statistics => self.render_statistics(f, chunks[1]),
             CurrentView::SELinuxMode => self.render_selinux_mode(f, chunks[1]),
+            CurrentView::LabelScan => self.render_label_scan(f, chunks[1]),
+            CurrentView::Users => self.render_users(f, chunks[1]),
+            CurrentView::Network => self.render_network(f, chunks[1]),
         }
 
         self.render_footer(f, chunks[2]);
 
-        if self.is_busy {
+        if self.is_busy || self.relabeler.is_running() {
             self.render_busy_popup(f);
         } else if self.state.popup_type != PopupType::None {
             self.render_popup(f);
@@ -951,6 +2166,23 @@ impl App {
         let block = Block::default()
         .borders(Borders::ALL)
         .style(Style::default().bg(Color::DarkGray).fg(Color::White));
+        if self.relabeler.is_running() {
+            let (done, total) = self.relabeler.progress();
+            let (title, progress) = match self.relabeler.phase {
+                RelabelPhase::Previewing => ("restorecon -n (dry run)", format!("{} file(s) would change", done)),
+                _ => ("restorecon", format!("Relabeled {} of {} file(s)", done, total)),
+            };
+            let text = vec![
+                Line::from(format!("{} {} {}", spin, title, self.relabeler.path)),
+                Line::from(""),
+                Line::from(progress),
+                Line::from(self.relabeler.current().to_string()),
+                Line::from(""),
+                Line::from(Span::styled("c / Esc: cancel", Style::default().fg(Color::Yellow))),
+            ];
+            f.render_widget(Paragraph::new(text).block(block).alignment(Alignment::Center), area);
+            return;
+        }
         let text = vec![
             Line::from("Processing Operation..."),
             Line::from(""),
@@ -968,8 +2200,63 @@ impl App {
         f.render_widget(p, area);
     }
 
+    /// Проверка пути по базе: текущее правило, метка на диске и эффект нового правила
+    fn describe_rule_test(&self, db: &FileContextsDb, spec: &str, ctx: Option<&str>, file_type: FcFileType) -> String {
+        let test_path = rule_test_path(spec);
+        if test_path.is_empty() {
+            return String::new();
+        }
+        let meta = std::fs::symlink_metadata(&test_path).ok();
+        let test_type = meta
+            .as_ref()
+            .map(label_scan::fc_file_type)
+            .unwrap_or_else(|| file_type.fc_flag().unwrap_or("--"));
+        let mut txt = format!("Test {}:\n", test_path);
+        match db.lookup(&test_path, test_type) {
+            Some(rule) => txt.push_str(&format!("  Rule now: {} -> {}\n", rule.spec, rule.context)),
+            None => txt.push_str("  Rule now: none\n"),
+        }
+        let live = match (&meta, file_label(&test_path)) {
+            (None, _) => "not on disk".to_string(),
+            (Some(_), Some(label)) => label,
+            (Some(_), None) => "unlabeled".to_string(),
+        };
+        txt.push_str(&format!("  On disk: {}\n", live));
+        if ctx.is_some() {
+            match &self.rule_preview.result {
+                Some(p) => {
+                    let winner = match p.winner.as_deref() {
+                        Some(w) if w == spec => "new rule wins".to_string(),
+                        Some(w) => format!("{} still wins", w),
+                        None => "regex does not match".to_string(),
+                    };
+                    txt.push_str(&format!(
+                        "  With new rule: {}; {} of {} matching files relabeled{}\n",
+                        winner,
+                        p.relabel,
+                        p.matched,
+                        if !p.finished {
+                            format!(" (counting, {} scanned)", p.scanned)
+                        } else if p.truncated {
+                            format!(" (stopped after {} files)", p.scanned)
+                        } else {
+                            String::new()
+                        }
+                    ));
+                }
+                None => txt.push_str("  With new rule: counting...\n"),
+            }
+        }
+        txt.push('\n');
+        txt
+    }
+
     fn render_popup<B: Backend>(&mut self, f: &mut Frame<B>) {
-        let area = self.centered_rect(60, 50, f.size());
+        let area = if self.state.popup_type == PopupType::ModuleSource {
+            self.centered_rect(90, 85, f.size())
+        } else {
+            self.centered_rect(60, 50, f.size())
+        };
         f.render_widget(Clear, area);
         let block = Block::default()
         .borders(Borders::ALL)
@@ -979,8 +2266,30 @@ impl App {
         match &self.state.popup_type {
             PopupType::AddPort => {
                 let mut txt = format!(
-                    "Add Port Rule\n\nFormat: PORT PROTO TYPE\nExample: 8080 tcp http_port_t\n\n"
+                    "Add Port Rule\n\nFormat: PORT[-PORT] PROTO TYPE  (PROTO: {})\nExample: 8080 tcp http_port_t\nExample: 9100-9105 tcp http_port_t\n\n",
+                    PROTOCOLS.join("/")
                 );
+                // Записи, которые уже покрывают порт или пересекаются с диапазоном
+                let mut words = self.state.input_buffer.split_whitespace();
+                let range = words.next().and_then(|p| p.parse::<PortRange>().ok());
+                let proto = words.next().unwrap_or("tcp");
+                if let Some(range) = range {
+                    let covering = self.port_manager.overlapping(&range, proto);
+                    if let Some(current) = covering.first() {
+                        txt.push_str(&format!("Now: {}/{} covered by {} (rule {})\n", range, proto, current.context, current.port));
+                    }
+                    for p in covering.iter().skip(1) {
+                        txt.push_str(&format!("     and by {} (rule {})\n", p.context, p.port));
+                    }
+                    if let Some(ctx) = words.next() {
+                        for e in advisor::well_known_reassignments(&range, proto, ctx) {
+                            txt.push_str(&format!("⚠ {}/{} is the standard {} port ({})\n", e.port, proto, e.service, e.context));
+                        }
+                    }
+                    if !covering.is_empty() {
+                        txt.push('\n');
+                    }
+                }
                 
                 // Показываем рекомендации если пользователь начал вводить порт
                 if let Some(port_part) = self.state.input_buffer.split_whitespace().next() {
@@ -996,15 +2305,128 @@ impl App {
                 txt.push_str(&format!("> {}", self.state.input_buffer));
                 f.render_widget(Paragraph::new(txt).block(block.title("Add Port")), area);
             }
+            PopupType::InstallModule => {
+                let mut txt = format!(
+                    "Install Module (semodule -X PRIORITY -i PATH)\n\nFormat: PATH [PRIORITY]  (default {})\nExample: /root/myapp.cil\nExample: /root/apache.pp 450\n\nA higher priority overrides the same module installed lower\n(distribution modules use 100).\n\n",
+                    DEFAULT_PRIORITY
+                );
+                let mut words = self.state.input_buffer.split_whitespace();
+                if let Some(Ok((name, _))) = words.next().map(ModuleManager::module_file_name) {
+                    let priority = words.next().and_then(|p| p.parse().ok()).unwrap_or(DEFAULT_PRIORITY);
+                    for m in self.module_manager.modules.iter().filter(|m| m.name == name) {
+                        let effect = match m.priority.cmp(&priority) {
+                            std::cmp::Ordering::Less => "will be overridden",
+                            std::cmp::Ordering::Equal => "will be replaced",
+                            std::cmp::Ordering::Greater => "will keep overriding the new one",
+                        };
+                        txt.push_str(&format!("Installed: {} at {} ({}) {}\n", m.name, m.priority, m.language, effect));
+                    }
+                    txt.push('\n');
+                }
+                txt.push_str(&format!("> {}", self.state.input_buffer));
+                f.render_widget(Paragraph::new(txt).block(block.title("Install Module")), area);
+            }
+            PopupType::NetworkContext => {
+                let mut txt = String::from(
+                    "Interface: IFACE TYPE [RANGE]\nExample: eth1 netif_t\n\nNode: ADDR[/PREFIX] TYPE [RANGE]\nExample: 10.20.0.0/16 node_t\nExample: fd00::/8 node_t s0\n\nExisting entries are modified (semanage -m).\n\n",
+                );
+                if let Some((target, _, _)) = parse_network_input(&self.state.input_buffer) {
+                    let current = match parse_cidr(&target) {
+                        Some((address, netmask, proto)) => self
+                            .node_manager
+                            .find(&address, &netmask, &proto)
+                            .map(|n| format!("Now: {} ({}) is {}\n\n", n.cidr(), n.protocol, n.context)),
+                        None => self
+                            .interface_manager
+                            .find(&target)
+                            .map(|i| format!("Now: {} is {}\n\n", i.name, i.context)),
+                    };
+                    txt.push_str(&current.unwrap_or_default());
+                }
+                txt.push_str(&format!("> {}", self.state.input_buffer));
+                f.render_widget(Paragraph::new(txt).block(block.title("Interface / Node Context")), area);
+            }
+            PopupType::UserMapping => {
+                let mut txt = String::from(
+                    "Login mapping: LOGIN SEUSER [RANGE]\nExample: alice staff_u s0-s0:c0.c1023\nExample: %admins staff_u\n\nSELinux user: user NAME ROLE[,ROLE] [RANGE]\nExample: user admin_u staff_r,sysadm_r s0-s0:c0.c1023\n\nExisting entries are modified (semanage -m).\n\n",
+                );
+                // Текущее значение для введенного имени
+                match parse_user_input(&self.state.input_buffer) {
+                    Some(UserEntry::User(u)) => {
+                        if let Some(old) = self.user_manager.find_user(&u.name) {
+                            txt.push_str(&format!("Now: {} has roles {} {}\n\n", old.name, old.roles.join(" "), old.range));
+                        }
+                    }
+                    Some(UserEntry::Login(l)) => {
+                        if let Some(old) = self.user_manager.find_login(&l.login) {
+                            txt.push_str(&format!("Now: {} -> {} {}\n\n", old.login, old.seuser, old.range));
+                        }
+                        if self.user_manager.find_user(&l.seuser).is_none() {
+                            txt.push_str(&format!("⚠ SELinux user {} does not exist\n\n", l.seuser));
+                        }
+                    }
+                    None => {}
+                }
+                txt.push_str(&format!("> {}", self.state.input_buffer));
+                f.render_widget(Paragraph::new(txt).block(block.title("SELinux User / Login")), area);
+            }
+            PopupType::PortConfirm => {
+                let Some(pf) = &self.pending_port else {
+                    return;
+                };
+                let mut text = vec![Line::from(format!("Assign {} to {}/{}", pf.context, pf.port, pf.protocol)), Line::from("")];
+                if let Some(old) = &pf.existing {
+                    text.push(Line::from(Span::styled(
+                        format!("{}/{} is already defined as {}", old.port, old.protocol, old.context),
+                        Style::default().fg(Color::Yellow),
+                    )));
+                    text.push(Line::from("The existing mapping will be modified (semanage port -m)."));
+                    text.push(Line::from(""));
+                }
+                if !pf.overlapping.is_empty() {
+                    text.push(Line::from("Also covered by:"));
+                    for p in &pf.overlapping {
+                        text.push(Line::from(format!("  {}/{} -> {}", p.port, p.protocol, p.context)));
+                    }
+                    text.push(Line::from(""));
+                }
+                for w in &pf.warnings {
+                    text.push(Line::from(Span::styled(format!("WARNING: {}", w), Style::default().fg(Color::Red))));
+                }
+                if !pf.warnings.is_empty() {
+                    text.push(Line::from(""));
+                }
+                let action = if pf.existing.is_some() { "modify" } else { "add" };
+                text.push(Line::from(format!("Enter: {}, Esc: cancel", action)));
+                f.render_widget(
+                    Paragraph::new(text).wrap(Wrap { trim: false }).block(block.title("Port Conflict")),
+                    area,
+                );
+            }
+            PopupType::ModifyPort => {
+                f.render_widget(
+                    Paragraph::new(format!(
+                        "Change Port Type (semanage port -m)\n\nFormat: PORT[-PORT] PROTO TYPE\nThe port or range must match an existing entry.\n\n> {}",
+                        self.state.input_buffer
+                    ))
+                    .block(block.title("Modify Port")),
+                                area,
+                );
+            }
             PopupType::AddFileContext => {
                 let mut txt = format!(
-                    "Add Context Rule\n\nFormat: PATH TYPE\nExample: /var/www/app httpd_sys_content_t\n\n"
+                    "Add Context Rule\n\nFormat: PATH TYPE [-f a|f|d|l|s|p|b|c]\nExample: /var/www/app httpd_sys_content_t -f d\n\nEquivalence: PATH = EXISTING_PATH\nExample: /srv/www = /var/www\n\n"
                 );
                 
                 // Показываем рекомендуемые контексты если пользователь начал вводить путь
                 if let Some(path_part) = self.state.input_buffer.split_whitespace().next() {
                     if !path_part.is_empty() {
-                        let suggested = self.advisor.get_suggested_file_contexts(path_part);
+                        if let Some(equal) = apply_equivalences(&self.file_context_manager.equivalences, path_part) {
+                            txt.push_str(&format!("= Labeled like {} (equivalence)\n", equal));
+                        }
+                        let suggested = self
+                            .advisor
+                            .get_suggested_file_contexts(path_part, &self.file_context_manager.equivalences);
                         if !suggested.is_empty() {
                             txt.push_str("💡 Suggested contexts:\n");
                             for (i, ctx) in suggested.iter().take(5).enumerate() {
@@ -1014,6 +2436,9 @@ impl App {
                         }
                     }
                 }
+                if let (Some((spec, ctx, file_type)), Some(db)) = (parse_context_input(&self.state.input_buffer), &self.fc_db) {
+                    txt.push_str(&self.describe_rule_test(db, &spec, ctx.as_deref(), file_type));
+                }
                 
                 txt.push_str(&format!("> {}", self.state.input_buffer));
                 f.render_widget(Paragraph::new(txt).block(block.title("Add Context")), area);
@@ -1026,6 +2451,110 @@ impl App {
                                 area,
                 );
             }
+            PopupType::ScanRoot => {
+                f.render_widget(
+                    Paragraph::new(format!(
+                        "Scan for mislabeled files\n\nLabels are compared with the file_contexts database.\nOther filesystems under the directory are skipped.\n\nDirectory:\n> {}",
+                        self.state.input_buffer
+                    ))
+                    .block(block.title("Label Scan")),
+                                area,
+                );
+            }
+            PopupType::RelabelPath => {
+                f.render_widget(
+                    Paragraph::new(format!(
+                        "Restore default labels (restorecon)\n\nA dry run lists the files that would change before anything is relabeled.\nFormat: [-R] PATH  (-R: recursive)\n\n> {}",
+                        self.state.input_buffer
+                    ))
+                    .block(block.title("Relabel")),
+                                area,
+                );
+            }
+            PopupType::RelabelConfirm => {
+                let mut text = vec![
+                    Line::from(format!(
+                        "{} file(s) under {} would be relabeled:",
+                        self.relabeler.preview.len(),
+                        self.relabeler.path
+                    )),
+                    Line::from(""),
+                ];
+                let shown = (area.height as usize).saturating_sub(7);
+                for change in self.relabeler.preview.iter().take(shown) {
+                    text.push(Line::from(format!(
+                        "{}: {} -> {}",
+                        change.path,
+                        context_type(&change.old),
+                        context_type(&change.new)
+                    )));
+                }
+                if self.relabeler.preview.len() > shown {
+                    text.push(Line::from(format!("... and {} more", self.relabeler.preview.len() - shown)));
+                }
+                text.push(Line::from(""));
+                text.push(Line::from(Span::styled("Enter: relabel, Esc: cancel", Style::default().fg(Color::Yellow))));
+                f.render_widget(Paragraph::new(text).block(block.title("Confirm Relabel")), area);
+            }
+            PopupType::RemedyChooser => {
+                let mut text = vec![
+                    Line::from(self.remedy_alert.as_ref().map(|a| a.signature()).unwrap_or_default()),
+                    Line::from(""),
+                ];
+                for (i, option) in self.remedy_options.iter().enumerate() {
+                    let risk_color = match option.risk.as_str() {
+                        "High" => Color::Red,
+                        "Medium" => Color::Yellow,
+                        _ => Color::Green,
+                    };
+                    let marker = if i == self.remedy_selected { "> " } else { "  " };
+                    let style = if i == self.remedy_selected {
+                        Style::default().fg(Color::Yellow)
+                    } else {
+                        Style::default()
+                    };
+                    text.push(Line::from(vec![
+                        Span::styled(format!("{}{}", marker, option.description), style),
+                        Span::styled(format!("  [{}]", option.risk), Style::default().fg(risk_color)),
+                    ]));
+                }
+                text.push(Line::from(""));
+                text.push(Line::from("↑/↓ select, Enter apply (undo with 'r'), Esc cancel"));
+                f.render_widget(
+                    Paragraph::new(text)
+                    .block(block.title("Fix AVC Denial"))
+                    .wrap(Wrap { trim: false }),
+                                area,
+                );
+            }
+            PopupType::IgnoreReason => {
+                let signature = self
+                    .state
+                    .selected_index
+                    .and_then(|i| self.get_filtered_avc_alerts().get(i).map(|a| a.signature()))
+                    .unwrap_or_default();
+                f.render_widget(
+                    Paragraph::new(format!(
+                        "Ignore AVC signature:\n{}\n\nIgnored denials are hidden from the list, recommendations\nand risk score ('H' shows them again).\n\nReason:\n> {}",
+                        signature, self.state.input_buffer
+                    ))
+                    .block(block.title("Ignore Denial"))
+                    .wrap(Wrap { trim: false }),
+                                area,
+                );
+            }
+            PopupType::AVCQuery => {
+                let txt = format!(
+                    "AVC Query\n\nTime: since:<t> until:<t>  (now, recent, boot, today, yesterday, -2h, 2024-01-15T10:00, \"2024-01-15 10:00:00\")\nFields: stype: ttype: class: perm: comm: path: pid:  (* and ? allowed)\nExample: since:today stype:httpd_t perm:write path:/var/www/*\n\n> {}",
+                    self.state.input_buffer
+                );
+                f.render_widget(
+                    Paragraph::new(txt)
+                    .block(block.title("AVC Query"))
+                    .wrap(Wrap { trim: false }),
+                                area,
+                );
+            }
             PopupType::Search => {
                 f.render_widget(
                     Paragraph::new(format!("Search Query:\n> {}", self.state.input_buffer))
@@ -1033,6 +2562,119 @@ impl App {
                                 area,
                 );
             }
+            PopupType::ModuleProjectName => {
+                let projects = ModuleProject::list();
+                let existing = if projects.is_empty() { "none".to_string() } else { projects.join(", ") };
+                f.render_widget(
+                    Paragraph::new(format!(
+                        "Open or create a module project\n\nSources live in {}/NAME/\nExisting: {}\n\nName:\n> {}",
+                        ModuleProject::projects_dir().display(),
+                        existing,
+                        self.state.input_buffer
+                    ))
+                    .block(block.title("Module Project"))
+                    .wrap(Wrap { trim: false }),
+                                area,
+                );
+            }
+            PopupType::ModuleProject => {
+                let Some(project) = &self.module_project else {
+                    return;
+                };
+                let mut text = vec![
+                    Line::from(format!("{} ({})", project.name, project.dir.display())),
+                    Line::from(format!("Build: {}", project.build_method().label())),
+                    Line::from(""),
+                ];
+                for (file, lines) in project.files() {
+                    text.push(Line::from(match lines {
+                        Some(n) => format!("  {:<24} {} lines", file, n),
+                        None => format!("  {:<24} -", file),
+                    }));
+                }
+                let installed: Vec<String> = self
+                    .module_manager
+                    .modules
+                    .iter()
+                    .filter(|m| m.name == project.name)
+                    .map(|m| format!("{} ({})", m.priority, m.language))
+                    .collect();
+                text.push(Line::from(format!(
+                    "Installed at: {}",
+                    if installed.is_empty() { "-".to_string() } else { installed.join(", ") }
+                )));
+                text.push(Line::from(""));
+                if self.project_problems.is_empty() {
+                    text.push(Line::from(Span::styled("No problems found", Style::default().fg(Color::Green))));
+                } else {
+                    for problem in self.project_problems.iter().take(8) {
+                        text.push(Line::from(Span::styled(problem.to_string(), Style::default().fg(Color::Red))));
+                    }
+                    if self.project_problems.len() > 8 {
+                        text.push(Line::from(format!("... and {} more", self.project_problems.len() - 8)));
+                    }
+                }
+                text.push(Line::from(""));
+                text.push(Line::from(format!("Priority: > {}", self.state.input_buffer)));
+                text.push(Line::from(Span::styled(
+                    "e/f/i: edit .te/.fc/.if in $EDITOR, Enter: build and install (undo with 'r'), Esc: close",
+                    Style::default().fg(Color::Yellow),
+                )));
+                f.render_widget(
+                    Paragraph::new(text)
+                    .block(block.title("Module Project"))
+                    .wrap(Wrap { trim: false }),
+                                area,
+                );
+            }
+            PopupType::ModuleSource => {
+                let Some(source) = &self.module_source else {
+                    return;
+                };
+                let m = &source.module;
+                let summary = &source.summary;
+                let origin = if ModuleManager::is_local(m) {
+                    Span::styled("local", Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD))
+                } else {
+                    Span::styled("distro", Style::default().fg(Color::Green))
+                };
+                let mut text = vec![
+                    Line::from(vec![
+                        Span::raw(format!(
+                            "{} (priority {}, {}, {}) ",
+                            m.name,
+                            m.priority,
+                            if m.language.is_empty() { "?" } else { m.language.as_str() },
+                            if m.enabled { "enabled" } else { "disabled" }
+                        )),
+                        origin,
+                    ]),
+                    Line::from(Span::styled(source.path.display().to_string(), Style::default().fg(Color::DarkGray))),
+                ];
+                if let Some(err) = &source.parse_error {
+                    text.push(Line::from(Span::styled(format!("Parse error: {}", err), Style::default().fg(Color::Red))));
+                }
+                text.push(Line::from(""));
+                text.push(Line::from(format!("Types ({}): {}", summary.types.len(), summary.types.join(", "))));
+                let booleans: Vec<String> = summary.booleans.iter().map(|(name, value)| format!("{}={}", name, value)).collect();
+                text.push(Line::from(format!("Booleans ({}): {}", booleans.len(), booleans.join(", "))));
+                text.push(Line::from(format!("Allow rules ({}):", summary.allow_count())));
+                for (domain, rules) in &summary.allows {
+                    text.push(Line::from(Span::styled(format!("  {}", domain), Style::default().fg(Color::Cyan))));
+                    for rule in rules {
+                        text.push(Line::from(format!("    {}", rule)));
+                    }
+                }
+                text.push(Line::from(""));
+                text.push(Line::from(Span::styled("-- CIL source --", Style::default().fg(Color::DarkGray))));
+                text.extend(source.text.lines().map(highlight_cil));
+                f.render_widget(
+                    Paragraph::new(text)
+                    .block(block.title(format!("Module Source: {} (↑/↓ PgUp/PgDn scroll, Esc close)", m.name)))
+                    .scroll((self.module_source_scroll, 0)),
+                                area,
+                );
+            }
             PopupType::DetailView(text) => {
                 f.render_widget(
                     Paragraph::new(text.as_str())
@@ -1072,7 +2714,7 @@ impl App {
             }
             PopupType::CreateModule => {
                 let selected_info = if let Some(idx) = self.state.selected_index {
-                    if let Some(alert) = self.avc_manager.alerts.get(idx) {
+                    if let Some(alert) = self.get_filtered_avc_alerts().get(idx) {
                         format!("Selected alert: {} -> {}", alert.comm, alert.permission)
                     } else {
                         "Will use all alerts".to_string()
@@ -1080,13 +2722,34 @@ impl App {
                 } else {
                     format!("Will use all {} alerts", self.avc_manager.alerts.len())
                 };
+                let format = parse_module_name_input(&self.state.input_buffer)
+                    .map(|(_, format)| format)
+                    .unwrap_or_else(ModuleFormat::default_for_system);
+                let mut txt = format!(
+                    "Create Module from AVC Alerts\n\n{}\nFormat: {} (add 'pp' or 'cil' after the name to choose)\n",
+                    selected_info,
+                    format.label()
+                );
+                // Для CIL показываем правила, которые попадут в модуль
+                if format == ModuleFormat::Cil {
+                    let alerts = match self.state.selected_index {
+                        Some(idx) => self.get_filtered_avc_alerts().get(idx).cloned().into_iter().collect(),
+                        None => self.avc_manager.alerts.clone(),
+                    };
+                    match cil_module::emit_cil("preview", &alerts) {
+                        Ok(cil) => {
+                            for line in cil.lines().skip(1).take(6) {
+                                txt.push_str(&format!("  {}\n", line));
+                            }
+                        }
+                        Err(e) => txt.push_str(&format!("  {}\n", e)),
+                    }
+                }
+                txt.push_str(&format!("\nEnter module name [pp|cil]:\n> {}", self.state.input_buffer));
                 f.render_widget(
-                    Paragraph::new(format!(
-                        "Create Module from AVC Alerts\n\n{}\n\nEnter module name:\n> {}",
-                        selected_info,
-                        self.state.input_buffer
-                    ))
-                    .block(block.title("Create Module")),
+                    Paragraph::new(txt)
+                    .block(block.title("Create Module"))
+                    .wrap(Wrap { trim: false }),
                                 area,
                 );
             }
@@ -1138,12 +2801,79 @@ impl App {
                              ListItem::new("7. Ports"),
                              ListItem::new("8. Statistics"),
                              ListItem::new("9. SELinux Mode"),
+                             ListItem::new("10. Label Scanner"),
+                             ListItem::new("11. SELinux Users"),
+                             ListItem::new("12. Interfaces & Nodes"),
         ])
         .block(Block::default().borders(Borders::ALL).title("Dashboard"))
         .highlight_style(Style::default().fg(Color::Yellow));
         f.render_stateful_widget(list, area, &mut self.state.list_state);
     }
     
+    fn render_label_scan<B: Backend>(&mut self, f: &mut Frame<B>, area: Rect) {
+        let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(4), Constraint::Min(0)])
+        .split(area);
+
+        let scanner = &self.label_scanner;
+        let marked = scanner.mismatches.iter().filter(|m| m.marked).count();
+        let header = Block::default().borders(Borders::ALL).title(format!(
+            "Label Scan {}",
+            scanner.root.as_deref().unwrap_or("(press 'a' to choose a directory)")
+        ));
+        match scanner.phase {
+            ScanPhase::Restoring => {
+                let ratio = scanner.done as f64 / scanner.total.max(1) as f64;
+                f.render_widget(
+                    Gauge::default()
+                    .block(header)
+                    .gauge_style(Style::default().fg(Color::Green))
+                    .ratio(ratio.min(1.0))
+                    .label(format!("Restoring {}/{} ('c' to cancel)", scanner.done, scanner.total)),
+                                chunks[0],
+                );
+            }
+            phase => {
+                let text = if phase == ScanPhase::Scanning {
+                    format!("Scanning... {} files checked ('c' to cancel)\n{}", scanner.scanned, scanner.current)
+                } else {
+                    format!(
+                        "{} file(s) checked, {} mismatch(es), {} marked{}{}",
+                        scanner.scanned,
+                        scanner.mismatches.len(),
+                        marked,
+                        if scanner.cancelled { " (cancelled)" } else { "" },
+                        if scanner.errors.is_empty() { String::new() } else { format!(", {} error(s)", scanner.errors.len()) }
+                    )
+                };
+                f.render_widget(Paragraph::new(text).block(header), chunks[0]);
+            }
+        }
+
+        let items: Vec<ListItem> = scanner
+        .mismatches
+        .iter()
+        .map(|m| {
+            let mark = if m.marked { "[x]" } else { "[ ]" };
+            ListItem::new(Line::from(vec![
+                Span::raw(format!("{} {} ", mark, m.path)),
+                Span::styled(context_type(&m.current).to_string(), Style::default().fg(Color::Red)),
+                Span::raw(" -> "),
+                Span::styled(context_type(&m.expected).to_string(), Style::default().fg(Color::Green)),
+                Span::styled(format!("  ({})", m.rule), Style::default().fg(Color::DarkGray)),
+            ]))
+        })
+        .collect();
+        f.render_stateful_widget(
+            List::new(items)
+            .block(Block::default().borders(Borders::ALL).title("Mislabeled files (Enter mark, '*' all, 'x' restore marked)"))
+            .highlight_style(Style::default().fg(Color::Yellow)),
+                                 chunks[1],
+                                 &mut self.state.list_state,
+        );
+    }
+
     fn render_selinux_mode<B: Backend>(&mut self, f: &mut Frame<B>, area: Rect) {
         let current_mode = self.selinux_mode_manager.get_current();
         let mode_text = current_mode.to_string();
@@ -1171,7 +2901,7 @@ impl App {
     fn render_statistics<B: Backend>(&mut self, f: &mut Frame<B>, area: Rect) {
         let (risk_level, risk_color) = StatsManager::get_risk_level(self.system_stats.risk_score);
         
-        let stats_text = vec![
+        let mut stats_text = vec![
             Line::from(vec![
                 Span::styled("System Statistics\n", Style::default().fg(Color::Cyan)),
             ]),
@@ -1225,8 +2955,52 @@ impl App {
                     Style::default().fg(risk_color),
                 ),
             ]),
+            Line::from(vec![
+                Span::raw("Ignored/Expected AVC: "),
+                Span::styled(format!("{}", self.system_stats.avc_suppressed), Style::default().fg(Color::DarkGray)),
+            ]),
+            Line::from(""),
+            Line::from(vec![
+                Span::raw("AVC History: "),
+                Span::styled(
+                    format!("{} denials stored ({} days retention)", self.system_stats.history_total, self.avc_store.retention_days),
+                    Style::default().fg(Color::Cyan),
+                ),
+            ]),
+            Line::from(vec![
+                Span::raw("New since last week: "),
+                Span::styled(
+                    format!("{}", self.system_stats.new_signatures_week.len()),
+                    Style::default().fg(Color::Yellow),
+                ),
+            ]),
         ];
+        for sig in self.system_stats.new_signatures_week.iter().take(5) {
+            stats_text.push(Line::from(Span::styled(format!("  + {}", sig), Style::default().fg(Color::Yellow))));
+        }
+        if !self.system_stats.domain_trends.is_empty() {
+            stats_text.push(Line::from(""));
+            stats_text.push(Line::from(Span::styled("Domain trends (7d / prev 7d):", Style::default().fg(Color::Cyan))));
+            for trend in self.system_stats.domain_trends.iter().take(5) {
+                let (arrow, color) = if trend.this_week > trend.last_week {
+                    ("↑", Color::Red)
+                } else if trend.this_week < trend.last_week {
+                    ("↓", Color::Green)
+                } else {
+                    ("=", Color::Gray)
+                };
+                stats_text.push(Line::from(vec![
+                    Span::raw(format!("  {: <24} {: >5} / {: <5} ", trend.domain, trend.this_week, trend.last_week)),
+                    Span::styled(arrow, Style::default().fg(color)),
+                ]));
+            }
+        }
         
+        let columns = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(40), Constraint::Percentage(60)])
+            .split(area);
+
         let block = Block::default()
             .borders(Borders::ALL)
             .title("Statistics");
@@ -1234,7 +3008,69 @@ impl App {
             Paragraph::new(stats_text)
                 .block(block)
                 .wrap(Wrap { trim: true }),
-            area,
+            columns[0],
+        );
+
+        let right = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([
+                Constraint::Length(6),
+                Constraint::Length(12),
+                Constraint::Min(5),
+            ])
+            .split(columns[1]);
+
+        let peak = self.system_stats.avc_timeline.iter().copied().max().unwrap_or(0);
+        let sparkline = Sparkline::default()
+            .block(Block::default().borders(Borders::ALL).title(format!(
+                "Denials per hour, last {}h (peak {})",
+                TIMELINE_HOURS, peak
+            )))
+            .data(&self.system_stats.avc_timeline)
+            .style(Style::default().fg(Color::Red));
+        f.render_widget(sparkline, right[0]);
+
+        let charts = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([
+                Constraint::Ratio(1, 3),
+                Constraint::Ratio(1, 3),
+                Constraint::Ratio(1, 3),
+            ])
+            .split(right[1]);
+        let sources = StatsManager::top_entries(&self.system_stats.avc_by_source, 5);
+        let targets = StatsManager::top_entries(&self.system_stats.avc_by_target, 5);
+        let perms = StatsManager::top_entries(&self.system_stats.avc_by_permission, 5);
+        for ((title, entries), chunk) in [("Top domains", &sources), ("Top targets", &targets), ("Top perms", &perms)]
+            .into_iter()
+            .zip(charts.iter())
+        {
+            let data: Vec<(&str, u64)> = entries.iter().map(|(k, v)| (k.as_str(), *v as u64)).collect();
+            // Ширина столбца подбирается так, чтобы уместить все столбцы в блок
+            let bar_width = (chunk.width.saturating_sub(2) / entries.len().max(1) as u16)
+                .saturating_sub(1)
+                .clamp(3, 12);
+            let chart = BarChart::default()
+                .block(Block::default().borders(Borders::ALL).title(title))
+                .data(&data)
+                .bar_width(bar_width)
+                .bar_gap(1)
+                .bar_style(Style::default().fg(Color::Yellow))
+                .value_style(Style::default().fg(Color::Black).bg(Color::Yellow));
+            f.render_widget(chart, *chunk);
+        }
+
+        let items: Vec<ListItem> = self
+            .get_stats_drilldown()
+            .iter()
+            .map(|(field, value, count)| ListItem::new(format!("{: <6} {: <32} {}", field, value, count)))
+            .collect();
+        f.render_stateful_widget(
+            List::new(items)
+                .block(Block::default().borders(Borders::ALL).title("Drill-down (Enter: show in AVC list)"))
+                .highlight_style(Style::default().fg(Color::Yellow)),
+            right[2],
+            &mut self.state.list_state,
         );
     }
     fn render_modules<B: Backend>(&mut self, f: &mut Frame<B>, area: Rect) {
@@ -1243,16 +3079,39 @@ impl App {
         .modules
         .iter()
         .map(|m| {
-            ListItem::new(format!(
-                "{} {}",
+            let overridden = self.module_manager.overridden_by(m);
+            let layer = match overridden {
+                Some(higher) => format!("overridden by {}", higher.priority),
+                None => {
+                    let lower = self.module_manager.overrides(m);
+                    if lower.is_empty() {
+                        String::new()
+                    } else {
+                        format!("overrides {}", lower.iter().map(|p| p.to_string()).collect::<Vec<_>>().join(", "))
+                    }
+                }
+            };
+            let item = ListItem::new(format!(
+                "{} {:<24} {:>3} {:<3} {:<6} {}",
                 if m.enabled { "[+]" } else { "[-]" },
-                    m.name
-            ))
+                    m.name,
+                    m.priority,
+                    m.language,
+                    if ModuleManager::is_local(m) { "local" } else { "distro" },
+                    layer
+            ));
+            if overridden.is_some() {
+                item.style(Style::default().fg(Color::DarkGray))
+            } else if !m.enabled {
+                item.style(Style::default().fg(Color::Red))
+            } else {
+                item
+            }
         })
         .collect();
         f.render_stateful_widget(
             List::new(items)
-            .block(Block::default().borders(Borders::ALL).title("Modules"))
+            .block(Block::default().borders(Borders::ALL).title("Modules: [+] enabled, [-] disabled (Enter toggle, 'v' source, 'a' install at priority, 'D' remove this priority, 'P' project)"))
             .highlight_style(Style::default().fg(Color::Yellow)),
                                  area,
                                  &mut self.state.list_state,
@@ -1266,6 +3125,17 @@ impl App {
             Some(avc::AVCSeverity::Low) => " (Low)",
             None => "",
         };
+        let source_text = self
+            .avc_manager
+            .active_source
+            .as_ref()
+            .map(|s| format!(" [{}]", s.label()))
+            .unwrap_or_default();
+        let query_text = if self.avc_manager.query.text.is_empty() {
+            String::new()
+        } else {
+            format!(" {{{}}}", self.avc_manager.query.text)
+        };
         let items: Vec<ListItem> = alerts
         .iter()
         .map(|a| {
@@ -1274,18 +3144,77 @@ impl App {
                 avc::AVCSeverity::Medium => "[~]",
                 avc::AVCSeverity::Low => "[ ]",
             };
-            ListItem::new(format!("{} {} {} {}", severity_mark, a.comm, a.permission, a.path))
+            let triage_tag = match self.triage_manager.state_of(a) {
+                TriageState::New => String::new(),
+                state => format!(" [{}]", state.label()),
+            };
+            let text = format!("{} {} {} {}{}", severity_mark, a.comm, a.permission, a.path, triage_tag);
+            match self.avc_grouping.key(a) {
+                Some(group) => ListItem::new(Line::from(vec![
+                    Span::styled(format!("{: <28} ", group), Style::default().fg(Color::Cyan)),
+                    Span::raw(text),
+                ])),
+                None => ListItem::new(text),
+            }
+        })
+        .collect();
+        let grouping_text = if self.avc_grouping == avc::AVCGrouping::None {
+            String::new()
+        } else {
+            let groups: std::collections::HashSet<_> = alerts.iter().map(|a| self.avc_grouping.key(a)).collect();
+            format!(" by {} ({} groups)", self.avc_grouping.label(), groups.len())
+        };
+        f.render_stateful_widget(
+            List::new(items)
+            .block(Block::default().borders(Borders::ALL).title(format!("AVC Alerts{}{}{}{} ('f' severity, '/' query, 'g' group, 't'/'T' triage)", filter_text, grouping_text, query_text, source_text)))
+            .highlight_style(Style::default().fg(Color::Yellow)),
+                                 area,
+                                 &mut self.state.list_state,
+        );
+    }
+    fn render_listeners<B: Backend>(&mut self, f: &mut Frame<B>, area: Rect) {
+        let findings = check_listeners(&self.listeners, &self.port_manager);
+        let items: Vec<ListItem> = self
+        .listeners
+        .iter()
+        .map(|l| {
+            let port_type = self
+                .port_manager
+                .type_of(l.port, &l.protocol)
+                .map(|p| p.context.as_str())
+                .unwrap_or("no type");
+            let owner = match l.pid {
+                Some(pid) => format!("{} ({}) {}", l.process, pid, if l.context.is_empty() { "?" } else { l.domain() }),
+                None => "?".to_string(),
+            };
+            let line = format!("{}/{} {} -> {} | {}", l.port, l.protocol, l.address, port_type, owner);
+            match findings.iter().find(|fd| fd.listener.port == l.port && fd.listener.protocol == l.protocol && fd.listener.pid == l.pid) {
+                Some(fd) => ListItem::new(format!("[!] {} | {}", line, fd.issue)).style(Style::default().fg(Color::Red)),
+                None => ListItem::new(format!("    {}", line)),
+            }
         })
         .collect();
         f.render_stateful_widget(
             List::new(items)
-            .block(Block::default().borders(Borders::ALL).title(format!("AVC Alerts{} (Press 'f' to filter)", filter_text)))
+            .block(
+                Block::default()
+                .borders(Borders::ALL)
+                .title(format!(
+                    "Listening sockets: {} flagged (Enter: label port for service, L: port types)",
+                    findings.len()
+                )),
+            )
             .highlight_style(Style::default().fg(Color::Yellow)),
                                  area,
                                  &mut self.state.list_state,
         );
     }
+
     fn render_ports<B: Backend>(&mut self, f: &mut Frame<B>, area: Rect) {
+        if self.ports_show_listeners {
+            self.render_listeners(f, area);
+            return;
+        }
         let items: Vec<ListItem> = self
         .port_manager
         .ports
@@ -1297,7 +3226,7 @@ impl App {
             .block(
                 Block::default()
                 .borders(Borders::ALL)
-                .title("Ports (Press 'a' to add)"),
+                .title("Ports (Press 'a' to add, 'E' to change type, 'L' for listening sockets)"),
             )
             .highlight_style(Style::default().fg(Color::Yellow)),
                                  area,
@@ -1305,18 +3234,126 @@ impl App {
         );
     }
     fn render_contexts<B: Backend>(&mut self, f: &mut Frame<B>, area: Rect) {
-        let items: Vec<ListItem> = self
-        .file_context_manager
-        .contexts
+        let equivalences = self.get_visible_equivalences();
+        let contexts = self.get_visible_file_contexts();
+        let title = if self.fc_show_all {
+            format!(
+                "File Contexts: full database, {} rules, {} equivalences (* local, H: local only, 'a' to add)",
+                contexts.len(),
+                equivalences.len()
+            )
+        } else {
+            format!(
+                "File Contexts: local customizations, {} rules, {} equivalences (H: full database, 'a' to add)",
+                contexts.len(),
+                equivalences.len()
+            )
+        };
+        let mut items: Vec<ListItem> = equivalences
         .iter()
-        .map(|c| ListItem::new(format!("{} -> {}", c.path, c.context)))
+        .map(|e| {
+            ListItem::new(format!(
+                "{}{} = {} (equivalence)",
+                if e.local { "* " } else { "  " },
+                e.path,
+                e.equal
+            ))
+            .style(Style::default().fg(Color::Cyan))
+        })
         .collect();
+        items.extend(contexts
+        .iter()
+        .map(|c| {
+            ListItem::new(format!(
+                "{}{} [{}] -> {}",
+                if c.local { "* " } else { "  " },
+                c.path,
+                c.file_type.label(),
+                c.full_context()
+            ))
+        }));
         f.render_stateful_widget(
             List::new(items)
             .block(
                 Block::default()
                 .borders(Borders::ALL)
-                .title("File Contexts (Press 'a' to add)"),
+                .title(title),
+            )
+            .highlight_style(Style::default().fg(Color::Yellow)),
+                                 area,
+                                 &mut self.state.list_state,
+        );
+    }
+    fn render_network<B: Backend>(&mut self, f: &mut Frame<B>, area: Rect) {
+        let mut items: Vec<ListItem> = self
+        .interface_manager
+        .interfaces
+        .iter()
+        .map(|i| {
+            ListItem::new(format!("{}interface {} -> {}", if i.local { "* " } else { "  " }, i.name, i.context))
+            .style(Style::default().fg(Color::Cyan))
+        })
+        .collect();
+        items.extend(self.node_manager.nodes.iter().map(|n| {
+            ListItem::new(format!(
+                "{}node {} ({}) -> {}",
+                if n.local { "* " } else { "  " },
+                n.cidr(),
+                n.protocol,
+                n.context
+            ))
+        }));
+        f.render_stateful_widget(
+            List::new(items)
+            .block(
+                Block::default()
+                .borders(Borders::ALL)
+                .title(format!(
+                    "Network: {} interfaces, {} nodes (* local, 'a' add, 'E' modify, Enter remove)",
+                    self.interface_manager.interfaces.len(),
+                    self.node_manager.nodes.len()
+                )),
+            )
+            .highlight_style(Style::default().fg(Color::Yellow)),
+                                 area,
+                                 &mut self.state.list_state,
+        );
+    }
+    fn render_users<B: Backend>(&mut self, f: &mut Frame<B>, area: Rect) {
+        let mut items: Vec<ListItem> = self
+        .user_manager
+        .logins
+        .iter()
+        .map(|l| {
+            ListItem::new(format!(
+                "{}login {} -> {} {}",
+                if l.local { "* " } else { "  " },
+                l.login,
+                l.seuser,
+                l.range
+            ))
+            .style(Style::default().fg(Color::Cyan))
+        })
+        .collect();
+        items.extend(self.user_manager.users.iter().map(|u| {
+            ListItem::new(format!(
+                "{}user {} roles: {} {}",
+                if u.local { "* " } else { "  " },
+                u.name,
+                u.roles.join(" "),
+                u.range
+            ))
+        }));
+        f.render_stateful_widget(
+            List::new(items)
+            .block(
+                Block::default()
+                .borders(Borders::ALL)
+                .title(format!(
+                    "SELinux Users: {} login mappings, {} users (* local, 'a' add, 'E' modify, Enter remove)",
+                    self.user_manager.logins.len(),
+                    self.user_manager.users.len()
+                )),
             )
             .highlight_style(Style::default().fg(Color::Yellow)),
                                  area,
@@ -1412,15 +3449,155 @@ impl App {
     }
 }
 
+/// Подсветка строки CIL: оператор после скобки, типы `*_t`, значения и комментарии
+fn highlight_cil(line: &str) -> Line<'static> {
+    let mut spans = Vec::new();
+    let mut rest = line;
+    let mut after_paren = false;
+    while let Some(c) = rest.chars().next() {
+        let (token, style) = if c == ';' {
+            (rest, Style::default().fg(Color::DarkGray))
+        } else if c == '(' || c == ')' {
+            (&rest[..1], Style::default().fg(Color::DarkGray))
+        } else if c.is_whitespace() {
+            let end = rest.find(|c: char| !c.is_whitespace()).unwrap_or(rest.len());
+            (&rest[..end], Style::default())
+        } else {
+            let end = rest.find(|c: char| c.is_whitespace() || c == '(' || c == ')' || c == ';').unwrap_or(rest.len());
+            let word = &rest[..end];
+            let style = if after_paren {
+                Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD)
+            } else if word.ends_with("_t") || word == "self" {
+                Style::default().fg(Color::Green)
+            } else if word == "true" || word == "false" || word.starts_with(|c: char| c.is_ascii_digit() || c == '"') {
+                Style::default().fg(Color::Magenta)
+            } else {
+                Style::default()
+            };
+            (word, style)
+        };
+        if !token.trim().is_empty() {
+            after_paren = token == "(";
+        }
+        spans.push(Span::styled(token.to_string(), style));
+        rest = &rest[token.len()..];
+    }
+    Line::from(spans)
+}
+
+/// "NAME [pp|cil]" из окна создания модуля; без формата — по наличию audit2allow
+fn parse_module_name_input(input: &str) -> Option<(String, ModuleFormat)> {
+    match input.split_whitespace().collect::<Vec<_>>().as_slice() {
+        [name] => Some((name.to_string(), ModuleFormat::default_for_system())),
+        [name, format] => Some((name.to_string(), ModuleFormat::parse(format)?)),
+        _ => None,
+    }
+}
+
+/// "PORT[-PORT] PROTO TYPE" из окон добавления и изменения порта
+fn parse_port_input(input: &str) -> Option<(PortRange, String, String)> {
+    match input.split_whitespace().collect::<Vec<_>>().as_slice() {
+        [port, proto, ctx] if PROTOCOLS.contains(proto) => Some((port.parse().ok()?, proto.to_string(), ctx.to_string())),
+        _ => None,
+    }
+}
+
+/// "IFACE TYPE [RANGE]" или "ADDR[/PREFIX] TYPE [RANGE]" -> (цель, тип, диапазон)
+fn parse_network_input(input: &str) -> Option<(String, String, String)> {
+    let words: Vec<&str> = input.split_whitespace().collect();
+    let (target, ctx, range) = match words.as_slice() {
+        [target, ctx] => (*target, *ctx, ""),
+        [target, ctx, range] => (*target, *ctx, *range),
+        _ => return None,
+    };
+    // Не адрес — значит имя интерфейса; опечатку в адресе за имя не принимаем
+    let is_iface = !target.starts_with(|c: char| c.is_ascii_digit()) && !target.contains(['/', ':']);
+    if parse_cidr(target).is_none() && !is_iface {
+        return None;
+    }
+    Some((target.to_string(), ctx.to_string(), range.to_string()))
+}
+
+/// "user NAME ROLE[,ROLE] [RANGE]" — пользователь SELinux,
+/// "LOGIN SEUSER [RANGE]" — сопоставление логина
+fn parse_user_input(input: &str) -> Option<UserEntry> {
+    let words: Vec<&str> = input.split_whitespace().collect();
+    match words.as_slice() {
+        ["user", name, roles, rest @ ..] if rest.len() <= 1 => Some(UserEntry::User(SELinuxUser {
+            name: name.to_string(),
+            roles: roles.split(',').filter(|r| !r.is_empty()).map(String::from).collect(),
+            range: rest.first().unwrap_or(&"").to_string(),
+            local: true,
+        })),
+        [login, seuser, rest @ ..] if *login != "user" && rest.len() <= 1 => Some(UserEntry::Login(LoginMapping {
+            login: login.to_string(),
+            seuser: seuser.to_string(),
+            range: rest.first().unwrap_or(&"").to_string(),
+            local: true,
+        })),
+        _ => None,
+    }
+}
+
+/// "PATH [TYPE] [-f X]" из окна добавления правила: (выражение пути, тип, тип файла).
+/// Тип может отсутствовать, пока пользователь вводит путь.
+fn parse_context_input(input: &str) -> Option<(String, Option<String>, FcFileType)> {
+    let mut parts: Vec<&str> = input.split_whitespace().collect();
+    // Необязательный хвост "-f X" задает тип файла
+    let mut file_type = FcFileType::All;
+    if parts.len() >= 2 && parts[parts.len() - 2] == "-f" {
+        file_type = FcFileType::from_flag(parts[parts.len() - 1])?;
+        parts.truncate(parts.len() - 2);
+    }
+    match parts.as_slice() {
+        [] => None,
+        [path] => Some((path.to_string(), None, file_type)),
+        [path @ .., ctx] => Some((path.join(" "), Some(ctx.to_string()), file_type)),
+    }
+}
+
+/// Путь для проверки правила — литеральный префикс выражения без завершающего `/`
+fn rule_test_path(spec: &str) -> String {
+    let stem = literal_stem(spec);
+    match stem.trim_end_matches('/') {
+        "" if stem.starts_with('/') => "/".to_string(),
+        trimmed => trimmed.to_string(),
+    }
+}
+
 fn main() -> Result<()> {
     let cli = Cli::parse();
+
+    // Обмен списком игнорируемых отказов между хостами — без запуска TUI
+    if cli.export_triage.is_some() || cli.import_triage.is_some() {
+        let mut triage = TriageManager::new();
+        if let Some(path) = &cli.import_triage {
+            let count = triage.import_from_file(path)?;
+            println!("Imported {} triage entries from {}", count, path.display());
+        }
+        if let Some(path) = &cli.export_triage {
+            triage.export_to_file(path, true)?;
+            println!("Exported ignore list to {}", path.display());
+        }
+        return Ok(());
+    }
+    let avc_source = AVCSource::from_cli(&cli.avc_source, cli.logfile.as_deref())?;
+    // --since/--until — сокращения для since:/until: в тексте запроса
+    let mut query_text = cli.avc_query.clone().unwrap_or_default();
+    if let Some(since) = &cli.since {
+        query_text = format!("since:\"{}\" {}", since, query_text);
+    }
+    if let Some(until) = &cli.until {
+        query_text = format!("until:\"{}\" {}", until, query_text);
+    }
+    let avc_query = AVCQuery::parse(&query_text)?;
     enable_raw_mode()?;
     let mut stdout = io::stdout();
     execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
     let backend = CrosstermBackend::new(stdout);
     let mut terminal = Terminal::new(backend)?;
 
-    let mut app = App::new(cli.simulate, cli.debug, cli.update_interval, cli.ascii)?;
+    let mut app = App::new(cli.simulate, cli.debug, cli.update_interval, cli.ascii, avc_source, avc_query, cli.retention_days)?;
     let res = run_app(&mut terminal, &mut app);
 
     disable_raw_mode()?;
@@ -1437,6 +3614,29 @@ fn main() -> Result<()> {
     Ok(())
 }
 
+/// Приостанавливает интерфейс и открывает файл в $VISUAL/$EDITOR (по умолчанию vi)
+fn run_editor<B: Backend>(terminal: &mut Terminal<B>, path: &Path) -> Result<()> {
+    let editor = std::env::var("VISUAL")
+        .or_else(|_| std::env::var("EDITOR"))
+        .unwrap_or_else(|_| "vi".to_string());
+    disable_raw_mode()?;
+    execute!(io::stdout(), LeaveAlternateScreen, DisableMouseCapture)?;
+    // Через sh: в $EDITOR бывают аргументы ("code -w")
+    let status = Command::new("sh")
+        .arg("-c")
+        .arg(format!("{} \"$1\"", editor))
+        .arg("sh")
+        .arg(path)
+        .status();
+    execute!(io::stdout(), EnterAlternateScreen, EnableMouseCapture)?;
+    enable_raw_mode()?;
+    terminal.clear()?;
+    match status? {
+        s if s.success() => Ok(()),
+        s => Err(anyhow::anyhow!("{} exited with {}", editor, s)),
+    }
+}
+
 fn run_app<B: Backend>(terminal: &mut Terminal<B>, app: &mut App) -> Result<()> {
     loop {
         terminal.draw(|f| app.ui(f))?;
@@ -1445,6 +3645,10 @@ fn run_app<B: Backend>(terminal: &mut Terminal<B>, app: &mut App) -> Result<()>
                 app.handle_key_event(key.code)?;
             }
         }
+        if let Some(path) = app.pending_edit.take() {
+            let result = run_editor(terminal, &path);
+            app.finish_edit(result);
+        }
         app.tick()?;
         if app.should_quit {
             return Ok(());
@@ -0,0 +1,511 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use anyhow::{Result, anyhow};
+use regex::Regex;
+use crate::module_source::parse_cil;
+
+/// Makefile из selinux-policy-devel: собирает `.te/.fc/.if` с макросами m4
+const DEVEL_MAKEFILE: &str = "/usr/share/selinux/devel/Makefile";
+
+/// Как собирается модуль проекта
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BuildMethod {
+    /// `.te` без макросов: checkmodule + semodule_package
+    Checkmodule,
+    /// `.te` с макросами refpolicy или есть `.if`: make -f devel/Makefile
+    DevelMakefile,
+    /// Модуль на CIL: ставится без компиляции
+    Cil,
+}
+
+impl BuildMethod {
+    pub fn label(&self) -> &'static str {
+        match self {
+            BuildMethod::Checkmodule => "checkmodule + semodule_package",
+            BuildMethod::DevelMakefile => "devel Makefile (m4 macros)",
+            BuildMethod::Cil => "CIL, no compilation",
+        }
+    }
+}
+
+/// Ошибка в исходнике проекта: файл, строка (с 1) и описание
+#[derive(Debug, Clone)]
+pub struct Problem {
+    pub file: String,
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.file, self.line, self.message)
+    }
+}
+
+/// Проект модуля политики: `<name>.te` и необязательные `<name>.fc`, `<name>.if`
+/// (или `<name>.cil`) в `~/.config/selab/modules/<name>/`.
+#[derive(Debug, Clone)]
+pub struct ModuleProject {
+    pub name: String,
+    pub dir: PathBuf,
+}
+
+impl ModuleProject {
+    pub fn projects_dir() -> PathBuf {
+        let mut dir = dirs::config_dir().unwrap_or_else(std::env::temp_dir);
+        dir.push("selab");
+        dir.push("modules");
+        dir
+    }
+
+    /// Имена существующих проектов
+    pub fn list() -> Vec<String> {
+        let mut names: Vec<String> = fs::read_dir(Self::projects_dir())
+            .map(|entries| {
+                entries
+                    .filter_map(|e| e.ok())
+                    .filter(|e| e.path().is_dir())
+                    .filter_map(|e| e.file_name().to_str().map(String::from))
+                    .collect()
+            })
+            .unwrap_or_default();
+        names.sort();
+        names
+    }
+
+    /// Открывает проект; новому создает заготовку `.te`
+    pub fn open(name: &str) -> Result<Self> {
+        if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+            return Err(anyhow!("Module name may contain only letters, digits and '_'"));
+        }
+        let project = Self {
+            name: name.to_string(),
+            dir: Self::projects_dir().join(name),
+        };
+        fs::create_dir_all(&project.dir)?;
+        if !project.file("te").exists() && !project.file("cil").exists() {
+            fs::write(project.file("te"), skeleton_te(name))?;
+        }
+        Ok(project)
+    }
+
+    /// Путь к файлу проекта с расширением `ext`
+    pub fn file(&self, ext: &str) -> PathBuf {
+        self.dir.join(format!("{}.{}", self.name, ext))
+    }
+
+    fn read(&self, ext: &str) -> Option<String> {
+        fs::read_to_string(self.file(ext)).ok()
+    }
+
+    /// Файлы проекта и число строк в них; None — файла нет
+    pub fn files(&self) -> Vec<(String, Option<usize>)> {
+        let exts: &[&str] = if self.build_method() == BuildMethod::Cil { &["cil"] } else { &["te", "fc", "if"] };
+        exts.iter()
+            .map(|ext| (format!("{}.{}", self.name, ext), self.read(ext).map(|s| s.lines().count())))
+            .collect()
+    }
+
+    pub fn build_method(&self) -> BuildMethod {
+        match self.read("te") {
+            None if self.file("cil").exists() => BuildMethod::Cil,
+            Some(te) if te.contains("policy_module(") || self.file("if").exists() => BuildMethod::DevelMakefile,
+            _ => BuildMethod::Checkmodule,
+        }
+    }
+
+    /// Проверки до компиляции: объявление модуля, синтаксис правил, а для `.te`
+    /// без макросов — что все типы, классы и разрешения объявлены или запрошены в require
+    pub fn validate(&self) -> Vec<Problem> {
+        let mut problems = Vec::new();
+        match self.build_method() {
+            BuildMethod::Cil => {
+                let text = self.read("cil").unwrap_or_default();
+                if let Err(e) = parse_cil(&text) {
+                    problems.push(self.problem("cil", 1, e.to_string()));
+                }
+            }
+            method => {
+                let te = self.read("te").unwrap_or_default();
+                self.validate_te(&te, method == BuildMethod::Checkmodule, &mut problems);
+                if let Some(fc) = self.read("fc") {
+                    self.validate_fc(&fc, &mut problems);
+                }
+                if let Some(interface) = self.read("if") {
+                    if let Some(line) = unbalanced_line(&interface, '(', ')') {
+                        problems.push(self.problem("if", line, "Unbalanced parentheses".to_string()));
+                    }
+                }
+            }
+        }
+        problems
+    }
+
+    fn problem(&self, ext: &str, line: usize, message: String) -> Problem {
+        Problem {
+            file: format!("{}.{}", self.name, ext),
+            line,
+            message,
+        }
+    }
+
+    fn validate_te(&self, te: &str, plain: bool, problems: &mut Vec<Problem>) {
+        lazy_static::lazy_static! {
+            static ref MODULE_RE: Regex = Regex::new(r"^(?:module\s+(\w+)\s+[\d.]+|policy_module\(\s*(\w+)\s*,)").unwrap();
+            static ref RULE_RE: Regex = Regex::new(
+                r"^(?:allow|auditallow|dontaudit|neverallow)\s+(\{[^}]*\}|\S+)\s+(\{[^}]*\}|\S+?)\s*:\s*(\{[^}]*\}|\S+)\s+(~?\{[^}]*\}|\S+)$"
+            ).unwrap();
+        }
+
+        let text = strip_comments(te, '#');
+        if let Some(line) = unbalanced_line(&text, '{', '}') {
+            problems.push(self.problem("te", line, "Unbalanced braces".to_string()));
+            return;
+        }
+        let statements = split_statements(&text);
+        match statements.first().and_then(|(_, s)| MODULE_RE.captures(s)) {
+            Some(caps) => {
+                let declared = caps.get(1).or_else(|| caps.get(2)).map(|m| m.as_str()).unwrap_or("");
+                if declared != self.name {
+                    let line = line_of(&text, statements[0].0);
+                    problems.push(self.problem("te", line, format!("Module is named {} but the project is {}", declared, self.name)));
+                }
+            }
+            None => problems.push(self.problem("te", 1, format!("Expected 'module {} 1.0;' first", self.name))),
+        }
+        if !plain {
+            return;
+        }
+
+        // Что известно модулю: свои типы и запрошенные в require
+        let mut types: HashSet<String> = HashSet::from(["self".to_string()]);
+        let mut classes: HashMap<String, HashSet<String>> = HashMap::new();
+        let mut rules = Vec::new();
+        for (offset, stmt) in &statements {
+            let (keyword, rest) = stmt.split_once(char::is_whitespace).unwrap_or((stmt, ""));
+            match keyword {
+                "require" => {
+                    let body = rest.trim().trim_start_matches('{').trim_end_matches('}');
+                    for (_, req) in split_statements(body) {
+                        let (kind, names) = req.split_once(char::is_whitespace).unwrap_or((&req, ""));
+                        match kind {
+                            "type" | "attribute" => types.extend(names.split(',').map(|n| n.trim().to_string())),
+                            "class" => {
+                                let (class, perms) = names.trim().split_once(char::is_whitespace).unwrap_or((names.trim(), ""));
+                                classes.entry(class.to_string()).or_default().extend(set_items(perms));
+                            }
+                            _ => {}
+                        }
+                    }
+                }
+                "type" | "attribute" => {
+                    if let Some(name) = rest.split([',', ' ']).next() {
+                        types.insert(name.trim().to_string());
+                    }
+                }
+                "allow" | "auditallow" | "dontaudit" | "neverallow" => match RULE_RE.captures(stmt) {
+                    Some(caps) => rules.push((*offset, caps)),
+                    None => problems.push(self.problem(
+                        "te",
+                        line_of(&text, *offset),
+                        format!("Malformed rule, expected '{} SOURCE TARGET:CLASS {{ PERMS }};'", keyword),
+                    )),
+                },
+                _ => {}
+            }
+        }
+
+        for (offset, caps) in rules {
+            let line = line_of(&text, offset);
+            let mut unknown = Vec::new();
+            for t in set_items(&caps[1]).into_iter().chain(set_items(&caps[2])) {
+                if !types.contains(&t) {
+                    unknown.push(format!("type {}", t));
+                }
+            }
+            for class in set_items(&caps[3]) {
+                let Some(known) = classes.get(&class) else {
+                    unknown.push(format!("class {}", class));
+                    continue;
+                };
+                for perm in set_items(&caps[4]) {
+                    if perm != "*" && !known.contains(&perm) {
+                        unknown.push(format!("{}:{}", class, perm));
+                    }
+                }
+            }
+            if !unknown.is_empty() {
+                problems.push(self.problem("te", line, format!("Not declared or required: {}", unknown.join(", "))));
+            }
+        }
+        problems.sort_by_key(|p| (p.file != format!("{}.te", self.name), p.line));
+    }
+
+    /// Строки `.fc`: `PATH [-TYPE] CONTEXT`
+    fn validate_fc(&self, fc: &str, problems: &mut Vec<Problem>) {
+        lazy_static::lazy_static! {
+            static ref FC_RE: Regex = Regex::new(
+                r"^\S+\s+(?:--\s+|-[dcbslp]\s+)?(?:gen_context\(.+\)|<<none>>|\w+:\w+:\w+(?::\S+)?)$"
+            ).unwrap();
+        }
+        for (i, line) in fc.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            if !line.is_empty() && !FC_RE.is_match(line) {
+                problems.push(self.problem("fc", i + 1, "Expected 'PATH [-TYPE] CONTEXT'".to_string()));
+            }
+        }
+    }
+
+    /// Проверяет и компилирует проект, возвращает путь к пакету для `semodule -i`
+    pub fn build(&self, simulation: bool) -> Result<PathBuf> {
+        if let Some(problem) = self.validate().first() {
+            return Err(anyhow!("{}", problem));
+        }
+        let method = self.build_method();
+        if method == BuildMethod::Cil {
+            return Ok(self.file("cil"));
+        }
+        let pp = self.file("pp");
+        if simulation {
+            return Ok(pp);
+        }
+
+        match method {
+            BuildMethod::DevelMakefile => {
+                if !Path::new(DEVEL_MAKEFILE).exists() {
+                    return Err(anyhow!("{} not found (install selinux-policy-devel)", DEVEL_MAKEFILE));
+                }
+                self.run("make", &["-f", DEVEL_MAKEFILE, &format!("{}.pp", self.name)])?;
+            }
+            _ => {
+                let module = format!("{}.mod", self.name);
+                self.run("checkmodule", &["-M", "-m", "-o", &module, &format!("{}.te", self.name)])?;
+                let fc = format!("{}.fc", self.name);
+                let mut args = vec!["-o", pp.to_str().unwrap_or_default(), "-m", module.as_str()];
+                if self.file("fc").exists() {
+                    args.extend(["-f", fc.as_str()]);
+                }
+                self.run("semodule_package", &args)?;
+            }
+        }
+        Ok(pp)
+    }
+
+    fn run(&self, program: &str, args: &[&str]) -> Result<()> {
+        let output = Command::new(program).current_dir(&self.dir).args(args).output()?;
+        if !output.status.success() {
+            let mut error = String::from_utf8_lossy(&output.stderr).trim().to_string();
+            if error.is_empty() {
+                error = String::from_utf8_lossy(&output.stdout).trim().to_string();
+            }
+            return Err(anyhow!("{} failed: {}", program, error));
+        }
+        Ok(())
+    }
+}
+
+/// Заготовка `.te` для нового проекта, собирается без правок
+fn skeleton_te(name: &str) -> String {
+    format!(
+        "module {name} 1.0;\n\
+         \n\
+         require {{\n\
+         \tclass file {{ read open getattr }};\n\
+         }}\n\
+         \n\
+         type {name}_t;\n\
+         type {name}_data_t;\n\
+         \n\
+         allow {name}_t {name}_data_t:file {{ read open getattr }};\n"
+    )
+}
+
+/// Заменяет комментарии пробелами, сохраняя смещения строк
+fn strip_comments(text: &str, marker: char) -> String {
+    text.lines()
+        .map(|line| match line.find(marker) {
+            Some(i) => format!("{}{}", &line[..i], " ".repeat(line.len() - i)),
+            None => line.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Строка, на которой нарушается баланс скобок (лишняя закрывающая или незакрытая)
+fn unbalanced_line(text: &str, open: char, close: char) -> Option<usize> {
+    let mut stack = Vec::new();
+    for (i, line) in text.lines().enumerate() {
+        for c in line.chars() {
+            if c == open {
+                stack.push(i + 1);
+            } else if c == close && stack.pop().is_none() {
+                return Some(i + 1);
+            }
+        }
+    }
+    stack.pop()
+}
+
+fn line_of(text: &str, offset: usize) -> usize {
+    text[..offset.min(text.len())].matches('\n').count() + 1
+}
+
+/// Делит текст `.te` на операторы: до `;` вне скобок, а блоки `require`,
+/// `optional`, `if`/`else` — до закрывающей `}`. Возвращает (смещение, текст).
+fn split_statements(text: &str) -> Vec<(usize, String)> {
+    let mut statements = Vec::new();
+    let mut depth = 0usize;
+    let mut start = 0;
+    for (i, c) in text.char_indices() {
+        match c {
+            '{' => depth += 1,
+            '}' => {
+                depth = depth.saturating_sub(1);
+                let stmt = text[start..=i].trim();
+                let is_block = ["require", "optional", "if", "else", "tunable_policy"]
+                    .iter()
+                    .any(|k| stmt.starts_with(k));
+                if depth == 0 && is_block {
+                    statements.push((start + leading_ws(&text[start..]), stmt.to_string()));
+                    start = i + 1;
+                }
+            }
+            ';' if depth == 0 => {
+                let stmt = text[start..i].trim();
+                if !stmt.is_empty() {
+                    statements.push((start + leading_ws(&text[start..]), stmt.split_whitespace().collect::<Vec<_>>().join(" ")));
+                }
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    // Хвост без `;` — например, `policy_module(...)` в новом проекте
+    let rest = text[start..].trim();
+    if !rest.is_empty() {
+        statements.push((start + leading_ws(&text[start..]), rest.split_whitespace().collect::<Vec<_>>().join(" ")));
+    }
+    statements
+}
+
+fn leading_ws(s: &str) -> usize {
+    s.len() - s.trim_start().len()
+}
+
+/// `{ read open }`, `~{ write }` или одиночное имя -> элементы
+fn set_items(s: &str) -> Vec<String> {
+    s.trim()
+        .trim_start_matches('~')
+        .trim_start_matches('{')
+        .trim_end_matches('}')
+        .split_whitespace()
+        .map(String::from)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn project(name: &str) -> ModuleProject {
+        ModuleProject { name: name.to_string(), dir: PathBuf::new() }
+    }
+
+    fn check_te(te: &str, plain: bool) -> Vec<String> {
+        let mut problems = Vec::new();
+        project("myapp").validate_te(te, plain, &mut problems);
+        problems.iter().map(|p| p.to_string()).collect()
+    }
+
+    #[test]
+    fn skeleton_is_valid() {
+        assert!(check_te(&skeleton_te("myapp"), true).is_empty());
+    }
+
+    #[test]
+    fn module_name_must_match_project() {
+        assert_eq!(
+            check_te("# заголовок\n\nmodule other 1.0;\n", true),
+            ["myapp.te:3: Module is named other but the project is myapp"]
+        );
+        assert_eq!(check_te("type myapp_t;\n", true), ["myapp.te:1: Expected 'module myapp 1.0;' first"]);
+    }
+
+    #[test]
+    fn unbalanced_braces_report_line() {
+        let te = "module myapp 1.0;\n\
+                  require {\n\
+                  \ttype bin_t;\n\
+                  \n\
+                  allow myapp_t bin_t:file { read };\n";
+        assert_eq!(check_te(te, true), ["myapp.te:2: Unbalanced braces"]);
+        // Скобка в комментарии не считается
+        assert_eq!(check_te("module myapp 1.0; # }\n", true), Vec::<String>::new());
+        assert_eq!(check_te("module myapp 1.0;\n}\n", true), ["myapp.te:2: Unbalanced braces"]);
+    }
+
+    #[test]
+    fn undeclared_type_class_and_perm() {
+        let te = "module myapp 1.0;\n\
+                  require {\n\
+                  \ttype bin_t;\n\
+                  \tclass file { read open };\n\
+                  }\n\
+                  type myapp_t;\n\
+                  allow myapp_t bin_t:file { read open };\n\
+                  allow myapp_t etc_t:file read;\n\
+                  allow myapp_t bin_t:file { read write };\n\
+                  allow myapp_t self:tcp_socket { create };\n";
+        assert_eq!(
+            check_te(te, true),
+            [
+                "myapp.te:8: Not declared or required: type etc_t",
+                "myapp.te:9: Not declared or required: file:write",
+                "myapp.te:10: Not declared or required: class tcp_socket",
+            ]
+        );
+    }
+
+    #[test]
+    fn policy_module_skips_require_check() {
+        let te = "policy_module(myapp, 1.0.0)\n\
+                  \n\
+                  type myapp_t;\n\
+                  allow myapp_t etc_t:file read_file_perms;\n";
+        assert!(check_te(te, false).is_empty());
+        // Имя модуля проверяется и в макросе, даже если за ним ничего нет
+        let te = "policy_module(other, 1.0.0)\n";
+        assert_eq!(check_te(te, false), ["myapp.te:1: Module is named other but the project is myapp"]);
+    }
+
+    #[test]
+    fn split_statements_keeps_blocks_and_offsets() {
+        let text = "module myapp 1.0;\nrequire {\n\ttype bin_t;\n}\nallow myapp_t\n\tbin_t:file { read };\n";
+        let statements = split_statements(text);
+        let lines: Vec<(usize, &str)> = statements.iter().map(|(o, s)| (line_of(text, *o), s.as_str())).collect();
+        assert_eq!(
+            lines,
+            [
+                (1, "module myapp 1.0"),
+                (2, "require {\n\ttype bin_t;\n}"),
+                (5, "allow myapp_t bin_t:file { read }"),
+            ]
+        );
+    }
+
+    #[test]
+    fn malformed_fc_line() {
+        let fc = "/opt/myapp(/.*)?\t\tgen_context(system_u:object_r:myapp_data_t,s0)\n\
+                  /opt/myapp/bin/run\t--\tsystem_u:object_r:myapp_exec_t:s0\n\
+                  # комментарий\n\
+                  /opt/myapp/tmp\t\t<<none>>\n\
+                  /opt/myapp/log -f myapp_log_t\n";
+        let mut problems = Vec::new();
+        project("myapp").validate_fc(fc, &mut problems);
+        let problems: Vec<String> = problems.iter().map(|p| p.to_string()).collect();
+        assert_eq!(problems, ["myapp.fc:5: Expected 'PATH [-TYPE] CONTEXT'"]);
+    }
+}
@@ -0,0 +1,264 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+use anyhow::{Result, anyhow};
+use crate::modules::{ModuleManager, SELinuxModule};
+
+/// Выражение CIL: атом или список в скобках.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Sexp {
+    Atom(String),
+    List(Vec<Sexp>),
+}
+
+impl Sexp {
+    fn atom(&self) -> Option<&str> {
+        match self {
+            Sexp::Atom(s) => Some(s),
+            Sexp::List(_) => None,
+        }
+    }
+
+    /// Выражение в одну строку: `(and a (not b))`
+    pub fn flatten(&self) -> String {
+        match self {
+            Sexp::Atom(s) => s.clone(),
+            Sexp::List(items) => format!("({})", items.iter().map(Sexp::flatten).collect::<Vec<_>>().join(" ")),
+        }
+    }
+}
+
+/// Разбирает текст CIL в список выражений верхнего уровня.
+/// Комментарии (`;` до конца строки) пропускаются, строки в кавычках — один атом.
+pub fn parse_cil(text: &str) -> Result<Vec<Sexp>> {
+    let mut stack: Vec<Vec<Sexp>> = vec![Vec::new()];
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            ';' => {
+                while chars.next_if(|&c| c != '\n').is_some() {}
+            }
+            '(' => stack.push(Vec::new()),
+            ')' => {
+                let list = stack.pop().filter(|_| !stack.is_empty()).ok_or_else(|| anyhow!("Unbalanced ')'"))?;
+                stack.last_mut().unwrap().push(Sexp::List(list));
+            }
+            '"' => {
+                let mut s = String::new();
+                for c in chars.by_ref() {
+                    if c == '"' {
+                        break;
+                    }
+                    s.push(c);
+                }
+                stack.last_mut().unwrap().push(Sexp::Atom(s));
+            }
+            c if c.is_whitespace() => {}
+            c => {
+                let mut s = c.to_string();
+                while let Some(c) = chars.next_if(|&c| !c.is_whitespace() && c != '(' && c != ')' && c != ';') {
+                    s.push(c);
+                }
+                stack.last_mut().unwrap().push(Sexp::Atom(s));
+            }
+        }
+    }
+    if stack.len() != 1 {
+        return Err(anyhow!("Unbalanced '('"));
+    }
+    Ok(stack.pop().unwrap_or_default())
+}
+
+/// Что разрешает модуль: объявленные типы, allow-правила по доменам и булевы.
+#[derive(Debug, Clone, Default)]
+pub struct ModuleSummary {
+    pub types: Vec<String>,
+    /// Домен -> "цель класс { разрешения }" (с условием булевого, если есть)
+    pub allows: BTreeMap<String, Vec<String>>,
+    /// Имя и значение по умолчанию
+    pub booleans: Vec<(String, bool)>,
+}
+
+impl ModuleSummary {
+    pub fn from_cil(statements: &[Sexp]) -> Self {
+        let mut summary = Self::default();
+        summary.collect(statements, None);
+        summary
+    }
+
+    fn collect(&mut self, statements: &[Sexp], condition: Option<&str>) {
+        for stmt in statements {
+            let Sexp::List(items) = stmt else {
+                continue;
+            };
+            match (items.first().and_then(Sexp::atom), items.get(1..).unwrap_or(&[])) {
+                (Some("type"), [Sexp::Atom(name)]) => self.types.push(name.clone()),
+                (Some("allow"), [Sexp::Atom(source), Sexp::Atom(target), perms]) => {
+                    let mut rule = format!("{} {}", target, Self::class_perms(perms));
+                    if let Some(cond) = condition {
+                        rule.push_str(&format!(" [if {}]", cond));
+                    }
+                    self.allows.entry(source.clone()).or_default().push(rule);
+                }
+                (Some("boolean") | Some("tunable"), [Sexp::Atom(name), Sexp::Atom(value)]) => {
+                    self.booleans.push((name.clone(), value == "true"));
+                }
+                (Some("booleanif") | Some("tunableif"), [cond, branches @ ..]) => {
+                    for branch in branches {
+                        if let Sexp::List(b) = branch {
+                            let cond = match b.first().and_then(Sexp::atom) {
+                                Some("false") => format!("!{}", cond.flatten()),
+                                _ => cond.flatten(),
+                            };
+                            self.collect(b.get(1..).unwrap_or(&[]), Some(&cond));
+                        }
+                    }
+                }
+                // Контейнеры: pp, переведенный в CIL, оборачивает правила в optional
+                (Some("optional") | Some("block") | Some("in"), [_, body @ ..]) => self.collect(body, condition),
+                _ => {}
+            }
+        }
+    }
+
+    /// `(file (read open))` -> "file { read open }"; именованный набор — как есть
+    fn class_perms(perms: &Sexp) -> String {
+        match perms {
+            Sexp::List(cp) => match cp.as_slice() {
+                [Sexp::Atom(class), Sexp::List(p)] => {
+                    format!("{} {{ {} }}", class, p.iter().map(Sexp::flatten).collect::<Vec<_>>().join(" "))
+                }
+                _ => perms.flatten(),
+            },
+            Sexp::Atom(name) => name.clone(),
+        }
+    }
+
+    pub fn allow_count(&self) -> usize {
+        self.allows.values().map(Vec::len).sum()
+    }
+}
+
+/// Извлеченный модуль: текст CIL и сводка по нему.
+#[derive(Debug, Clone)]
+pub struct ModuleSource {
+    pub module: SELinuxModule,
+    pub path: PathBuf,
+    pub text: String,
+    pub summary: ModuleSummary,
+    /// Ошибка разбора — текст все равно показываем
+    pub parse_error: Option<String>,
+}
+
+impl ModuleSource {
+    /// Извлекает модуль в CIL (`semodule -E --cil`) в кэш. Файл из кэша
+    /// используется повторно, если он новее модуля в хранилище.
+    pub fn load(module: &SELinuxModule, simulation: bool) -> Result<Self> {
+        let dir = ModuleManager::cache_dir(module.priority);
+        let path = dir.join(format!("{}.cil", module.name));
+        let text = if simulation {
+            simulation_source(&module.name)
+        } else {
+            if !Self::cache_is_fresh(module, &path) {
+                ModuleManager::extract_module(module, true, &dir)?;
+            }
+            fs::read_to_string(&path)?
+        };
+        let (summary, parse_error) = match parse_cil(&text) {
+            Ok(statements) => (ModuleSummary::from_cil(&statements), None),
+            Err(e) => (ModuleSummary::default(), Some(e.to_string())),
+        };
+        Ok(Self {
+            module: module.clone(),
+            path,
+            text,
+            summary,
+            parse_error,
+        })
+    }
+
+    fn cache_is_fresh(module: &SELinuxModule, cached: &PathBuf) -> bool {
+        let mtime = |p: &PathBuf| fs::metadata(p).and_then(|m| m.modified()).ok();
+        match (mtime(cached), mtime(&store_path(module))) {
+            (Some(cache), Some(store)) => cache >= store,
+            (Some(_), None) => true,
+            _ => false,
+        }
+    }
+}
+
+/// Каталог модуля в хранилище: `/var/lib/selinux/<policy>/active/modules/<priority>/<name>`
+fn store_path(module: &SELinuxModule) -> PathBuf {
+    let policy = fs::read_to_string("/etc/selinux/config")
+        .ok()
+        .and_then(|c| {
+            c.lines()
+                .find_map(|l| l.trim().strip_prefix("SELINUXTYPE=").map(|v| v.trim().to_string()))
+        })
+        .unwrap_or_else(|| "targeted".to_string());
+    PathBuf::from(format!(
+        "/var/lib/selinux/{}/active/modules/{}/{}",
+        policy, module.priority, module.name
+    ))
+}
+
+/// Исходник модуля для режима симуляции
+fn simulation_source(name: &str) -> String {
+    match name {
+        "myapp" => "; Local module built from AVC denials\n\
+                    (type myapp_t)\n\
+                    (type myapp_exec_t)\n\
+                    (type myapp_data_t)\n\
+                    (roletype object_r myapp_exec_t)\n\
+                    (roletype object_r myapp_data_t)\n\
+                    (boolean myapp_can_network true)\n\
+                    (allow myapp_t myapp_data_t (file (read write open getattr)))\n\
+                    (allow myapp_t myapp_data_t (dir (search getattr)))\n\
+                    (allow myapp_t self (tcp_socket (create connect)))\n\
+                    (booleanif myapp_can_network\n    \
+                        (true\n        \
+                            (allow myapp_t http_port_t (tcp_socket (name_connect)))\n    \
+                        )\n\
+                    )\n"
+            .to_string(),
+        _ => format!(
+            "(optional {name}_optional\n    \
+                (type {name}_t)\n    \
+                (type {name}_var_lib_t)\n    \
+                (boolean {name}_enable_homedirs false)\n    \
+                (allow {name}_t {name}_var_lib_t (dir (read search open getattr)))\n    \
+                (allow {name}_t {name}_var_lib_t (file (read open getattr)))\n    \
+                (booleanif {name}_enable_homedirs\n        \
+                    (true\n            \
+                        (allow {name}_t user_home_t (file (read open)))\n        \
+                    )\n    \
+                )\n\
+            )\n"
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn summary_of_simulation_module() {
+        let statements = parse_cil(&simulation_source("myapp")).unwrap();
+        let summary = ModuleSummary::from_cil(&statements);
+        assert_eq!(summary.types, ["myapp_t", "myapp_exec_t", "myapp_data_t"]);
+        assert_eq!(summary.booleans, [("myapp_can_network".to_string(), true)]);
+        assert_eq!(summary.allow_count(), 4);
+        assert_eq!(
+            summary.allows["myapp_t"].last().unwrap(),
+            "http_port_t tcp_socket { name_connect } [if myapp_can_network]"
+        );
+    }
+
+    #[test]
+    fn empty_lists_do_not_panic() {
+        let statements = parse_cil("() (booleanif b ()) (tunableif t (false))").unwrap();
+        let summary = ModuleSummary::from_cil(&statements);
+        assert_eq!(summary.allow_count(), 0);
+    }
+}
@@ -1,13 +1,65 @@
 use serde::{Deserialize, Serialize};
+use std::fs;
+use std::os::unix::fs::{DirBuilderExt, PermissionsExt};
+use std::path::{Path, PathBuf};
 use std::process::Command;
-use anyhow::Result;
+use anyhow::{Result, anyhow};
 use regex::Regex;
+use crate::avc_source::command_exists;
+use crate::cil_module::emit_cil;
 
+/// Приоритет, с которым `semodule -i` ставит модуль по умолчанию
+pub const DEFAULT_PRIORITY: i32 = 400;
+
+/// Наибольший приоритет, с которым модули ставят пакеты: 100 — пакет политики
+/// дистрибутива, 200 — прочие пакеты (container-selinux и т.п.)
+pub const PACKAGE_PRIORITY: i32 = 200;
+
+/// Модуль в хранилище политики. Один и тот же модуль может быть установлен
+/// с несколькими приоритетами — действует запись с наибольшим.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SELinuxModule {
     pub name: String,
     pub enabled: bool,
     pub priority: i32,
+    /// Язык модуля: `pp`, `cil`; пусто в старых записях
+    #[serde(default)]
+    pub language: String,
+}
+
+/// В каком виде собирать модуль из AVC-отказов
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ModuleFormat {
+    /// audit2allow -M: `.te` -> checkmodule -> `.pp`
+    Pp,
+    /// Правила сразу в CIL, без audit2allow, checkmodule и semodule_package
+    Cil,
+}
+
+impl ModuleFormat {
+    /// CIL, если audit2allow не установлен (policycoreutils-devel часто нет на серверах)
+    pub fn default_for_system() -> Self {
+        if command_exists("audit2allow") {
+            ModuleFormat::Pp
+        } else {
+            ModuleFormat::Cil
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "pp" => Some(ModuleFormat::Pp),
+            "cil" => Some(ModuleFormat::Cil),
+            _ => None,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            ModuleFormat::Pp => "pp via audit2allow",
+            ModuleFormat::Cil => "native CIL",
+        }
+    }
 }
 
 #[derive(Clone)]
@@ -22,42 +74,77 @@ impl ModuleManager {
 
     pub fn load_modules(&mut self) -> Result<()> {
         let output = Command::new("semodule")
-        .arg("-l")
-        .output()?
-        .stdout;
-
-        let logs = String::from_utf8_lossy(&output);
-        let re = Regex::new(r"^(\S+)\s+(\d+)\s*")?;
-
-        self.modules.clear();
-        for line in logs.lines() {
-            if let Some(cap) = re.captures(line) {
-                let name = cap[1].to_string();
-                let priority: i32 = cap.get(2).map_or(400, |m| m.as_str().parse().unwrap_or(400));
-
-                self.modules.push(SELinuxModule {
-                    name,
-                    enabled: true,
-                    priority,
-                });
-            }
+        .arg("--list-modules=full")
+        .output()?;
+        if !output.status.success() {
+            return Err(anyhow!("semodule failed: {}", String::from_utf8_lossy(&output.stderr).trim()));
         }
+
+        self.modules = Self::parse_module_list(&String::from_utf8_lossy(&output.stdout));
         Ok(())
     }
 
+    /// Разбирает `semodule --list-modules=full`: приоритет, имя, язык и
+    /// отметка `disabled` (`100 zabbix pp disabled`). Записи одного модуля
+    /// идут подряд, от действующей к перекрытым.
+    pub fn parse_module_list(output: &str) -> Vec<SELinuxModule> {
+        lazy_static::lazy_static! {
+            static ref LINE_RE: Regex = Regex::new(r"^\s*(\d+)\s+(\S+)\s+(\S+)(\s+disabled)?\s*$").unwrap();
+        }
+        let mut modules: Vec<SELinuxModule> = output
+            .lines()
+            .filter_map(|line| {
+                let cap = LINE_RE.captures(line)?;
+                Some(SELinuxModule {
+                    name: cap[2].to_string(),
+                    enabled: cap.get(4).is_none(),
+                    priority: cap[1].parse().ok()?,
+                    language: cap[3].to_string(),
+                })
+            })
+            .collect();
+        modules.sort_by(|a, b| a.name.cmp(&b.name).then(b.priority.cmp(&a.priority)));
+        modules
+    }
+
+    /// Запись с более высоким приоритетом, которая перекрывает эту
+    pub fn overridden_by(&self, module: &SELinuxModule) -> Option<&SELinuxModule> {
+        self.modules
+            .iter()
+            .filter(|m| m.name == module.name && m.priority > module.priority)
+            .max_by_key(|m| m.priority)
+    }
+
+    /// Записи с более низким приоритетом, которые перекрывает эта
+    pub fn overrides(&self, module: &SELinuxModule) -> Vec<i32> {
+        self.modules
+            .iter()
+            .filter(|m| m.name == module.name && m.priority < module.priority)
+            .map(|m| m.priority)
+            .collect()
+    }
+
+    /// Модуль установлен локально (`semodule -i`, по умолчанию 400), а не пришел с пакетом
+    pub fn is_local(module: &SELinuxModule) -> bool {
+        module.priority > PACKAGE_PRIORITY
+    }
+
+    /// Действующие записи — по одной на модуль
+    pub fn active_modules(&self) -> impl Iterator<Item = &SELinuxModule> {
+        self.modules.iter().filter(|m| self.overridden_by(m).is_none())
+    }
+
     pub fn load_simulation_data(&mut self) {
-        self.modules = vec![
-            SELinuxModule {
-                name: "apache".to_string(),
-                enabled: true,
-                priority: 400,
-            },
-            SELinuxModule {
-                name: "mysql".to_string(),
-                enabled: true,
-                priority: 400,
-            },
-        ];
+        self.modules = Self::parse_module_list(
+            "100 apache          pp\n\
+             400 apache          cil\n\
+             100 base            pp\n\
+             200 container       cil\n\
+             100 mysql           pp disabled\n\
+             400 myapp           cil\n\
+             100 sandbox         pp disabled\n\
+             100 zabbix          pp\n",
+        );
     }
 
     pub fn enable_module(&mut self, name: &str, simulation: bool) -> Result<()> {
@@ -68,10 +155,7 @@ impl ModuleManager {
             return Ok(());
         }
 
-        Command::new("semodule")
-        .arg("-e")
-        .arg(name)
-        .output()?;
+        Self::run_semodule(&["-e", name])?;
 
         if let Some(module) = self.modules.iter_mut().find(|m| m.name == name) {
             module.enabled = true;
@@ -87,10 +171,7 @@ impl ModuleManager {
             return Ok(());
         }
 
-        Command::new("semodule")
-        .arg("-d")
-        .arg(name)
-        .output()?;
+        Self::run_semodule(&["-d", name])?;
 
         if let Some(module) = self.modules.iter_mut().find(|m| m.name == name) {
             module.enabled = false;
@@ -98,40 +179,86 @@ impl ModuleManager {
         Ok(())
     }
 
-    pub fn install_module(&mut self, path: &str, simulation: bool) -> Result<()> {
+    fn run_semodule(args: &[&str]) -> Result<()> {
+        let output = Command::new("semodule").args(args).output()?;
+        if !output.status.success() {
+            return Err(anyhow!("semodule failed: {}", String::from_utf8_lossy(&output.stderr).trim()));
+        }
+        Ok(())
+    }
+
+    /// Имя и язык модуля по имени файла: `/tmp/myapp.cil` -> ("myapp", "cil")
+    pub fn module_file_name(path: &str) -> Result<(String, String)> {
+        let path = Path::new(path);
+        let name = path.file_stem().and_then(|s| s.to_str()).ok_or_else(|| anyhow!("Invalid module file {}", path.display()))?;
+        let language = match path.extension().and_then(|s| s.to_str()) {
+            Some("pp") | None => "pp",
+            Some(ext) => ext,
+        };
+        Ok((name.to_string(), language.to_string()))
+    }
+
+    /// `semodule -X <priority> -i <path>`
+    pub fn install_module(&mut self, path: &str, priority: i32, simulation: bool) -> Result<()> {
+        if !(1..=999).contains(&priority) {
+            return Err(anyhow!("Priority must be between 1 and 999"));
+        }
+        let (name, language) = Self::module_file_name(path)?;
         if simulation {
-            self.modules.push(SELinuxModule {
-                name: path.to_string(),
-                              enabled: true,
-                              priority: 400,
-            });
+            self.modules.retain(|m| m.name != name || m.priority != priority);
+            self.modules.push(SELinuxModule { name, enabled: true, priority, language });
+            self.modules.sort_by(|a, b| a.name.cmp(&b.name).then(b.priority.cmp(&a.priority)));
             return Ok(());
         }
 
-        Command::new("semodule")
-        .arg("-i")
-        .arg(path)
-        .output()?;
+        Self::run_semodule(&["-X", &priority.to_string(), "-i", path])?;
 
         self.load_modules()?;
         Ok(())
     }
 
-    pub fn remove_module(&mut self, name: &str, simulation: bool) -> Result<()> {
+    /// `semodule -X <priority> -r <name>` — снимает только запись с этим приоритетом
+    pub fn remove_module(&mut self, name: &str, priority: i32, simulation: bool) -> Result<()> {
         if simulation {
-            self.modules.retain(|m| m.name != name);
+            self.modules.retain(|m| m.name != name || m.priority != priority);
             return Ok(());
         }
 
-        Command::new("semodule")
-        .arg("-r")
-        .arg(name)
-        .output()?;
+        Self::run_semodule(&["-X", &priority.to_string(), "-r", name])?;
 
         self.load_modules()?;
         Ok(())
     }
 
+    /// Каталог, куда извлекаются модули: `~/.cache/selab/modules/<priority>`
+    pub fn cache_dir(priority: i32) -> PathBuf {
+        let mut dir = dirs::cache_dir().unwrap_or_else(std::env::temp_dir);
+        dir.push("selab");
+        dir.push("modules");
+        dir.push(priority.to_string());
+        dir
+    }
+
+    /// Извлекает модуль из хранилища (`semodule -E`) в `dir` и возвращает путь к файлу.
+    /// `cil = true` — в виде CIL, иначе в исходном языке модуля.
+    pub fn extract_module(module: &SELinuxModule, cil: bool, dir: &Path) -> Result<PathBuf> {
+        fs::create_dir_all(dir)?;
+        let format = if cil { "--cil" } else { "--hll" };
+        let output = Command::new("semodule")
+            .current_dir(dir)
+            .args(["-X", &module.priority.to_string(), format, "-E", &module.name])
+            .output()?;
+        if !output.status.success() {
+            return Err(anyhow!("semodule -E {} failed: {}", module.name, String::from_utf8_lossy(&output.stderr).trim()));
+        }
+        let ext = if cil || module.language.is_empty() { "cil" } else { module.language.as_str() };
+        let path = dir.join(format!("{}.{}", module.name, ext));
+        if !path.exists() {
+            return Err(anyhow!("semodule did not write {}", path.display()));
+        }
+        Ok(path)
+    }
+
     /// Создает модуль из AVC алертов используя audit2allow
     pub fn create_module_from_avc(&mut self, module_name: &str, avc_logs: &str, simulation: bool) -> Result<String> {
         if simulation {
@@ -179,7 +306,17 @@ impl ModuleManager {
     }
 
     /// Создает модуль из выбранных AVC алертов
-    pub fn create_module_from_alerts(&mut self, module_name: &str, alerts: &[crate::avc::AVCAlert], simulation: bool) -> Result<String> {
+    pub fn create_module_from_alerts(
+        &mut self,
+        module_name: &str,
+        alerts: &[crate::avc::AVCAlert],
+        format: ModuleFormat,
+        simulation: bool,
+    ) -> Result<String> {
+        if format == ModuleFormat::Cil {
+            return self.create_cil_module(module_name, alerts, simulation);
+        }
+
         // Формируем лог в формате audit
         let mut log_content = String::new();
         for alert in alerts {
@@ -196,4 +333,28 @@ impl ModuleManager {
 
         self.create_module_from_avc(module_name, &log_content, simulation)
     }
+
+    /// Пишет минимальные allow-правила в `~/.config/selab/cil/<name>.cil` и ставит его `semodule -i`
+    fn create_cil_module(&mut self, module_name: &str, alerts: &[crate::avc::AVCAlert], simulation: bool) -> Result<String> {
+        if module_name.is_empty() || !module_name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+            return Err(anyhow!("Module name may contain only letters, digits and '_'"));
+        }
+        let cil = emit_cil(module_name, alerts)?;
+        let rules = cil.lines().filter(|l| l.starts_with("(allow")).count();
+        if simulation {
+            return Ok(format!("Would install CIL module {} with {} allow rule(s)", module_name, rules));
+        }
+
+        // Не в /tmp: файл с предсказуемым именем там может подменить другой пользователь
+        // до `semodule -i`. Каталог в конфигурации доступен только владельцу.
+        let dir = dirs::config_dir().unwrap_or_else(|| PathBuf::from(".")).join("selab").join("cil");
+        fs::DirBuilder::new().recursive(true).mode(0o700).create(&dir)?;
+        fs::set_permissions(&dir, fs::Permissions::from_mode(0o700))?;
+        let path = dir.join(format!("{}.cil", module_name));
+        fs::write(&path, cil)?;
+        Self::run_semodule(&["-i", &path.to_string_lossy()])?;
+
+        self.load_modules()?;
+        Ok(format!("CIL module {} with {} allow rule(s) installed", module_name, rules))
+    }
 }
@@ -0,0 +1,381 @@
+use serde::{Deserialize, Serialize};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::process::Command;
+use anyhow::{Result, anyhow};
+use crate::rollback::shell_quote;
+use crate::severity_rules::context_type;
+
+/// Контекст сетевого интерфейса (`semanage interface`).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InterfaceContext {
+    pub name: String,
+    /// Полный контекст `user:role:type[:range]`
+    pub context: String,
+    #[serde(default)]
+    pub local: bool,
+}
+
+/// Контекст узла — адреса или подсети (`semanage node`).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NodeContext {
+    pub address: String,
+    pub netmask: String,
+    /// `ipv4` или `ipv6`
+    pub protocol: String,
+    pub context: String,
+    #[serde(default)]
+    pub local: bool,
+}
+
+impl InterfaceContext {
+    pub fn context_type(&self) -> &str {
+        context_type(&self.context)
+    }
+
+    pub fn range(&self) -> &str {
+        context_range(&self.context)
+    }
+
+    /// Строка для снимка состояния: "name context"
+    pub fn state_line(&self) -> String {
+        format!("{} {}", self.name, self.context)
+    }
+
+    pub fn from_state_line(s: &str) -> Option<Self> {
+        let (name, context) = s.split_once(' ')?;
+        Some(Self {
+            name: name.to_string(),
+            context: context.to_string(),
+            local: true,
+        })
+    }
+
+    /// `semanage interface -a|-m` с типом и диапазоном этого контекста
+    pub fn semanage_command(&self, action: &str) -> String {
+        format!(
+            "semanage interface {} -t {}{} {}",
+            action,
+            shell_quote(self.context_type()),
+            range_arg(&self.context),
+            shell_quote(&self.name)
+        )
+    }
+
+    pub fn delete_command(&self) -> String {
+        format!("semanage interface -d {}", shell_quote(&self.name))
+    }
+}
+
+impl NodeContext {
+    pub fn context_type(&self) -> &str {
+        context_type(&self.context)
+    }
+
+    pub fn range(&self) -> &str {
+        context_range(&self.context)
+    }
+
+    /// Адрес в записи CIDR: `10.0.0.0/8`
+    pub fn cidr(&self) -> String {
+        match prefix_len(&self.netmask) {
+            Some(prefix) => format!("{}/{}", self.address, prefix),
+            None => format!("{} {}", self.address, self.netmask),
+        }
+    }
+
+    /// Строка для снимка состояния: "address netmask protocol context"
+    pub fn state_line(&self) -> String {
+        format!("{} {} {} {}", self.address, self.netmask, self.protocol, self.context)
+    }
+
+    pub fn from_state_line(s: &str) -> Option<Self> {
+        let mut it = s.split_whitespace();
+        Some(Self {
+            address: it.next()?.to_string(),
+            netmask: it.next()?.to_string(),
+            protocol: it.next()?.to_string(),
+            context: it.next()?.to_string(),
+            local: true,
+        })
+    }
+
+    /// Ключ записи без контекста: адрес, маска и протокол
+    fn key(&self) -> (&str, &str, &str) {
+        (&self.address, &self.netmask, &self.protocol)
+    }
+
+    pub fn same_node(&self, other: &NodeContext) -> bool {
+        self.key() == other.key()
+    }
+
+    pub fn semanage_command(&self, action: &str) -> String {
+        format!(
+            "semanage node {} -M {} -p {} -t {}{} {}",
+            action,
+            shell_quote(&self.netmask),
+            shell_quote(&self.protocol),
+            shell_quote(self.context_type()),
+            range_arg(&self.context),
+            shell_quote(&self.address)
+        )
+    }
+
+    pub fn delete_command(&self) -> String {
+        format!(
+            "semanage node -d -M {} -p {} {}",
+            shell_quote(&self.netmask),
+            shell_quote(&self.protocol),
+            shell_quote(&self.address)
+        )
+    }
+}
+
+/// MLS-часть полного контекста, пустая — политика без MLS
+fn context_range(context: &str) -> &str {
+    context.splitn(4, ':').nth(3).unwrap_or("")
+}
+
+/// ` -r <range>` для команд semanage, если у контекста есть MLS-часть
+fn range_arg(context: &str) -> String {
+    match context_range(context) {
+        "" => String::new(),
+        range => format!(" -r {}", shell_quote(range)),
+    }
+}
+
+/// Контекст, который получит запись после `semanage ... -t <type>`
+fn full_context(context_type: &str, range: &str) -> String {
+    let range = if range.is_empty() { "s0" } else { range };
+    format!("system_u:object_r:{}:{}", context_type, range)
+}
+
+/// `10.0.0.0/8` или одиночный адрес -> (адрес, маска, протокол)
+pub fn parse_cidr(s: &str) -> Option<(String, String, String)> {
+    let (addr, prefix) = match s.split_once('/') {
+        Some((addr, prefix)) => (addr, Some(prefix.parse::<u32>().ok()?)),
+        None => (s, None),
+    };
+    match addr.parse::<IpAddr>().ok()? {
+        IpAddr::V4(a) => {
+            let prefix = prefix.unwrap_or(32);
+            if prefix > 32 {
+                return None;
+            }
+            let mask = u32::MAX.checked_shl(32 - prefix).unwrap_or(0);
+            Some((a.to_string(), Ipv4Addr::from(mask).to_string(), "ipv4".to_string()))
+        }
+        IpAddr::V6(a) => {
+            let prefix = prefix.unwrap_or(128);
+            if prefix > 128 {
+                return None;
+            }
+            let mask = u128::MAX.checked_shl(128 - prefix).unwrap_or(0);
+            Some((a.to_string(), Ipv6Addr::from(mask).to_string(), "ipv6".to_string()))
+        }
+    }
+}
+
+/// Длина префикса по маске; None — маска несмежная
+fn prefix_len(netmask: &str) -> Option<u32> {
+    let bits = match netmask.parse::<IpAddr>().ok()? {
+        IpAddr::V4(m) => u128::from(u32::from(m)) << 96,
+        IpAddr::V6(m) => u128::from(m),
+    };
+    let prefix = bits.leading_ones();
+    (bits.checked_shl(prefix).unwrap_or(0) == 0).then_some(prefix)
+}
+
+fn run_semanage(args: &[&str]) -> Result<()> {
+    let output = Command::new("semanage").args(args).output()?;
+    if !output.status.success() {
+        return Err(anyhow!("semanage {} failed: {}", args[0], String::from_utf8_lossy(&output.stderr).trim()));
+    }
+    Ok(())
+}
+
+fn semanage_list(object: &str, local_only: bool) -> Result<String> {
+    let mut cmd = Command::new("semanage");
+    cmd.args([object, "-l", "-n"]);
+    if local_only {
+        cmd.arg("-C");
+    }
+    Ok(String::from_utf8_lossy(&cmd.output()?.stdout).to_string())
+}
+
+#[derive(Clone)]
+pub struct InterfaceManager {
+    pub interfaces: Vec<InterfaceContext>,
+}
+
+impl InterfaceManager {
+    pub fn new() -> Self {
+        Self { interfaces: Vec::new() }
+    }
+
+    pub fn load_interfaces(&mut self) -> Result<()> {
+        self.interfaces = Self::parse_interface_list(&semanage_list("interface", false)?);
+        for local in Self::parse_interface_list(&semanage_list("interface", true)?) {
+            if let Some(i) = self.interfaces.iter_mut().find(|i| i.name == local.name) {
+                i.local = true;
+            }
+        }
+        Ok(())
+    }
+
+    /// Разбирает `semanage interface -l -n`: `eth1  system_u:object_r:netif_t:s0`
+    pub fn parse_interface_list(output: &str) -> Vec<InterfaceContext> {
+        output
+            .lines()
+            .filter_map(|line| {
+                let mut fields = line.split_whitespace();
+                let (name, context) = (fields.next()?, fields.next()?);
+                context.contains(':').then(|| InterfaceContext {
+                    name: name.to_string(),
+                    context: context.to_string(),
+                    local: false,
+                })
+            })
+            .collect()
+    }
+
+    pub fn find(&self, name: &str) -> Option<&InterfaceContext> {
+        self.interfaces.iter().find(|i| i.name == name)
+    }
+
+    /// `semanage interface -a` для нового интерфейса, `-m` для описанного
+    pub fn set_interface(&mut self, name: &str, context_type: &str, range: &str, simulation: bool) -> Result<()> {
+        if simulation {
+            let entry = InterfaceContext {
+                name: name.to_string(),
+                context: full_context(context_type, range),
+                local: true,
+            };
+            match self.interfaces.iter_mut().find(|i| i.name == name) {
+                Some(existing) => *existing = entry,
+                None => self.interfaces.push(entry),
+            }
+            return Ok(());
+        }
+
+        let action = if self.find(name).is_some() { "-m" } else { "-a" };
+        let mut args = vec!["interface", action, "-t", context_type];
+        if !range.is_empty() {
+            args.extend(["-r", range]);
+        }
+        args.push(name);
+        run_semanage(&args)?;
+
+        self.load_interfaces()?;
+        Ok(())
+    }
+
+    pub fn remove_interface(&mut self, name: &str, simulation: bool) -> Result<()> {
+        if simulation {
+            self.interfaces.retain(|i| i.name != name);
+            return Ok(());
+        }
+
+        run_semanage(&["interface", "-d", name])?;
+
+        self.load_interfaces()?;
+        Ok(())
+    }
+}
+
+#[derive(Clone)]
+pub struct NodeManager {
+    pub nodes: Vec<NodeContext>,
+}
+
+impl NodeManager {
+    pub fn new() -> Self {
+        Self { nodes: Vec::new() }
+    }
+
+    pub fn load_nodes(&mut self) -> Result<()> {
+        self.nodes = Self::parse_node_list(&semanage_list("node", false)?);
+        for local in Self::parse_node_list(&semanage_list("node", true)?) {
+            if let Some(n) = self
+                .nodes
+                .iter_mut()
+                .find(|n| n.address == local.address && n.netmask == local.netmask && n.protocol == local.protocol)
+            {
+                n.local = true;
+            }
+        }
+        Ok(())
+    }
+
+    /// Разбирает `semanage node -l -n`: адрес, маска, протокол и контекст
+    /// (`127.0.0.1  255.255.255.255  ipv4  system_u:object_r:lo_node_t:s0`)
+    pub fn parse_node_list(output: &str) -> Vec<NodeContext> {
+        output
+            .lines()
+            .filter_map(|line| {
+                let fields: Vec<&str> = line.split_whitespace().collect();
+                let [address, netmask, protocol, context] = fields.as_slice() else {
+                    return None;
+                };
+                (*protocol == "ipv4" || *protocol == "ipv6").then(|| NodeContext {
+                    address: address.to_string(),
+                    netmask: netmask.to_string(),
+                    protocol: protocol.to_string(),
+                    context: context.to_string(),
+                    local: false,
+                })
+            })
+            .collect()
+    }
+
+    pub fn find(&self, address: &str, netmask: &str, protocol: &str) -> Option<&NodeContext> {
+        self.nodes
+            .iter()
+            .find(|n| n.address == address && n.netmask == netmask && n.protocol == protocol)
+    }
+
+    /// `semanage node -a` для новой подсети, `-m` для описанной
+    pub fn set_node(&mut self, address: &str, netmask: &str, protocol: &str, context_type: &str, range: &str, simulation: bool) -> Result<()> {
+        if simulation {
+            let entry = NodeContext {
+                address: address.to_string(),
+                netmask: netmask.to_string(),
+                protocol: protocol.to_string(),
+                context: full_context(context_type, range),
+                local: true,
+            };
+            match self
+                .nodes
+                .iter_mut()
+                .find(|n| n.address == address && n.netmask == netmask && n.protocol == protocol)
+            {
+                Some(existing) => *existing = entry,
+                None => self.nodes.push(entry),
+            }
+            return Ok(());
+        }
+
+        let action = if self.find(address, netmask, protocol).is_some() { "-m" } else { "-a" };
+        let mut args = vec!["node", action, "-M", netmask, "-p", protocol, "-t", context_type];
+        if !range.is_empty() {
+            args.extend(["-r", range]);
+        }
+        args.push(address);
+        run_semanage(&args)?;
+
+        self.load_nodes()?;
+        Ok(())
+    }
+
+    pub fn remove_node(&mut self, address: &str, netmask: &str, protocol: &str, simulation: bool) -> Result<()> {
+        if simulation {
+            self.nodes
+                .retain(|n| n.address != address || n.netmask != netmask || n.protocol != protocol);
+            return Ok(());
+        }
+
+        run_semanage(&["node", "-d", "-M", netmask, "-p", protocol, address])?;
+
+        self.load_nodes()?;
+        Ok(())
+    }
+}
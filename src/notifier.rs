@@ -0,0 +1,452 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::ffi::CString;
+use std::fs;
+use std::io::{Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+use std::time::{Duration, Instant};
+use anyhow::{Result, anyhow, Context};
+use crate::avc::{AVCAlert, AVCSeverity};
+use crate::severity_rules::{context_type, glob_match};
+
+const NETWORK_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Куда отправлять уведомления.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum SinkKind {
+    /// POST JSON на `http://host[:port]/path`
+    Webhook { url: String },
+    /// Письмо через локальный `sendmail -t`
+    Sendmail {
+        to: String,
+        #[serde(default)]
+        from: Option<String>,
+    },
+    /// Локальный syslog (facility authpriv)
+    Syslog,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SinkConfig {
+    #[serde(flatten)]
+    pub kind: SinkKind,
+    /// Не больше N уведомлений в час (0 — без ограничения)
+    #[serde(default)]
+    pub max_per_hour: u32,
+    /// Домены (шаблоны), отказы которых в этот канал не отправляются
+    #[serde(default)]
+    pub suppress_domains: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct NotifierConfig {
+    /// Уведомлять о High-отказах
+    #[serde(default = "default_true")]
+    pub notify_high: bool,
+    /// Уведомлять о сигнатурах, которых раньше не было в истории
+    #[serde(default = "default_true")]
+    pub notify_new: bool,
+    /// Домены (шаблоны), которые не уведомляются ни в один канал
+    #[serde(default)]
+    pub suppress_domains: Vec<String>,
+    #[serde(default)]
+    pub sinks: Vec<SinkConfig>,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+/// Причина уведомления
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NotifyReason {
+    HighSeverity,
+    NewSignature,
+}
+
+#[derive(Serialize)]
+struct NotificationPayload<'a> {
+    source: &'static str,
+    host: String,
+    timestamp: String,
+    denials: Vec<NotificationItem<'a>>,
+}
+
+#[derive(Serialize)]
+struct NotificationItem<'a> {
+    reason: NotifyReason,
+    #[serde(flatten)]
+    alert: &'a AVCAlert,
+}
+
+/// Подсистема уведомлений о новых отказах. Конфигурация —
+/// `~/.config/selab/notifications.json`.
+pub struct Notifier {
+    pub config: NotifierConfig,
+    /// Моменты отправки по каждому каналу (для ограничения частоты)
+    sent: HashMap<usize, VecDeque<Instant>>,
+    /// Ошибки фоновой отправки
+    errors_tx: Sender<String>,
+    errors_rx: Receiver<String>,
+}
+
+impl Notifier {
+    pub fn new() -> Self {
+        let config_path = Self::default_config_path();
+        let mut parse_error = None;
+        let config = match fs::read_to_string(&config_path) {
+            // Битый файл не перезаписываем — сообщаем об ошибке, каналы выключены
+            Ok(data) => serde_json::from_str(&data).unwrap_or_else(|e| {
+                parse_error = Some(format!("Invalid {}: {}", config_path.display(), e));
+                NotifierConfig::default()
+            }),
+            Err(_) => {
+                // Шаблон без каналов — уведомления выключены, пока их не настроят
+                let config = NotifierConfig {
+                    notify_high: true,
+                    notify_new: true,
+                    ..Default::default()
+                };
+                if let Ok(data) = serde_json::to_string_pretty(&config) {
+                    let _ = fs::write(&config_path, data);
+                }
+                config
+            }
+        };
+        let notifier = Self::with_config(config);
+        if let Some(err) = parse_error {
+            let _ = notifier.errors_tx.send(err);
+        }
+        notifier
+    }
+
+    pub fn with_config(config: NotifierConfig) -> Self {
+        let (errors_tx, errors_rx) = mpsc::channel();
+        Self {
+            config,
+            sent: HashMap::new(),
+            errors_tx,
+            errors_rx,
+        }
+    }
+
+    fn default_config_path() -> PathBuf {
+        if let Some(mut dir) = dirs::config_dir() {
+            dir.push("selab");
+            let _ = fs::create_dir_all(&dir);
+            dir.push("notifications.json");
+            return dir;
+        }
+        let mut home = std::env::var_os("HOME").map(PathBuf::from).unwrap_or_else(|| PathBuf::from("."));
+        home.push(".selab_notifications.json");
+        home
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        !self.config.sinks.is_empty()
+    }
+
+    /// Решает, о каких отказах нужно уведомить. `new_signature` — отказ с такой
+    /// сигнатурой ранее не встречался в истории.
+    pub fn classify(&self, alert: &AVCAlert, new_signature: bool) -> Option<NotifyReason> {
+        let domain = context_type(&alert.source_context);
+        if self.config.suppress_domains.iter().any(|p| glob_match(p, domain)) {
+            return None;
+        }
+        if self.config.notify_high && matches!(alert.severity, AVCSeverity::High) {
+            Some(NotifyReason::HighSeverity)
+        } else if self.config.notify_new && new_signature {
+            Some(NotifyReason::NewSignature)
+        } else {
+            None
+        }
+    }
+
+    /// Отправляет уведомления во все каналы в фоновом потоке, чтобы медленный
+    /// webhook не блокировал UI. Сбой одного канала не мешает остальным.
+    pub fn dispatch(&mut self, denials: &[(AVCAlert, NotifyReason)]) {
+        if denials.is_empty() {
+            return;
+        }
+        let mut batches: Vec<(SinkKind, Vec<(AVCAlert, NotifyReason)>)> = Vec::new();
+        let sinks = self.config.sinks.clone();
+        for (idx, sink) in sinks.iter().enumerate() {
+            let selected: Vec<(AVCAlert, NotifyReason)> = denials
+                .iter()
+                .filter(|(a, _)| {
+                    let domain = context_type(&a.source_context);
+                    !sink.suppress_domains.iter().any(|p| glob_match(p, domain))
+                })
+                .cloned()
+                .collect();
+            if selected.is_empty() || !self.allow_send(idx, sink.max_per_hour) {
+                continue;
+            }
+            batches.push((sink.kind.clone(), selected));
+        }
+        if batches.is_empty() {
+            return;
+        }
+
+        let errors_tx = self.errors_tx.clone();
+        thread::spawn(move || {
+            let host = hostname();
+            for (kind, selected) in &batches {
+                let payload = NotificationPayload {
+                    source: "selab",
+                    host: host.clone(),
+                    timestamp: chrono::Utc::now().to_rfc3339(),
+                    denials: selected
+                        .iter()
+                        .map(|(alert, reason)| NotificationItem { reason: *reason, alert })
+                        .collect(),
+                };
+                if let Err(e) = Self::send(kind, &payload) {
+                    let _ = errors_tx.send(format!("{:?}: {}", kind, e));
+                }
+            }
+        });
+    }
+
+    /// Ошибки отправки, накопившиеся с прошлого вызова
+    pub fn take_errors(&self) -> Vec<String> {
+        self.errors_rx.try_iter().collect()
+    }
+
+    fn allow_send(&mut self, sink_idx: usize, max_per_hour: u32) -> bool {
+        let window = self.sent.entry(sink_idx).or_default();
+        let hour = Duration::from_secs(3600);
+        while window.front().is_some_and(|t| t.elapsed() > hour) {
+            window.pop_front();
+        }
+        if max_per_hour > 0 && window.len() >= max_per_hour as usize {
+            return false;
+        }
+        window.push_back(Instant::now());
+        true
+    }
+
+    fn send(kind: &SinkKind, payload: &NotificationPayload) -> Result<()> {
+        match kind {
+            SinkKind::Webhook { url } => {
+                let body = serde_json::to_string(payload)?;
+                post_json(url, &body)
+            }
+            SinkKind::Sendmail { to, from } => {
+                let mut message = String::new();
+                if let Some(from) = from {
+                    message.push_str(&format!("From: {}\n", from));
+                }
+                message.push_str(&format!("To: {}\n", to));
+                message.push_str(&format!(
+                    "Subject: [selab] {} SELinux denial(s) on {}\n\n",
+                    payload.denials.len(),
+                    payload.host
+                ));
+                for item in &payload.denials {
+                    message.push_str(&format!("{}\n", summary_line(item.alert, item.reason)));
+                }
+                let mut child = Command::new("sendmail")
+                    .arg("-t")
+                    .stdin(Stdio::piped())
+                    .spawn()
+                    .with_context(|| "Failed to run sendmail")?;
+                if let Some(stdin) = child.stdin.as_mut() {
+                    stdin.write_all(message.as_bytes())?;
+                }
+                let status = child.wait()?;
+                if !status.success() {
+                    return Err(anyhow!("sendmail exited with {}", status));
+                }
+                Ok(())
+            }
+            SinkKind::Syslog => {
+                for item in &payload.denials {
+                    write_syslog(&summary_line(item.alert, item.reason));
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+fn summary_line(alert: &AVCAlert, reason: NotifyReason) -> String {
+    let reason = match reason {
+        NotifyReason::HighSeverity => "high severity",
+        NotifyReason::NewSignature => "new signature",
+    };
+    format!(
+        "{}: {} denied {{ {} }} {} -> {} ({}) path={}",
+        reason,
+        alert.comm,
+        alert.permission,
+        context_type(&alert.source_context),
+        context_type(&alert.target_context),
+        alert.target_class,
+        alert.path
+    )
+}
+
+fn hostname() -> String {
+    fs::read_to_string("/proc/sys/kernel/hostname")
+        .map(|h| h.trim().to_string())
+        .unwrap_or_else(|_| "unknown".to_string())
+}
+
+fn write_syslog(message: &str) {
+    let Ok(msg) = CString::new(message) else {
+        return;
+    };
+    // openlog сохраняет указатель на ident, поэтому он должен быть 'static
+    static IDENT: &std::ffi::CStr = c"selab";
+    unsafe {
+        libc::openlog(IDENT.as_ptr(), libc::LOG_PID, libc::LOG_AUTHPRIV);
+        libc::syslog(libc::LOG_WARNING, c"%s".as_ptr(), msg.as_ptr());
+    }
+}
+
+/// Минимальный HTTP/1.1 POST без внешних зависимостей (только http://).
+fn post_json(url: &str, body: &str) -> Result<()> {
+    let rest = url
+        .strip_prefix("http://")
+        .ok_or_else(|| anyhow!("Only http:// webhooks are supported: {}", url))?;
+    let (authority, path) = match rest.find('/') {
+        Some(i) => (&rest[..i], &rest[i..]),
+        None => (rest, "/"),
+    };
+    let addr_str = if authority.contains(':') {
+        authority.to_string()
+    } else {
+        format!("{}:80", authority)
+    };
+    let addr = addr_str
+        .to_socket_addrs()?
+        .next()
+        .ok_or_else(|| anyhow!("Cannot resolve {}", authority))?;
+
+    let mut stream = TcpStream::connect_timeout(&addr, NETWORK_TIMEOUT)?;
+    stream.set_read_timeout(Some(NETWORK_TIMEOUT))?;
+    stream.set_write_timeout(Some(NETWORK_TIMEOUT))?;
+    let request = format!(
+        "POST {} HTTP/1.1\r\nHost: {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\nUser-Agent: selab\r\n\r\n{}",
+        path,
+        authority,
+        body.len(),
+        body
+    );
+    stream.write_all(request.as_bytes())?;
+
+    let mut response = String::new();
+    let _ = stream.read_to_string(&mut response);
+    let status: u16 = response
+        .split_whitespace()
+        .nth(1)
+        .and_then(|s| s.parse().ok())
+        .ok_or_else(|| anyhow!("Invalid HTTP response from {}", url))?;
+    if !(200..300).contains(&status) {
+        return Err(anyhow!("Webhook returned HTTP {}", status));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+
+    fn alert() -> AVCAlert {
+        let line = r#"type=AVC msg=audit(1700000000.123:42): avc:  denied  { read } for  pid=1234 comm="httpd" name="index.html" dev="dm-0" ino=1 scontext=system_u:system_r:httpd_t:s0 tcontext=unconfined_u:object_r:user_home_t:s0 tclass=file permissive=0"#;
+        crate::avc::AVCManager::new().parse_avc_records(line).into_iter().next().expect("fixture AVC parses")
+    }
+
+    /// Принимает одно соединение, отвечает `status` и возвращает запрос целиком
+    fn serve_once(listener: TcpListener, status: &'static str) -> thread::JoinHandle<String> {
+        thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            stream.set_read_timeout(Some(NETWORK_TIMEOUT)).unwrap();
+            let mut request = Vec::new();
+            let mut buf = [0u8; 4096];
+            loop {
+                let n = stream.read(&mut buf).unwrap();
+                request.extend_from_slice(&buf[..n]);
+                let text = String::from_utf8_lossy(&request);
+                if let Some(end) = text.find("\r\n\r\n") {
+                    let length: usize = text
+                        .lines()
+                        .find_map(|l| l.strip_prefix("Content-Length: "))
+                        .and_then(|v| v.trim().parse().ok())
+                        .unwrap_or(0);
+                    if request.len() >= end + 4 + length || n == 0 {
+                        break;
+                    }
+                }
+            }
+            let _ = stream.write_all(format!("HTTP/1.1 {}\r\nContent-Length: 0\r\n\r\n", status).as_bytes());
+            String::from_utf8(request).unwrap()
+        })
+    }
+
+    fn webhook_notifier(listener: &TcpListener, max_per_hour: u32) -> Notifier {
+        Notifier::with_config(NotifierConfig {
+            notify_high: true,
+            notify_new: true,
+            suppress_domains: Vec::new(),
+            sinks: vec![SinkConfig {
+                kind: SinkKind::Webhook {
+                    url: format!("http://{}/hook", listener.local_addr().unwrap()),
+                },
+                max_per_hour,
+                suppress_domains: Vec::new(),
+            }],
+        })
+    }
+
+    #[test]
+    fn webhook_posts_json_body() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut notifier = webhook_notifier(&listener, 0);
+        let server = serve_once(listener, "204 No Content");
+        notifier.dispatch(&[(alert(), NotifyReason::NewSignature)]);
+
+        let request = server.join().unwrap();
+        assert!(request.starts_with("POST /hook HTTP/1.1\r\n"));
+        assert!(request.contains("Content-Type: application/json\r\n"));
+        let body = &request[request.find("\r\n\r\n").unwrap() + 4..];
+        let json: serde_json::Value = serde_json::from_str(body).unwrap();
+        assert_eq!(json["source"], "selab");
+        assert_eq!(json["denials"][0]["reason"], "new_signature");
+        assert_eq!(json["denials"][0]["comm"], "httpd");
+        assert_eq!(json["denials"][0]["permission"], "read");
+    }
+
+    #[test]
+    fn rate_limit_skips_sends_over_hourly_budget() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut notifier = webhook_notifier(&listener, 1);
+        let server = serve_once(listener, "200 OK");
+        notifier.dispatch(&[(alert(), NotifyReason::HighSeverity)]);
+        server.join().unwrap();
+
+        // Второй батч за тот же час в канал не уходит
+        notifier.dispatch(&[(alert(), NotifyReason::HighSeverity)]);
+        assert_eq!(notifier.sent[&0].len(), 1);
+        assert!(!notifier.allow_send(0, 1));
+        assert!(notifier.allow_send(0, 2));
+    }
+
+    #[test]
+    fn non_2xx_response_is_reported() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        let server = serve_once(listener, "500 Internal Server Error");
+        let err = post_json(&url, "{}").unwrap_err();
+        server.join().unwrap();
+        assert_eq!(err.to_string(), "Webhook returned HTTP 500");
+    }
+}
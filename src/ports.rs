@@ -1,13 +1,115 @@
 use serde::{Deserialize, Serialize};
+use std::fmt;
 use std::process::Command;
-use anyhow::Result;
+use std::str::FromStr;
+use anyhow::{Result, anyhow};
 use regex::Regex;
+use crate::advisor::well_known_reassignments;
+use crate::rollback::shell_quote;
+
+/// Протоколы, для которых `semanage port` хранит типы
+pub const PROTOCOLS: [&str; 4] = ["tcp", "udp", "sctp", "dccp"];
+
+/// Порт или диапазон портов (`8080`, `8008-8009`). В JSON хранится строкой,
+/// как и до появления диапазонов.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct PortRange {
+    pub low: u16,
+    pub high: u16,
+}
+
+impl PortRange {
+    pub fn single(port: u16) -> Self {
+        Self { low: port, high: port }
+    }
+
+    pub fn contains(&self, port: u16) -> bool {
+        self.low <= port && port <= self.high
+    }
+
+    pub fn overlaps(&self, other: &PortRange) -> bool {
+        self.low <= other.high && other.low <= self.high
+    }
+
+    pub fn count(&self) -> u32 {
+        u32::from(self.high - self.low) + 1
+    }
+}
+
+impl fmt::Display for PortRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.low == self.high {
+            write!(f, "{}", self.low)
+        } else {
+            write!(f, "{}-{}", self.low, self.high)
+        }
+    }
+}
+
+impl FromStr for PortRange {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.trim();
+        let (low, high) = match s.split_once('-') {
+            Some((low, high)) => (low.trim().parse::<u16>(), high.trim().parse::<u16>()),
+            None => (s.parse::<u16>(), s.parse::<u16>()),
+        };
+        match (low, high) {
+            (Ok(low), Ok(high)) if low <= high => Ok(Self { low, high }),
+            _ => Err(anyhow!("Invalid port or range: {}", s)),
+        }
+    }
+}
+
+impl TryFrom<String> for PortRange {
+    type Error = anyhow::Error;
+
+    fn try_from(s: String) -> Result<Self> {
+        s.parse()
+    }
+}
+
+impl From<PortRange> for String {
+    fn from(range: PortRange) -> Self {
+        range.to_string()
+    }
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PortContext {
-    pub port: String,
+    pub port: PortRange,
+    pub protocol: String,
+    pub context: String,
+    /// Локальная настройка (`semanage port -l -C`), а не запись политики
+    #[serde(default)]
+    pub local: bool,
+}
+
+/// Результат проверки перед назначением типа порту.
+#[derive(Debug, Clone)]
+pub struct PortPreflight {
+    pub port: PortRange,
     pub protocol: String,
     pub context: String,
+    /// Запись ровно для этого порта или диапазона — `-a` не пройдет, нужен `-m`
+    pub existing: Option<PortContext>,
+    /// Прочие записи, покрывающие порт или пересекающиеся с диапазоном
+    pub overlapping: Vec<PortContext>,
+    /// Стандартные порты служб, которые получат чужой тип
+    pub warnings: Vec<String>,
+}
+
+impl PortPreflight {
+    pub fn needs_confirmation(&self) -> bool {
+        self.existing.is_some() || !self.overlapping.is_empty() || !self.warnings.is_empty()
+    }
+
+    /// Тип уже назначен — менять нечего
+    pub fn is_noop(&self) -> bool {
+        self.existing.as_ref().is_some_and(|p| p.context == self.context)
+    }
 }
 
 #[derive(Clone)]
@@ -22,56 +124,308 @@ impl PortManager {
 
     pub fn load_ports(&mut self) -> Result<()> {
         let output = Command::new("semanage")
-        .args(&["port", "-l"])
+        .args(["port", "-l"])
+        .output()?
+        .stdout;
+        let local_output = Command::new("semanage")
+        .args(["port", "-l", "-C"])
         .output()?
         .stdout;
 
-        let logs = String::from_utf8_lossy(&output);
-        let re = Regex::new(r"^(\S+)\s+(\S+)\s+(\S+)\s+(\d+(?:-\d+)?)$")?;
-
-        self.ports.clear();
-        for line in logs.lines() {
-            if let Some(cap) = re.captures(line) {
-                let context = cap[1].to_string();
-                let protocol = cap[2].to_string();
-                let _mls = cap[3].to_string();
-                let port = cap[4].to_string();
+        let local = Self::parse_port_list(&String::from_utf8_lossy(&local_output));
+        let mut ports = Self::parse_port_list(&String::from_utf8_lossy(&output));
+        for p in &mut ports {
+            p.local = local.iter().any(|l| l.port == p.port && l.protocol == p.protocol && l.context == p.context);
+        }
+        self.ports = ports;
+        Ok(())
+    }
 
-                self.ports.push(PortContext { port, protocol, context });
+    /// Разбирает вывод `semanage port -l`: в строке тип, протокол и список
+    /// портов и диапазонов через запятую (`http_port_t tcp 80, 81, 8008-8009`).
+    pub fn parse_port_list(output: &str) -> Vec<PortContext> {
+        lazy_static::lazy_static! {
+            static ref LINE_RE: Regex = Regex::new(r"^(\S+)\s+(tcp|udp|sctp|dccp)\s+(\d.*)$").unwrap();
+        }
+        let mut ports = Vec::new();
+        for line in output.lines() {
+            let Some(cap) = LINE_RE.captures(line.trim_end()) else {
+                continue;
+            };
+            for item in cap[3].split(',') {
+                if let Ok(port) = item.parse::<PortRange>() {
+                    ports.push(PortContext {
+                        port,
+                        protocol: cap[2].to_string(),
+                        context: cap[1].to_string(),
+                        local: false,
+                    });
+                }
             }
         }
+        ports
+    }
+
+    /// Тип порта по политике: из диапазонов, содержащих порт, побеждает самый узкий
+    pub fn type_of(&self, port: u16, protocol: &str) -> Option<&PortContext> {
+        self.ports
+            .iter()
+            .filter(|p| p.protocol == protocol && p.port.contains(port))
+            .min_by_key(|p| p.port.count())
+    }
+
+    /// Запись ровно для этого порта или диапазона
+    pub fn find(&self, port: &PortRange, protocol: &str) -> Option<&PortContext> {
+        self.ports.iter().find(|p| p.port == *port && p.protocol == protocol)
+    }
+
+    /// Записи, которые уже покрывают порт или пересекаются с диапазоном
+    pub fn overlapping(&self, port: &PortRange, protocol: &str) -> Vec<&PortContext> {
+        let mut found: Vec<&PortContext> = self
+            .ports
+            .iter()
+            .filter(|p| p.protocol == protocol && p.port.overlaps(port))
+            .collect();
+        found.sort_by_key(|p| p.port.count());
+        found
+    }
+
+    /// Проверка перед `semanage port -a`: существующая запись, пересечения и
+    /// стандартные порты, которые уйдут от своего типа
+    pub fn preflight(&self, port: &PortRange, protocol: &str, context: &str) -> PortPreflight {
+        let existing = self.find(port, protocol).cloned();
+        let overlapping = self
+            .overlapping(port, protocol)
+            .into_iter()
+            .filter(|p| p.port != *port)
+            .cloned()
+            .collect();
+        let warnings = well_known_reassignments(port, protocol, context)
+            .into_iter()
+            .map(|e| {
+                format!(
+                    "{}/{} is the standard {} port ({}); the service will lose access to it",
+                    e.port, protocol, e.service, e.context
+                )
+            })
+            .collect();
+        PortPreflight {
+            port: *port,
+            protocol: protocol.to_string(),
+            context: context.to_string(),
+            existing,
+            overlapping,
+            warnings,
+        }
+    }
+
+    fn check_protocol(protocol: &str) -> Result<()> {
+        if PROTOCOLS.contains(&protocol) {
+            Ok(())
+        } else {
+            Err(anyhow!("Unknown protocol {} (expected one of {})", protocol, PROTOCOLS.join(", ")))
+        }
+    }
+
+    fn run_semanage(args: &[&str]) -> Result<()> {
+        let output = Command::new("semanage").args(args).output()?;
+        if !output.status.success() {
+            return Err(anyhow!("semanage port failed: {}", String::from_utf8_lossy(&output.stderr).trim()));
+        }
         Ok(())
     }
 
-    pub fn add_port(&mut self, port: &str, protocol: &str, context: &str, simulation: bool) -> Result<()> {
+    pub fn add_port(&mut self, port: &PortRange, protocol: &str, context: &str, simulation: bool) -> Result<()> {
+        Self::check_protocol(protocol)?;
         if simulation {
             self.ports.push(PortContext {
-                port: port.to_string(),
+                port: *port,
                             protocol: protocol.to_string(),
                             context: context.to_string(),
+                            local: true,
             });
             return Ok(());
         }
 
-        Command::new("semanage")
-        .args(&["port", "-a", "-t", context, "-p", protocol, port])
-        .output()?;
+        Self::run_semanage(&["port", "-a", "-t", context, "-p", protocol, &port.to_string()])?;
 
         self.load_ports()?;
         Ok(())
     }
 
-    pub fn remove_port(&mut self, port: &str, protocol: &str, simulation: bool) -> Result<()> {
+    /// Меняет тип существующей записи (`semanage port -m`)
+    pub fn modify_port(&mut self, port: &PortRange, protocol: &str, context: &str, simulation: bool) -> Result<()> {
+        Self::check_protocol(protocol)?;
+        if simulation {
+            match self.ports.iter_mut().find(|p| p.port == *port && p.protocol == protocol) {
+                Some(p) => {
+                    p.context = context.to_string();
+                    p.local = true;
+                }
+                None => return Err(anyhow!("Port {}/{} is not defined", port, protocol)),
+            }
+            return Ok(());
+        }
+
+        Self::run_semanage(&["port", "-m", "-t", context, "-p", protocol, &port.to_string()])?;
+
+        self.load_ports()?;
+        Ok(())
+    }
+
+    /// Назначает тип по результатам проверки: `semanage port -m` для существующей
+    /// записи, иначе `-a`. Возвращает описание изменения и команды отката.
+    /// `-m` поверх записи политики создает локальное переопределение — откат
+    /// удаляет его (`-d`), и порт возвращается к типу из политики.
+    pub fn assign(&mut self, preflight: &PortPreflight, simulation: bool) -> Result<(String, Vec<String>)> {
+        let PortPreflight { port, protocol, context, existing, .. } = preflight;
+        let delete = format!("semanage port -d -p {} {}", shell_quote(protocol), shell_quote(&port.to_string()));
+        match existing {
+            Some(old) => {
+                self.modify_port(port, protocol, context, simulation)?;
+                let rollback = if old.local {
+                    format!(
+                        "semanage port -m -t {} -p {} {}",
+                        shell_quote(&old.context),
+                        shell_quote(protocol),
+                        shell_quote(&port.to_string())
+                    )
+                } else {
+                    delete
+                };
+                Ok((format!("Changed port {}/{}: {} -> {}", port, protocol, old.context, context), vec![rollback]))
+            }
+            None => {
+                self.add_port(port, protocol, context, simulation)?;
+                Ok((format!("Added port {}/{} as {}", port, protocol, context), vec![delete]))
+            }
+        }
+    }
+
+    pub fn remove_port(&mut self, port: &PortRange, protocol: &str, simulation: bool) -> Result<()> {
         if simulation {
-            self.ports.retain(|p| p.port != port || p.protocol != protocol);
+            self.ports.retain(|p| p.port != *port || p.protocol != protocol);
             return Ok(());
         }
 
-        Command::new("semanage")
-        .args(&["port", "-d", "-p", protocol, port])
-        .output()?;
+        Self::run_semanage(&["port", "-d", "-p", protocol, &port.to_string()])?;
 
         self.load_ports()?;
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Строки `semanage port -l`: списки, диапазоны и все протоколы
+    const LIST: &str = "SELinux Port Type              Proto    Port Number\n\
+                        \n\
+                        http_cache_port_t              tcp      8080, 8118, 8123, 10001-10010\n\
+                        http_port_t                    tcp      80, 81, 443, 488, 8008, 8009, 8443, 9000\n\
+                        dns_port_t                     udp      53, 853\n\
+                        reserved_port_t                sctp     1-511\n\
+                        hi_reserved_port_t             sctp     512-1023\n\
+                        unreserved_port_t              tcp      61000-65535, 1024-32767\n\
+                        unreserved_port_t              dccp     1024-65535\n";
+
+    fn range(low: u16, high: u16) -> PortRange {
+        PortRange { low, high }
+    }
+
+    #[test]
+    fn port_range_from_str() {
+        assert_eq!("80".parse::<PortRange>().unwrap(), PortRange::single(80));
+        assert_eq!(" 8008-8009 ".parse::<PortRange>().unwrap(), range(8008, 8009));
+        assert_eq!("1024 - 65535".parse::<PortRange>().unwrap(), range(1024, 65535));
+        for bad in ["9-1", "70000", "1-70000", "", "http", "80-", "-80", "-1"] {
+            assert!(bad.parse::<PortRange>().is_err(), "{:?} must be rejected", bad);
+        }
+        assert_eq!(range(8008, 8009).to_string(), "8008-8009");
+        assert_eq!(PortRange::single(80).to_string(), "80");
+    }
+
+    #[test]
+    fn parses_lists_and_ranges() {
+        let mgr = PortManager { ports: PortManager::parse_port_list("http_port_t tcp 80, 81, 8008-8009\n") };
+        let ports: Vec<PortRange> = mgr.ports.iter().map(|p| p.port).collect();
+        assert_eq!(ports, [PortRange::single(80), PortRange::single(81), range(8008, 8009)]);
+        assert!(mgr.ports.iter().all(|p| p.protocol == "tcp" && p.context == "http_port_t"));
+    }
+
+    #[test]
+    fn parses_every_protocol_and_skips_header() {
+        let ports = PortManager::parse_port_list(LIST);
+        assert_eq!(ports.len(), 19);
+        let sctp: Vec<_> = ports.iter().filter(|p| p.protocol == "sctp").map(|p| (p.port, p.context.as_str())).collect();
+        assert_eq!(sctp, [(range(1, 511), "reserved_port_t"), (range(512, 1023), "hi_reserved_port_t")]);
+        let dccp: Vec<_> = ports.iter().filter(|p| p.protocol == "dccp").map(|p| p.port).collect();
+        assert_eq!(dccp, [range(1024, 65535)]);
+    }
+
+    #[test]
+    fn bad_items_are_skipped() {
+        let ports = PortManager::parse_port_list("odd_port_t tcp 9-1, 70000, 22\nodd_port_t icmp 7\n");
+        let ports: Vec<_> = ports.iter().map(|p| (p.port, p.protocol.as_str())).collect();
+        assert_eq!(ports, [(PortRange::single(22), "tcp")]);
+    }
+
+    #[test]
+    fn type_of_prefers_narrowest_range() {
+        let mgr = PortManager { ports: PortManager::parse_port_list(LIST) };
+        let type_of = |port, proto| mgr.type_of(port, proto).map(|p| p.context.as_str());
+        assert_eq!(type_of(8080, "tcp"), Some("http_cache_port_t"));
+        assert_eq!(type_of(10005, "tcp"), Some("http_cache_port_t"));
+        assert_eq!(type_of(12345, "tcp"), Some("unreserved_port_t"));
+        assert_eq!(type_of(53, "udp"), Some("dns_port_t"));
+        assert_eq!(type_of(53, "tcp"), None);
+        assert_eq!(type_of(40000, "tcp"), None);
+    }
+
+    #[test]
+    fn overlaps_and_overlapping() {
+        assert!(range(10, 20).overlaps(&range(20, 30)));
+        assert!(range(10, 20).overlaps(&PortRange::single(15)));
+        assert!(range(15, 16).overlaps(&range(10, 20)));
+        assert!(!range(10, 20).overlaps(&range(21, 30)));
+        assert!(!PortRange::single(9).overlaps(&range(10, 20)));
+
+        let mgr = PortManager { ports: PortManager::parse_port_list(LIST) };
+        let found: Vec<_> = mgr
+            .overlapping(&range(8000, 8100), "tcp")
+            .iter()
+            .map(|p| (p.port.to_string(), p.context.as_str()))
+            .collect();
+        // Самые узкие записи первыми, равные — в порядке списка
+        assert_eq!(
+            found,
+            [
+                ("8080".to_string(), "http_cache_port_t"),
+                ("8008".to_string(), "http_port_t"),
+                ("8009".to_string(), "http_port_t"),
+                ("1024-32767".to_string(), "unreserved_port_t"),
+            ]
+        );
+    }
+
+    #[test]
+    fn assign_rollback_restores_policy_or_local_type() {
+        let mut mgr = PortManager { ports: PortManager::parse_port_list(LIST) };
+
+        // Запись политики: откат снимает локальное переопределение
+        let pre = mgr.preflight(&PortRange::single(8080), "tcp", "http_port_t");
+        let (_, rollback) = mgr.assign(&pre, true).unwrap();
+        assert_eq!(rollback, ["semanage port -d -p 'tcp' '8080'"]);
+        assert!(mgr.find(&PortRange::single(8080), "tcp").unwrap().local);
+
+        // Теперь запись локальная: откат возвращает прежний тип
+        let pre = mgr.preflight(&PortRange::single(8080), "tcp", "websm_port_t");
+        let (_, rollback) = mgr.assign(&pre, true).unwrap();
+        assert_eq!(rollback, ["semanage port -m -t 'http_port_t' -p 'tcp' '8080'"]);
+
+        let pre = mgr.preflight(&range(40000, 40010), "udp", "my_port_t");
+        let (_, rollback) = mgr.assign(&pre, true).unwrap();
+        assert_eq!(rollback, ["semanage port -d -p 'udp' '40000-40010'"]);
+    }
+}
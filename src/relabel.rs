@@ -0,0 +1,284 @@
+use std::io::{BufRead, BufReader, Read};
+use std::path::PathBuf;
+use std::process::{Child, Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use anyhow::{Result, anyhow};
+use regex::Regex;
+use crate::label_scan::{find_mismatches, FileContextsDb};
+use crate::rollback::shell_quote;
+
+/// Изменение метки, о котором сообщил restorecon.
+#[derive(Debug, Clone)]
+pub struct RelabelChange {
+    pub path: String,
+    pub old: String,
+    pub new: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RelabelPhase {
+    Idle,
+    /// Пробный прогон `restorecon -n`
+    Previewing,
+    Relabeling,
+}
+
+enum RelabelEvent {
+    Change(RelabelChange),
+    Failed(String),
+    Finished,
+}
+
+/// Итог применения — для записи в историю изменений
+pub struct RelabelSummary {
+    pub description: String,
+    pub rollback_commands: Vec<String>,
+    pub changed_files: Vec<String>,
+}
+
+/// `restorecon` для пути: сначала пробный прогон (`-n`) со списком файлов,
+/// затем применение. Вывод `-v` читается построчно, процесс можно прервать.
+pub struct Relabeler {
+    pub path: String,
+    pub recursive: bool,
+    pub phase: RelabelPhase,
+    /// Файлы, которые изменит restorecon (результат пробного прогона)
+    pub preview: Vec<RelabelChange>,
+    /// Файлы, уже измененные при применении
+    pub changed: Vec<RelabelChange>,
+    pub errors: Vec<String>,
+    pub cancelled: bool,
+    child: Arc<Mutex<Option<Child>>>,
+    cancel: Arc<AtomicBool>,
+    rx: Option<Receiver<RelabelEvent>>,
+}
+
+impl Relabeler {
+    pub fn new() -> Self {
+        Self {
+            path: String::new(),
+            recursive: true,
+            phase: RelabelPhase::Idle,
+            preview: Vec::new(),
+            changed: Vec::new(),
+            errors: Vec::new(),
+            cancelled: false,
+            child: Arc::new(Mutex::new(None)),
+            cancel: Arc::new(AtomicBool::new(false)),
+            rx: None,
+        }
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.phase != RelabelPhase::Idle
+    }
+
+    fn begin(&mut self, phase: RelabelPhase) -> (Sender<RelabelEvent>, Arc<AtomicBool>) {
+        let (tx, rx) = mpsc::channel();
+        self.rx = Some(rx);
+        self.phase = phase;
+        self.errors.clear();
+        self.cancelled = false;
+        self.cancel = Arc::new(AtomicBool::new(false));
+        (tx, self.cancel.clone())
+    }
+
+    /// Пробный прогон. В режиме симуляции список строится по базе file_contexts.
+    pub fn start_preview(&mut self, path: &str, recursive: bool, db: FileContextsDb, simulation: bool) -> Result<()> {
+        if self.is_running() {
+            return Err(anyhow!("Relabel is already running"));
+        }
+        if !path.starts_with('/') {
+            return Err(anyhow!("Path must be absolute"));
+        }
+        std::fs::symlink_metadata(path).map_err(|e| anyhow!("Cannot relabel {}: {}", path, e))?;
+        self.path = path.to_string();
+        self.recursive = recursive;
+        self.preview.clear();
+        self.changed.clear();
+        let (tx, cancel) = self.begin(RelabelPhase::Previewing);
+
+        if simulation {
+            let root = PathBuf::from(path);
+            thread::spawn(move || {
+                for m in find_mismatches(&root, &db, recursive, &cancel) {
+                    let new = m.restored_label();
+                    let _ = tx.send(RelabelEvent::Change(RelabelChange { path: m.path, old: m.current, new }));
+                }
+                let _ = tx.send(RelabelEvent::Finished);
+            });
+            return Ok(());
+        }
+        self.spawn_restorecon(true, tx, cancel)
+    }
+
+    /// Применяет restorecon к тому же пути. В режиме симуляции метки не меняются —
+    /// воспроизводится список пробного прогона.
+    pub fn start_relabel(&mut self, simulation: bool) -> Result<()> {
+        if self.is_running() {
+            return Err(anyhow!("Relabel is already running"));
+        }
+        if self.preview.is_empty() {
+            return Err(anyhow!("Nothing to relabel"));
+        }
+        self.changed.clear();
+        let (tx, cancel) = self.begin(RelabelPhase::Relabeling);
+
+        if simulation {
+            let items = self.preview.clone();
+            thread::spawn(move || {
+                for item in items {
+                    if cancel.load(Ordering::Relaxed) {
+                        break;
+                    }
+                    let _ = tx.send(RelabelEvent::Change(item));
+                }
+                let _ = tx.send(RelabelEvent::Finished);
+            });
+            return Ok(());
+        }
+        self.spawn_restorecon(false, tx, cancel)
+    }
+
+    fn spawn_restorecon(&mut self, dry_run: bool, tx: Sender<RelabelEvent>, cancel: Arc<AtomicBool>) -> Result<()> {
+        let mut cmd = Command::new("restorecon");
+        if dry_run {
+            cmd.arg("-n");
+        }
+        if self.recursive {
+            cmd.arg("-R");
+        }
+        let mut child = match cmd.arg("-v").arg(&self.path).stdout(Stdio::piped()).stderr(Stdio::piped()).spawn() {
+            Ok(child) => child,
+            Err(e) => {
+                self.phase = RelabelPhase::Idle;
+                self.rx = None;
+                return Err(anyhow!("Failed to run restorecon: {}", e));
+            }
+        };
+        let stdout = child.stdout.take();
+        let stderr = child.stderr.take();
+        *self.child.lock().unwrap() = Some(child);
+        let child = self.child.clone();
+
+        thread::spawn(move || {
+            let stderr_reader = thread::spawn(move || {
+                let mut text = String::new();
+                if let Some(mut stderr) = stderr {
+                    let _ = stderr.read_to_string(&mut text);
+                }
+                text
+            });
+            if let Some(stdout) = stdout {
+                for line in BufReader::new(stdout).lines().map_while(|l| l.ok()) {
+                    if let Some(change) = parse_restorecon_line(&line) {
+                        let _ = tx.send(RelabelEvent::Change(change));
+                    }
+                }
+            }
+            let status = child.lock().unwrap().take().and_then(|mut c| c.wait().ok());
+            let stderr = stderr_reader.join().unwrap_or_default();
+            if !cancel.load(Ordering::Relaxed) && status.is_some_and(|s| !s.success()) {
+                let _ = tx.send(RelabelEvent::Failed(format!("restorecon failed: {}", stderr.trim())));
+            }
+            let _ = tx.send(RelabelEvent::Finished);
+        });
+        Ok(())
+    }
+
+    pub fn cancel(&mut self) {
+        if self.is_running() {
+            self.cancel.store(true, Ordering::Relaxed);
+            self.cancelled = true;
+            if let Some(child) = self.child.lock().unwrap().as_mut() {
+                let _ = child.kill();
+            }
+        }
+    }
+
+    /// Сбрасывает результат пробного прогона (применение отменено)
+    pub fn discard_preview(&mut self) {
+        if !self.is_running() {
+            self.preview.clear();
+        }
+    }
+
+    /// Обработано файлов и ожидаемое общее число (0 — неизвестно, идет пробный прогон)
+    pub fn progress(&self) -> (usize, usize) {
+        match self.phase {
+            RelabelPhase::Relabeling => (self.changed.len(), self.preview.len()),
+            _ => (self.preview.len(), 0),
+        }
+    }
+
+    /// Последний обработанный файл
+    pub fn current(&self) -> &str {
+        let last = match self.phase {
+            RelabelPhase::Relabeling => self.changed.last(),
+            _ => self.preview.last(),
+        };
+        last.map(|c| c.path.as_str()).unwrap_or("")
+    }
+
+    /// Забирает события фонового потока. Возвращает итог, когда применение
+    /// завершилось (в том числе прерванное) и хотя бы одна метка изменена.
+    pub fn poll(&mut self) -> Option<RelabelSummary> {
+        let rx = self.rx.as_ref()?;
+        let mut finished = false;
+        for event in rx.try_iter() {
+            match event {
+                RelabelEvent::Change(change) => match self.phase {
+                    RelabelPhase::Relabeling => self.changed.push(change),
+                    _ => self.preview.push(change),
+                },
+                RelabelEvent::Failed(e) => self.errors.push(e),
+                RelabelEvent::Finished => finished = true,
+            }
+        }
+        if !finished {
+            return None;
+        }
+        let phase = self.phase;
+        self.phase = RelabelPhase::Idle;
+        self.rx = None;
+        if phase != RelabelPhase::Relabeling || self.changed.is_empty() {
+            return None;
+        }
+
+        let rollback_commands = self
+            .changed
+            .iter()
+            .map(|c| format!("chcon -h {} {}", shell_quote(&c.old), shell_quote(&c.path)))
+            .collect();
+        let changed_files = self.changed.iter().map(|c| format!("{}: {} -> {}", c.path, c.old, c.new)).collect();
+        let description = format!(
+            "restorecon{} {}: relabeled {} file(s){}",
+            if self.recursive { " -R" } else { "" },
+            self.path,
+            self.changed.len(),
+            if self.cancelled { " (cancelled)" } else { "" }
+        );
+        self.preview.clear();
+        Some(RelabelSummary {
+            description,
+            rollback_commands,
+            changed_files,
+        })
+    }
+}
+
+/// Строка `restorecon -v`: "Relabeled /p from a to b" или "Would relabel /p from a to b" (`-n`)
+fn parse_restorecon_line(line: &str) -> Option<RelabelChange> {
+    lazy_static::lazy_static! {
+        static ref LINE_RE: Regex = Regex::new(r"^(?:Relabeled|Would relabel) (.+) from (\S+) to (\S+)$").unwrap();
+    }
+    let cap = LINE_RE.captures(line.trim())?;
+    Some(RelabelChange {
+        path: cap[1].to_string(),
+        old: cap[2].to_string(),
+        new: cap[3].to_string(),
+    })
+}
@@ -0,0 +1,274 @@
+use anyhow::{Result, anyhow};
+use crate::advisor::Advisor;
+use crate::avc::{AVCAlert, AVCSeverity};
+use crate::booleans::BooleanManager;
+use crate::file_contexts::{file_label, FcFileType, FileContextManager, Mislabel};
+use crate::modules::{ModuleFormat, ModuleManager};
+use crate::ports::{PortManager, PortRange};
+use crate::rollback::shell_quote;
+use crate::selinux_mode::SELinuxModeManager;
+use crate::severity_rules::{context_type, glob_match};
+
+/// Типы, которые политика отводит домену: контент (чтение / запись) и порт.
+struct DomainHint {
+    domain: &'static str,
+    content: &'static str,
+    rw_content: &'static str,
+    port: Option<&'static str>,
+}
+
+const DOMAIN_HINTS: &[DomainHint] = &[
+    DomainHint { domain: "httpd_t", content: "httpd_sys_content_t", rw_content: "httpd_sys_rw_content_t", port: Some("http_port_t") },
+    DomainHint { domain: "nginx_t", content: "httpd_sys_content_t", rw_content: "httpd_sys_rw_content_t", port: Some("http_port_t") },
+    DomainHint { domain: "ftpd_t", content: "public_content_t", rw_content: "public_content_rw_t", port: Some("ftp_port_t") },
+    DomainHint { domain: "smbd_t", content: "samba_share_t", rw_content: "samba_share_t", port: None },
+    DomainHint { domain: "named_t", content: "named_zone_t", rw_content: "named_cache_t", port: Some("dns_port_t") },
+    DomainHint { domain: "mysqld_t", content: "mysqld_db_t", rw_content: "mysqld_db_t", port: Some("mysqld_port_t") },
+    DomainHint { domain: "postgresql_t", content: "postgresql_db_t", rw_content: "postgresql_db_t", port: Some("postgresql_port_t") },
+    DomainHint { domain: "sshd_t", content: "sshd_key_t", rw_content: "sshd_key_t", port: Some("ssh_port_t") },
+    DomainHint { domain: "postfix_master_t", content: "postfix_etc_t", rw_content: "postfix_spool_t", port: Some("smtp_port_t") },
+    DomainHint { domain: "container_t", content: "container_file_t", rw_content: "container_file_t", port: None },
+];
+
+/// Тип порта, который политика отводит службе домена
+pub fn port_type_for_domain(domain: &str) -> Option<&'static str> {
+    DOMAIN_HINTS.iter().find(|h| h.domain == domain).and_then(|h| h.port)
+}
+
+/// Булевы, которые обычно закрывают отказ:
+/// (домен, класс, разрешение, тип цели, булево, нужное значение). Шаблоны — `*` и `?`.
+const BOOLEAN_HINTS: &[(&str, &str, &str, &str, &str, bool)] = &[
+    ("httpd_t", "tcp_socket", "name_connect", "*db_port_t", "httpd_can_network_connect_db", true),
+    ("httpd_t", "tcp_socket", "name_connect", "smtp_port_t", "httpd_can_sendmail", true),
+    ("httpd_t", "tcp_socket", "name_connect", "*", "httpd_can_network_connect", true),
+    ("httpd_t", "*", "*", "user_home*", "httpd_enable_homedirs", true),
+    ("httpd_t", "*", "*", "user_home*", "httpd_read_user_content", true),
+    ("httpd_t", "*", "write", "httpd_sys_content_t", "httpd_unified", true),
+    ("httpd_t", "process", "execmem", "*", "httpd_execmem", true),
+    ("ftpd_t", "*", "*", "user_home*", "ftp_home_dir", true),
+    ("ftpd_t", "*", "write", "*", "ftpd_full_access", true),
+    ("smbd_t", "*", "*", "user_home*", "samba_enable_home_dirs", true),
+    ("nfsd_t", "*", "write", "*", "nfs_export_all_rw", true),
+    ("container_t", "*", "*", "cgroup_t", "container_manage_cgroup", true),
+    ("*", "process", "execmem", "*", "deny_execmem", false),
+];
+
+/// Классы объектов, которые живут в файловой системе и имеют путь
+const FILE_CLASSES: [&str; 7] = ["file", "dir", "lnk_file", "sock_file", "fifo_file", "chr_file", "blk_file"];
+
+/// Разрешения, после которых домену нужен тип "на запись"
+const WRITE_PERMS: [&str; 8] = ["write", "append", "create", "unlink", "rename", "add_name", "remove_name", "setattr"];
+
+/// Способ устранения отказа.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Remedy {
+    /// `restorecon` — вернуть метку, которую ожидает политика
+    Restore { path: String, expected: String },
+    /// `semanage fcontext -a -t <type> <spec>` и `restorecon` на пути
+    Relabel { path: String, spec: String, file_type: String },
+    /// `semanage port -a|-m -t <type> -p <proto> <port>` (через `PortManager::preflight`)
+    PortType { port: PortRange, protocol: String, port_type: String },
+    /// `setsebool -P <name> on|off`
+    Boolean { name: String, value: bool },
+    /// `semanage permissive -a <domain>`
+    Permissive { domain: String },
+    /// Минимальный модуль только с этим разрешением
+    Module { name: String },
+}
+
+#[derive(Debug, Clone)]
+pub struct RemedyOption {
+    pub remedy: Remedy,
+    pub description: String,
+    pub risk: String,
+}
+
+/// Подбор и применение способов устранения AVC-отказа. Все изменения вносятся
+/// через типизированные менеджеры, а для каждого возвращаются точные команды отката.
+pub struct RemedyPlanner;
+
+impl RemedyPlanner {
+    /// Все применимые варианты, от наименее рискованного к наиболее.
+    /// `mislabel` — результат сравнения метки пути с ожидаемой политикой.
+    pub fn options(
+        alert: &AVCAlert,
+        mislabel: Option<&Mislabel>,
+        advisor: &Advisor,
+        boolean_manager: &BooleanManager,
+        module_manager: &ModuleManager,
+        file_context_manager: &FileContextManager,
+        port_manager: &PortManager,
+    ) -> Vec<RemedyOption> {
+        let domain = context_type(&alert.source_context);
+        let target = context_type(&alert.target_context);
+        let hint = DOMAIN_HINTS.iter().find(|h| h.domain == domain);
+        let mut options = Vec::new();
+
+        // 1) Восстановление метки — файл просто помечен не так, как ждет политика
+        if let Some(m) = mislabel {
+            options.push(RemedyOption {
+                description: format!("Restore label of {}: {} -> {} (restorecon)", m.path, m.current, m.expected),
+                risk: "Low".to_string(),
+                remedy: Remedy::Restore { path: m.path.clone(), expected: m.expected.clone() },
+            });
+        }
+
+        // 2) Новое правило метки — не расширяет права домена
+        if let (Some(hint), None) = (hint, mislabel) {
+            if FILE_CLASSES.contains(&alert.target_class.as_str()) && alert.path.starts_with('/') {
+                let file_type = if WRITE_PERMS.contains(&alert.permission.as_str()) {
+                    hint.rw_content
+                } else {
+                    hint.content
+                };
+                let spec = if alert.target_class == "dir" {
+                    format!("{}(/.*)?", alert.path)
+                } else {
+                    alert.path.clone()
+                };
+                let exists = file_context_manager.contexts.iter().any(|c| c.path == spec);
+                if target != file_type && !exists {
+                    options.push(RemedyOption {
+                        description: format!("Relabel {} as {} (fcontext rule + restorecon)", alert.path, file_type),
+                        risk: "Low".to_string(),
+                        remedy: Remedy::Relabel { path: alert.path.clone(), spec, file_type: file_type.to_string() },
+                    });
+                }
+            }
+        }
+
+        // 3) Тип порта — домен получает порт, предназначенный для его службы
+        if let (Some(port), Some(port_type)) = (alert.port, hint.and_then(|h| h.port)) {
+            let protocol = alert.target_class.trim_end_matches("_socket").to_string();
+            let port = PortRange::single(port);
+            if alert.permission == "name_bind" && target != port_type {
+                // Уже описанный порт меняется через `-m`: preflight покажет, чей он,
+                // и попросит подтверждения
+                let (description, risk) = match port_manager.find(&port, &protocol) {
+                    Some(old) => (
+                        format!("Change port {}/{} from {} to {}", port, protocol, old.context, port_type),
+                        "Medium",
+                    ),
+                    None => (format!("Label port {}/{} as {}", port, protocol, port_type), "Low"),
+                };
+                options.push(RemedyOption {
+                    description,
+                    risk: risk.to_string(),
+                    remedy: Remedy::PortType { port, protocol, port_type: port_type.to_string() },
+                });
+            }
+        }
+
+        // 4) Булевы — только существующие и еще не выставленные
+        for (d, class, perm, ttype, name, value) in BOOLEAN_HINTS {
+            let matches = glob_match(d, domain)
+                && glob_match(class, &alert.target_class)
+                && glob_match(perm, &alert.permission)
+                && glob_match(ttype, target);
+            let current = boolean_manager.booleans.iter().find(|b| b.name == *name);
+            if !matches || current.is_none_or(|b| b.current_value == *value) {
+                continue;
+            }
+            if options.iter().any(|o| matches!(&o.remedy, Remedy::Boolean { name: n, .. } if n == name)) {
+                continue;
+            }
+            let risk = advisor
+                .get_advice(name)
+                .map(|a| a.risk.clone())
+                .unwrap_or_else(|| "Medium".to_string());
+            options.push(RemedyOption {
+                description: format!("Set boolean {} {}", name, if *value { "on" } else { "off" }),
+                risk,
+                remedy: Remedy::Boolean { name: name.to_string(), value: *value },
+            });
+        }
+
+        // 5) Минимальный модуль — ровно одно allow-правило
+        let base = format!("selab_{}", domain.trim_end_matches("_t"));
+        let mut name = base.clone();
+        let mut n = 1;
+        while module_manager.modules.iter().any(|m| m.name == name) {
+            n += 1;
+            name = format!("{}{}", base, n);
+        }
+        options.push(RemedyOption {
+            description: format!(
+                "Install module {}: allow {} {}:{} {{ {} }}",
+                name, domain, target, alert.target_class, alert.permission
+            ),
+            risk: if matches!(alert.severity, AVCSeverity::High) { "High" } else { "Medium" }.to_string(),
+            remedy: Remedy::Module { name },
+        });
+
+        // 6) Permissive-домен — снимает все ограничения с домена
+        if domain != "unconfined_t" {
+            options.push(RemedyOption {
+                description: format!("Make domain {} permissive (all its denials are only logged)", domain),
+                risk: "High".to_string(),
+                remedy: Remedy::Permissive { domain: domain.to_string() },
+            });
+        }
+
+        options
+    }
+
+    /// Применяет вариант и возвращает команды отката.
+    pub fn apply(
+        remedy: &Remedy,
+        alert: &AVCAlert,
+        boolean_manager: &mut BooleanManager,
+        module_manager: &mut ModuleManager,
+        file_context_manager: &mut FileContextManager,
+        port_manager: &mut PortManager,
+        simulation: bool,
+    ) -> Result<Vec<String>> {
+        match remedy {
+            Remedy::Restore { path, .. } => {
+                let old_label = file_label(path).unwrap_or_else(|| alert.target_context.clone());
+                file_context_manager.restore(path, false, simulation)?;
+                if old_label.contains(':') {
+                    Ok(vec![format!("chcon {} {}", shell_quote(&old_label), shell_quote(path))])
+                } else {
+                    Ok(Vec::new())
+                }
+            }
+            Remedy::Relabel { path, spec, file_type } => {
+                // Метку запоминаем до изменения; без доступа к файлу берем tcontext отказа
+                let old_label = file_label(path).unwrap_or_else(|| alert.target_context.clone());
+                let recursive = alert.target_class == "dir";
+                file_context_manager.add_file_context(spec, file_type, FcFileType::All, simulation)?;
+                file_context_manager.restore(path, recursive, simulation)?;
+                let mut rollback = vec![format!("semanage fcontext -d -f a {}", shell_quote(spec))];
+                if recursive {
+                    rollback.push(format!("restorecon -R {}", shell_quote(path)));
+                }
+                if old_label.contains(':') {
+                    rollback.push(format!("chcon {} {}", shell_quote(&old_label), shell_quote(path)));
+                }
+                Ok(rollback)
+            }
+            Remedy::PortType { port, protocol, port_type } => {
+                let preflight = port_manager.preflight(port, protocol, port_type);
+                Ok(port_manager.assign(&preflight, simulation)?.1)
+            }
+            Remedy::Boolean { name, value } => {
+                let previous = boolean_manager
+                    .booleans
+                    .iter()
+                    .find(|b| &b.name == name)
+                    .map(|b| b.current_value)
+                    .ok_or_else(|| anyhow!("Unknown boolean {}", name))?;
+                boolean_manager.set_boolean(name, *value, simulation)?;
+                Ok(vec![format!("setsebool -P {} {}", name, if previous { "on" } else { "off" })])
+            }
+            Remedy::Permissive { domain } => {
+                SELinuxModeManager::set_domain_permissive(domain, true, simulation)?;
+                Ok(vec![format!("semanage permissive -d {}", domain)])
+            }
+            Remedy::Module { name } => {
+                module_manager.create_module_from_alerts(name, std::slice::from_ref(alert), ModuleFormat::default_for_system(), simulation)?;
+                Ok(vec![format!("semodule -r {}", name)])
+            }
+        }
+    }
+}
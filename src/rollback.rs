@@ -1,11 +1,15 @@
 use serde::{Deserialize, Serialize};
 use std::collections::VecDeque;
 use std::fs;
+use std::os::unix::fs::DirBuilderExt;
 use std::path::PathBuf;
 use chrono::Utc;
 use anyhow::{Result, anyhow, Context};
 use super::booleans::BooleanState;
 use super::modules::SELinuxModule;
+use super::severity_rules::context_type;
+use super::users::{LoginMapping, SELinuxUser};
+use super::network::{InterfaceContext, NodeContext};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SystemState {
@@ -14,7 +18,22 @@ pub struct SystemState {
     pub booleans: Vec<BooleanState>,
     pub modules: Vec<SELinuxModule>,
     pub file_contexts: Vec<String>,
+    /// Локальные эквивалентности путей: строки "path = equal"
+    #[serde(default)]
+    pub fc_equivalences: Vec<String>,
     pub ports: Vec<String>,
+    /// Локальные сопоставления логинов: строки "login:seuser:range"
+    #[serde(default)]
+    pub logins: Vec<String>,
+    /// Локальные пользователи SELinux: строки "name:role role:range"
+    #[serde(default)]
+    pub selinux_users: Vec<String>,
+    /// Локальные контексты интерфейсов: строки "name context"
+    #[serde(default)]
+    pub interfaces: Vec<String>,
+    /// Локальные контексты узлов: строки "address netmask protocol context"
+    #[serde(default)]
+    pub nodes: Vec<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -27,6 +46,9 @@ pub struct ChangeRecord {
     pub new_state: SystemState,
     pub rollback_commands: Vec<String>,
     pub applied_commands: Vec<String>,
+    /// Файлы, метки которых изменила операция ("path: old -> new")
+    #[serde(default)]
+    pub changed_files: Vec<String>,
 }
 
 #[derive(Clone)]
@@ -34,6 +56,8 @@ pub struct RollbackManager {
     pub change_history: VecDeque<ChangeRecord>,
     pub max_history: usize,
     history_path: PathBuf,
+    /// Id, выданный задаче заранее (под него сохраняются резервные копии)
+    reserved_id: Option<String>,
 }
 
 impl RollbackManager {
@@ -43,6 +67,7 @@ impl RollbackManager {
             change_history: VecDeque::new(),
             max_history: 200,
             history_path,
+            reserved_id: None,
         };
         let _ = manager.load_history_from_disk(); // тихая попытка загрузки
         manager
@@ -60,6 +85,46 @@ impl RollbackManager {
         home
     }
 
+    /// Резервные копии записи истории: `~/.config/selab/rollback/<id>`.
+    /// Каталог доступен только владельцу — из него откат ставит модули через semodule.
+    pub fn backup_dir(change_id: &str) -> Result<PathBuf> {
+        let dir = Self::backup_root().join(change_id);
+        fs::DirBuilder::new()
+            .recursive(true)
+            .mode(0o700)
+            .create(&dir)
+            .with_context(|| format!("Failed to create backup directory {:?}", dir))?;
+        Ok(dir)
+    }
+
+    /// Выдает id следующей записи заранее, чтобы задача могла сохранить
+    /// резервные копии в `backup_dir(id)` до того, как запись появится
+    pub fn reserve_change_id(&mut self) -> String {
+        let id = format!("chg_{}", Utc::now().timestamp_millis());
+        self.reserved_id = Some(id.clone());
+        id
+    }
+
+    /// Задача не удалась — id не пригодился, копии удаляем
+    pub fn release_change_id(&mut self) {
+        if let Some(id) = self.reserved_id.take() {
+            Self::remove_backups(&id);
+        }
+    }
+
+    fn remove_backups(change_id: &str) {
+        let _ = fs::remove_dir_all(Self::backup_root().join(change_id));
+    }
+
+    fn backup_root() -> PathBuf {
+        let mut dir = match dirs::config_dir() {
+            Some(dir) => dir.join("selab"),
+            None => std::env::var_os("HOME").map(PathBuf::from).unwrap_or_else(|| PathBuf::from(".")).join(".selab"),
+        };
+        dir.push("rollback");
+        dir
+    }
+
     fn load_history_from_disk(&mut self) -> Result<()> {
         if self.history_path.exists() {
             let data = fs::read_to_string(&self.history_path)
@@ -86,7 +151,9 @@ impl RollbackManager {
 
     fn trim_history(&mut self) {
         while self.change_history.len() > self.max_history {
-            self.change_history.pop_back();
+            if let Some(record) = self.change_history.pop_back() {
+                Self::remove_backups(&record.id);
+            }
         }
     }
 
@@ -97,6 +164,19 @@ impl RollbackManager {
         previous_state: SystemState,
         new_state: SystemState,
         provided_rollback_commands: Vec<String>,
+    ) {
+        self.record_change_with_files(action, description, previous_state, new_state, provided_rollback_commands, Vec::new());
+    }
+
+    /// Как `record_change`, но сохраняет список файлов с измененными метками
+    pub fn record_change_with_files(
+        &mut self,
+        action: String,
+        description: String,
+        previous_state: SystemState,
+        new_state: SystemState,
+        provided_rollback_commands: Vec<String>,
+        changed_files: Vec<String>,
     ) {
         let mut rollback_commands = provided_rollback_commands;
         // Автогенерация команд отката по дельтам состояний (добавит недостающие)
@@ -109,7 +189,7 @@ impl RollbackManager {
         }
 
         let record = ChangeRecord {
-            id: format!("chg_{}", Utc::now().timestamp_millis()),
+            id: self.reserved_id.take().unwrap_or_else(|| format!("chg_{}", Utc::now().timestamp_millis())),
             timestamp: Utc::now().to_rfc3339(),
             action,
             description,
@@ -117,6 +197,7 @@ impl RollbackManager {
             new_state,
             rollback_commands,
             applied_commands: Vec::new(),
+            changed_files,
         };
 
         self.change_history.push_front(record);
@@ -135,6 +216,8 @@ impl RollbackManager {
                     change.applied_commands.push(cmd);
                 }
             }
+            // Команды выполнены — резервные копии записи больше не нужны
+            Self::remove_backups(&change.id);
             // Запишем факт отката в историю как запись-метку (без автогенерации)
             let marker = ChangeRecord {
                 id: format!("rollback_{}", Utc::now().timestamp_millis()),
@@ -145,6 +228,7 @@ impl RollbackManager {
                 new_state: change.previous_state.clone(),
                 rollback_commands: Vec::new(),
                 applied_commands: change.applied_commands.clone(),
+                changed_files: Vec::new(),
             };
             self.change_history.push_front(marker);
             self.trim_history();
@@ -219,22 +303,42 @@ impl RollbackManager {
             }
         }
 
-        // 3) File contexts: строки формата "path:context"
+        // 3) File contexts (только локальные настройки): строки формата
+        // "path [f]:user:role:type:range"; в старых записях — "path:type"
         use std::collections::HashSet;
         let prev_fc: HashSet<_> = previous.file_contexts.iter().cloned().collect();
         let new_fc: HashSet<_> = new.file_contexts.iter().cloned().collect();
         // То, что было раньше, но пропало в новом — нужно добавить обратно
         for missing in prev_fc.difference(&new_fc) {
-            if let Some((path, ctx)) = Self::split_once(missing, ':') {
-                cmds.push(format!("semanage fcontext -a -t {} {}", ctx, path));
+            if let Some((path, flag, ctx)) = Self::split_fcontext(missing) {
+                cmds.push(format!(
+                    "semanage fcontext -a -f {} -t {} {}",
+                    flag,
+                    context_type(&ctx),
+                    shell_quote(&path)
+                ));
                 // Восстановление метки на FS — опционально, но полезно
-                cmds.push(format!("restorecon -v {}", path));
+                cmds.push(format!("restorecon -v {}", shell_quote(&path)));
             }
         }
         // То, что появилось в новом и отсутствовало в прежнем — нужно удалить
         for extra in new_fc.difference(&prev_fc) {
-            if let Some((path, _ctx)) = Self::split_once(extra, ':') {
-                cmds.push(format!("semanage fcontext -d {}", path));
+            if let Some((path, flag, _ctx)) = Self::split_fcontext(extra) {
+                cmds.push(format!("semanage fcontext -d -f {} {}", flag, shell_quote(&path)));
+            }
+        }
+
+        // 3a) Эквивалентности путей: строки формата "path = equal"
+        let prev_eq: HashSet<_> = previous.fc_equivalences.iter().cloned().collect();
+        let new_eq: HashSet<_> = new.fc_equivalences.iter().cloned().collect();
+        for extra in new_eq.difference(&prev_eq) {
+            if let Some((path, _)) = extra.split_once(" = ") {
+                cmds.push(format!("semanage fcontext -d {}", shell_quote(path)));
+            }
+        }
+        for missing in prev_eq.difference(&new_eq) {
+            if let Some((path, equal)) = missing.split_once(" = ") {
+                cmds.push(format!("semanage fcontext -a -e {} {}", shell_quote(equal), shell_quote(path)));
             }
         }
 
@@ -256,9 +360,72 @@ impl RollbackManager {
             }
         }
 
+        // 5) Пользователи SELinux и сопоставления логинов. Пользователей
+        // возвращаем до логинов, удаляем — после: логин ссылается на пользователя
+        let prev_users: HashSet<_> = previous.selinux_users.iter().cloned().collect();
+        let new_users: HashSet<_> = new.selinux_users.iter().cloned().collect();
+        let prev_logins: HashSet<_> = previous.logins.iter().cloned().collect();
+        let new_logins: HashSet<_> = new.logins.iter().cloned().collect();
+        let name_of = |s: &String| s.split(':').next().unwrap_or("").to_string();
+        let prev_user_names: HashSet<_> = prev_users.iter().map(name_of).collect();
+        let new_user_names: HashSet<_> = new_users.iter().map(name_of).collect();
+        let prev_login_names: HashSet<_> = prev_logins.iter().map(name_of).collect();
+        let new_login_names: HashSet<_> = new_logins.iter().map(name_of).collect();
+        for user in prev_users.difference(&new_users).filter_map(|s| SELinuxUser::from_state_line(s)) {
+            let action = if new_user_names.contains(&user.name) { "-m" } else { "-a" };
+            cmds.push(user.semanage_command(action));
+        }
+        for login in prev_logins.difference(&new_logins).filter_map(|s| LoginMapping::from_state_line(s)) {
+            let action = if new_login_names.contains(&login.login) { "-m" } else { "-a" };
+            cmds.push(login.semanage_command(action));
+        }
+        for login in new_login_names.difference(&prev_login_names) {
+            cmds.push(format!("semanage login -d {}", shell_quote(login)));
+        }
+        for name in new_user_names.difference(&prev_user_names) {
+            cmds.push(format!("semanage user -d {}", shell_quote(name)));
+        }
+
+        // 6) Интерфейсы и узлы
+        let prev_if: Vec<_> = previous.interfaces.iter().filter_map(|s| InterfaceContext::from_state_line(s)).collect();
+        let new_if: Vec<_> = new.interfaces.iter().filter_map(|s| InterfaceContext::from_state_line(s)).collect();
+        for old in &prev_if {
+            match new_if.iter().find(|i| i.name == old.name) {
+                Some(cur) if cur.context == old.context => {}
+                Some(_) => cmds.push(old.semanage_command("-m")),
+                None => cmds.push(old.semanage_command("-a")),
+            }
+        }
+        for cur in new_if.iter().filter(|i| !prev_if.iter().any(|o| o.name == i.name)) {
+            cmds.push(cur.delete_command());
+        }
+        let prev_nodes: Vec<_> = previous.nodes.iter().filter_map(|s| NodeContext::from_state_line(s)).collect();
+        let new_nodes: Vec<_> = new.nodes.iter().filter_map(|s| NodeContext::from_state_line(s)).collect();
+        for old in &prev_nodes {
+            match new_nodes.iter().find(|n| n.same_node(old)) {
+                Some(cur) if cur.context == old.context => {}
+                Some(_) => cmds.push(old.semanage_command("-m")),
+                None => cmds.push(old.semanage_command("-a")),
+            }
+        }
+        for cur in new_nodes.iter().filter(|n| !prev_nodes.iter().any(|o| o.same_node(n))) {
+            cmds.push(cur.delete_command());
+        }
+
         cmds
     }
 
+    /// (путь, флаг `-f`, контекст) из строки состояния file context
+    fn split_fcontext(s: &str) -> Option<(String, String, String)> {
+        if let Some((head, ctx)) = s.split_once("]:") {
+            if let Some((path, flag)) = head.rsplit_once(" [") {
+                return Some((path.to_string(), flag.to_string(), ctx.to_string()));
+            }
+        }
+        let (path, ctx) = Self::split_once(s, ':')?;
+        Some((path, "a".to_string(), ctx))
+    }
+
     fn split_once(s: &str, sep: char) -> Option<(String, String)> {
         let mut it = s.splitn(2, sep);
         let a = it.next()?;
@@ -266,3 +433,8 @@ impl RollbackManager {
         Some((a.to_string(), b.to_string()))
     }
 }
+
+/// Экранирование аргумента для команд отката (они выполняются через `sh -c`)
+pub fn shell_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "'\\''"))
+}
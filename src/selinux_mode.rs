@@ -120,6 +120,25 @@ impl SELinuxModeManager {
         Ok(())
     }
     
+    /// Переводит отдельный домен в permissive (`semanage permissive -a`) или
+    /// возвращает его в enforcing (`-d`). Остальная система не затрагивается.
+    pub fn set_domain_permissive(domain: &str, permissive: bool, simulation: bool) -> Result<()> {
+        if simulation {
+            return Ok(());
+        }
+        let flag = if permissive { "-a" } else { "-d" };
+        let output = Command::new("semanage")
+            .args(["permissive", flag, domain])
+            .output()?;
+        if !output.status.success() {
+            return Err(anyhow::anyhow!(
+                "semanage permissive failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+        Ok(())
+    }
+
     pub fn set_mode(&mut self, mode: SELinuxMode, persistent: bool, simulation: bool) -> Result<()> {
         if persistent {
             mode.set_persistent(simulation)?;
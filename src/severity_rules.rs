@@ -0,0 +1,279 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+use std::time::SystemTime;
+use anyhow::{Result, Context};
+use crate::avc::AVCSeverity;
+
+/// Правило классификации AVC. Пустое поле означает "любое значение".
+/// Все поля поддерживают шаблоны с `*` и `?`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SeverityRule {
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub source_type: Option<String>,
+    #[serde(default)]
+    pub target_type: Option<String>,
+    #[serde(default)]
+    pub class: Option<String>,
+    #[serde(default)]
+    pub permission: Option<String>,
+    #[serde(default)]
+    pub path: Option<String>,
+    pub severity: AVCSeverity,
+}
+
+impl SeverityRule {
+    fn new(description: &str, severity: AVCSeverity) -> Self {
+        Self {
+            description: description.to_string(),
+            source_type: None,
+            target_type: None,
+            class: None,
+            permission: None,
+            path: None,
+            severity,
+        }
+    }
+
+    fn target(mut self, pattern: &str) -> Self {
+        self.target_type = Some(pattern.to_string());
+        self
+    }
+
+    fn class(mut self, pattern: &str) -> Self {
+        self.class = Some(pattern.to_string());
+        self
+    }
+
+    fn perm(mut self, pattern: &str) -> Self {
+        self.permission = Some(pattern.to_string());
+        self
+    }
+
+    fn path(mut self, pattern: &str) -> Self {
+        self.path = Some(pattern.to_string());
+        self
+    }
+
+    pub fn matches(&self, source_type: &str, target_type: &str, class: &str, permission: &str, path: &str) -> bool {
+        fn field(pattern: &Option<String>, value: &str) -> bool {
+            pattern.as_ref().is_none_or(|p| glob_match(p, value))
+        }
+        field(&self.source_type, source_type)
+            && field(&self.target_type, target_type)
+            && field(&self.class, class)
+            && field(&self.permission, permission)
+            && field(&self.path, path)
+    }
+}
+
+/// Набор правил, загружаемый из `~/.config/selab/severity_rules.json`.
+/// Файл перечитывается автоматически, если изменилось время модификации.
+#[derive(Clone)]
+pub struct SeverityRules {
+    pub rules: Vec<SeverityRule>,
+    rules_path: PathBuf,
+    loaded_mtime: Option<SystemTime>,
+    /// Ошибка последнего чтения файла, еще не показанная пользователю
+    error: Option<String>,
+}
+
+impl SeverityRules {
+    pub fn new() -> Self {
+        let mut rules = Self {
+            rules: Self::default_rules(),
+            rules_path: Self::default_rules_path(),
+            loaded_mtime: None,
+            error: None,
+        };
+        if !rules.rules_path.exists() {
+            let _ = rules.save_defaults(); // даём пользователю шаблон для редактирования
+        }
+        rules.refresh();
+        rules
+    }
+
+    fn default_rules_path() -> PathBuf {
+        if let Some(mut dir) = dirs::config_dir() {
+            dir.push("selab");
+            let _ = fs::create_dir_all(&dir);
+            dir.push("severity_rules.json");
+            return dir;
+        }
+        let mut home = std::env::var_os("HOME").map(PathBuf::from).unwrap_or_else(|| PathBuf::from("."));
+        home.push(".selab_severity_rules.json");
+        home
+    }
+
+    fn save_defaults(&self) -> Result<()> {
+        let data = serde_json::to_string_pretty(&Self::default_rules())
+            .with_context(|| "Failed to serialize severity rules")?;
+        fs::write(&self.rules_path, data)
+            .with_context(|| format!("Failed to write severity rules at {:?}", self.rules_path))?;
+        Ok(())
+    }
+
+    /// Перечитывает файл правил, если он изменился с момента последней загрузки.
+    /// При ошибке разбора остаются прежние правила.
+    pub fn reload_if_changed(&mut self) -> Result<()> {
+        let mtime = match fs::metadata(&self.rules_path).and_then(|m| m.modified()) {
+            Ok(t) => t,
+            Err(_) => return Ok(()),
+        };
+        if self.loaded_mtime == Some(mtime) {
+            return Ok(());
+        }
+        let data = fs::read_to_string(&self.rules_path)
+            .with_context(|| format!("Failed to read severity rules at {:?}", self.rules_path))?;
+        self.loaded_mtime = Some(mtime);
+        let rules: Vec<SeverityRule> = serde_json::from_str(&data)
+            .with_context(|| "Failed to parse severity rules JSON")?;
+        self.rules = rules;
+        Ok(())
+    }
+
+    /// Как `reload_if_changed`, но ошибку запоминает для `take_error`
+    pub fn refresh(&mut self) {
+        if let Err(e) = self.reload_if_changed() {
+            self.error = Some(format!("{:#}", e));
+        }
+    }
+
+    /// Ошибка разбора файла правил. Сообщается один раз на каждую версию файла.
+    pub fn take_error(&mut self) -> Option<String> {
+        self.error.take()
+    }
+
+    /// Первое подходящее правило определяет уровень. Если ни одно не подошло —
+    /// используется классификация по разрешению.
+    pub fn classify(&self, source_context: &str, target_context: &str, class: &str, permission: &str, path: &str) -> AVCSeverity {
+        let source_type = context_type(source_context);
+        let target_type = context_type(target_context);
+        self.rules
+            .iter()
+            .find(|r| r.matches(source_type, target_type, class, permission, path))
+            .map(|r| r.severity.clone())
+            .unwrap_or_else(|| Self::fallback(permission))
+    }
+
+    fn fallback(permission: &str) -> AVCSeverity {
+        match permission {
+            "execute" | "write" | "unlink" => AVCSeverity::High,
+            "read" | "getattr" => AVCSeverity::Medium,
+            _ => AVCSeverity::Low,
+        }
+    }
+
+    fn default_rules() -> Vec<SeverityRule> {
+        vec![
+            SeverityRule::new("Доступ к паролям и теневым файлам", AVCSeverity::High).target("shadow_t"),
+            SeverityRule::new("Доступ к /etc/passwd и group", AVCSeverity::High).target("passwd_file_t").perm("write"),
+            SeverityRule::new("Доступ к ключам и сертификатам", AVCSeverity::High).target("*_key_t"),
+            SeverityRule::new("Доступ к ключам ядра", AVCSeverity::High).class("key"),
+            SeverityRule::new("Доступ к SSH-ключам пользователей", AVCSeverity::High).target("ssh_home_t"),
+            SeverityRule::new("Доступ к конфигурации sshd", AVCSeverity::High).target("sshd_*"),
+            SeverityRule::new("Приватные ключи на диске", AVCSeverity::High).path("*/.ssh/*"),
+            SeverityRule::new("Изменение политики SELinux", AVCSeverity::High).target("security_t"),
+            SeverityRule::new("Изменение файлов политики", AVCSeverity::High).target("semanage_*"),
+            SeverityRule::new("Доступ к sudoers", AVCSeverity::High).path("/etc/sudoers*"),
+            SeverityRule::new("Загрузка модулей ядра", AVCSeverity::High).perm("module_load"),
+            SeverityRule::new("Временные файлы: только чтение атрибутов", AVCSeverity::Low).target("tmp_t").perm("getattr"),
+        ]
+    }
+}
+
+/// Извлекает тип из контекста `user:role:type:level`. Для голого типа возвращает его же.
+pub fn context_type(context: &str) -> &str {
+    context.split(':').nth(2).unwrap_or(context)
+}
+
+/// Простое сопоставление с шаблоном: `*` — любая последовательность, `?` — один символ.
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    let p: Vec<char> = pattern.chars().collect();
+    let t: Vec<char> = text.chars().collect();
+    let (mut pi, mut ti) = (0, 0);
+    let mut star: Option<(usize, usize)> = None;
+    while ti < t.len() {
+        if pi < p.len() && (p[pi] == '?' || p[pi] == t[ti]) {
+            pi += 1;
+            ti += 1;
+        } else if pi < p.len() && p[pi] == '*' {
+            star = Some((pi, ti));
+            pi += 1;
+        } else if let Some((sp, st)) = star {
+            pi = sp + 1;
+            ti = st + 1;
+            star = Some((sp, st + 1));
+        } else {
+            return false;
+        }
+    }
+    while pi < p.len() && p[pi] == '*' {
+        pi += 1;
+    }
+    pi == p.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rules(rules: Vec<SeverityRule>) -> SeverityRules {
+        SeverityRules {
+            rules,
+            rules_path: PathBuf::new(),
+            loaded_mtime: None,
+            error: None,
+        }
+    }
+
+    #[test]
+    fn glob_patterns() {
+        assert!(glob_match("*_key_t", "sshd_key_t"));
+        assert!(glob_match("sshd_*", "sshd_t"));
+        assert!(glob_match("*/.ssh/*", "/home/alice/.ssh/id_rsa"));
+        assert!(glob_match("http?_t", "httpd_t"));
+        assert!(glob_match("*", ""));
+        assert!(glob_match("a*b*c", "axxbyyc"));
+        assert!(!glob_match("a*b*c", "axxbyy"));
+        assert!(!glob_match("http?_t", "http_t"));
+        assert!(!glob_match("shadow_t", "shadow_t2"));
+    }
+
+    #[test]
+    fn first_matching_rule_wins() {
+        let rules = rules(vec![
+            SeverityRule::new("tmp getattr", AVCSeverity::Low).target("tmp_t").perm("getattr"),
+            SeverityRule::new("any tmp", AVCSeverity::High).target("tmp_t"),
+        ]);
+        let classify = |perm| rules.classify("system_u:system_r:httpd_t:s0", "system_u:object_r:tmp_t:s0", "file", perm, "/tmp/x");
+        assert!(matches!(classify("getattr"), AVCSeverity::Low));
+        assert!(matches!(classify("read"), AVCSeverity::High));
+    }
+
+    #[test]
+    fn falls_back_to_permission() {
+        let rules = rules(vec![SeverityRule::new("keys", AVCSeverity::High).path("*/.ssh/*")]);
+        assert!(matches!(rules.classify("httpd_t", "etc_t", "file", "write", "/etc/motd"), AVCSeverity::High));
+        assert!(matches!(rules.classify("httpd_t", "etc_t", "file", "read", "/etc/motd"), AVCSeverity::Medium));
+        assert!(matches!(rules.classify("httpd_t", "etc_t", "file", "lock", "/etc/motd"), AVCSeverity::Low));
+        assert!(matches!(rules.classify("httpd_t", "user_home_t", "file", "lock", "/home/a/.ssh/config"), AVCSeverity::High));
+    }
+
+    #[test]
+    fn malformed_file_keeps_rules_and_reports_error() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("severity_rules.json");
+        fs::write(&path, "[{\"severity\": \"High\",}]").unwrap();
+        let mut rules = rules(SeverityRules::default_rules());
+        rules.rules_path = path;
+        rules.refresh();
+        assert_eq!(rules.rules.len(), SeverityRules::default_rules().len());
+        assert!(rules.take_error().unwrap().contains("Failed to parse severity rules JSON"));
+        // Та же версия файла — повторно не сообщаем
+        rules.refresh();
+        assert!(rules.take_error().is_none());
+    }
+}
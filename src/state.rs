@@ -12,6 +12,9 @@ pub enum CurrentView {
     Ports,
     Statistics,
     SELinuxMode,
+    LabelScan,
+    Users,
+    Network,
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -25,14 +28,28 @@ pub enum InputMode {
 pub enum PopupType {
     None,
     AddPort,
+    ModifyPort, // Смена типа существующей записи порта
+    PortConfirm, // Подтверждение: порт уже описан или это стандартный порт службы
     AddFileContext,
     Help(String), // Показать справку по конкретному ключу
     Search,
+    AVCQuery, // Запрос к истории AVC (время + поля)
     DetailView(String), // Детальная информация об элементе
     ExportConfig,
     ImportConfig,
     AVCRecommendations,
     CreateModule, // Создание модуля из AVC
+    IgnoreReason, // Причина игнорирования AVC-сигнатуры
+    RemedyChooser, // Выбор способа устранения AVC-отказа
+    ScanRoot, // Каталог для поиска файлов с неверными метками
+    RelabelPath, // Путь для restorecon
+    RelabelConfirm, // Подтверждение по результату пробного прогона restorecon
+    UserMapping, // Добавление/изменение логина или пользователя SELinux
+    NetworkContext, // Контекст интерфейса или узла
+    InstallModule, // Путь к модулю и приоритет установки
+    ModuleSource, // Извлеченный исходник модуля и сводка по нему
+    ModuleProjectName, // Имя проекта модуля (.te/.fc/.if)
+    ModuleProject, // Проект модуля: правка, проверка, сборка и установка
 }
 
 impl CurrentView {
@@ -47,13 +64,16 @@ impl CurrentView {
             Self::FileContexts => Self::Ports,
             Self::Ports => Self::Statistics,
             Self::Statistics => Self::SELinuxMode,
-            Self::SELinuxMode => Self::Dashboard,
+            Self::SELinuxMode => Self::LabelScan,
+            Self::LabelScan => Self::Users,
+            Self::Users => Self::Network,
+            Self::Network => Self::Dashboard,
         }
     }
 
     pub fn previous(&self) -> Self {
         match self {
-            Self::Dashboard => Self::SELinuxMode,
+            Self::Dashboard => Self::Network,
             Self::AVCAlerts => Self::Dashboard,
             Self::ModuleManager => Self::AVCAlerts,
             Self::BooleanManager => Self::ModuleManager,
@@ -63,6 +83,9 @@ impl CurrentView {
             Self::Ports => Self::FileContexts,
             Self::Statistics => Self::Ports,
             Self::SELinuxMode => Self::Statistics,
+            Self::LabelScan => Self::SELinuxMode,
+            Self::Users => Self::LabelScan,
+            Self::Network => Self::Users,
         }
     }
 }
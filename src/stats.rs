@@ -1,16 +1,27 @@
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use chrono::{Duration, Local};
 use crate::avc::{AVCManager, AVCSeverity};
+use crate::avc_query::alert_time;
+use crate::avc_store::{AVCStore, DomainTrend};
+use crate::severity_rules::context_type;
+use crate::triage::TriageManager;
 use crate::booleans::BooleanManager;
 use crate::modules::ModuleManager;
 use crate::rollback::RollbackManager;
 
+/// Число часовых интервалов в графике частоты отказов
+pub const TIMELINE_HOURS: i64 = 24;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SystemStats {
     pub total_avc_alerts: usize,
     pub avc_by_severity: HashMap<String, usize>,
     pub avc_by_permission: HashMap<String, usize>,
     pub avc_by_source: HashMap<String, usize>,
+    pub avc_by_target: HashMap<String, usize>,
+    /// Отказы по часам за последние сутки (последний элемент — текущий час)
+    pub avc_timeline: Vec<u64>,
     pub total_booleans: usize,
     pub booleans_changed: usize,
     pub total_modules: usize,
@@ -18,6 +29,11 @@ pub struct SystemStats {
     pub total_changes: usize,
     pub recent_changes: Vec<ChangeSummary>,
     pub risk_score: f64,
+    pub history_total: usize,
+    /// Отказы, помеченные как ignored/expected и не учитываемые в риске
+    pub avc_suppressed: usize,
+    pub new_signatures_week: Vec<String>,
+    pub domain_trends: Vec<DomainTrend>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -35,12 +51,15 @@ impl StatsManager {
         boolean_manager: &BooleanManager,
         module_manager: &ModuleManager,
         rollback_manager: &RollbackManager,
+        avc_store: &AVCStore,
+        triage: &TriageManager,
     ) -> SystemStats {
         let total_avc = avc_manager.alerts.len();
         
         let mut avc_by_severity = HashMap::new();
         let mut avc_by_permission = HashMap::new();
         let mut avc_by_source = HashMap::new();
+        let mut avc_by_target = HashMap::new();
         
         for alert in &avc_manager.alerts {
             let severity_str = match alert.severity {
@@ -51,8 +70,32 @@ impl StatsManager {
             *avc_by_severity.entry(severity_str.to_string()).or_insert(0) += 1;
             *avc_by_permission.entry(alert.permission.clone()).or_insert(0) += 1;
             
-            let source = alert.source_context.split(':').next().unwrap_or("unknown").to_string();
+            let source = context_type(&alert.source_context).to_string();
             *avc_by_source.entry(source).or_insert(0) += 1;
+            let target = context_type(&alert.target_context).to_string();
+            *avc_by_target.entry(target).or_insert(0) += 1;
+        }
+
+        // Частота отказов: берем историю из базы, а если она пуста (например,
+        // в режиме симуляции) — текущие алерты
+        let now = Local::now();
+        let mut avc_timeline = vec![0u64; TIMELINE_HOURS as usize];
+        let mut add_to_timeline = |ts: i64| {
+            let hours_ago = (now.timestamp() - ts) / 3600;
+            if (0..TIMELINE_HOURS).contains(&hours_ago) {
+                avc_timeline[(TIMELINE_HOURS - 1 - hours_ago) as usize] += 1;
+            }
+        };
+        if avc_store.records.is_empty() {
+            for alert in &avc_manager.alerts {
+                if let Some(t) = alert_time(alert) {
+                    add_to_timeline(t.timestamp());
+                }
+            }
+        } else {
+            for record in &avc_store.records {
+                add_to_timeline(record.event_time);
+            }
         }
         
         let total_booleans = boolean_manager.booleans.len();
@@ -60,8 +103,8 @@ impl StatsManager {
             .filter(|b| b.current_value != b.default_value)
             .count();
         
-        let total_modules = module_manager.modules.len();
-        let modules_enabled = module_manager.modules.iter()
+        let total_modules = module_manager.active_modules().count();
+        let modules_enabled = module_manager.active_modules()
             .filter(|m| m.enabled)
             .count();
         
@@ -78,8 +121,11 @@ impl StatsManager {
         
         // Расчет risk score: учитываем только реальные изменения безопасности
         // Переключения режима SELinux не учитываются как риск
-        let high_severity_count = avc_by_severity.get("High").copied().unwrap_or(0);
-        let medium_severity_count = avc_by_severity.get("Medium").copied().unwrap_or(0);
+        // Известные безопасные отказы (ignored/expected) риск не увеличивают
+        let active_alerts: Vec<_> = avc_manager.alerts.iter().filter(|a| !triage.is_suppressed(a)).collect();
+        let avc_suppressed = total_avc - active_alerts.len();
+        let high_severity_count = active_alerts.iter().filter(|a| matches!(a.severity, AVCSeverity::High)).count();
+        let medium_severity_count = active_alerts.iter().filter(|a| matches!(a.severity, AVCSeverity::Medium)).count();
         
         // Фильтруем изменения: исключаем переключения режима SELinux
         let security_changes = rollback_manager.change_history.iter()
@@ -96,6 +142,8 @@ impl StatsManager {
             avc_by_severity,
             avc_by_permission,
             avc_by_source,
+            avc_by_target,
+            avc_timeline,
             total_booleans,
             booleans_changed,
             total_modules,
@@ -103,9 +151,21 @@ impl StatsManager {
             total_changes,
             recent_changes,
             risk_score,
+            history_total: avc_store.records.len(),
+            avc_suppressed,
+            new_signatures_week: avc_store.new_signatures_since(Local::now() - Duration::days(7)),
+            domain_trends: avc_store.domain_trends(),
         }
     }
     
+    /// Топ-N значений по убыванию количества
+    pub fn top_entries(map: &HashMap<String, usize>, n: usize) -> Vec<(String, usize)> {
+        let mut entries: Vec<(String, usize)> = map.iter().map(|(k, v)| (k.clone(), *v)).collect();
+        entries.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        entries.truncate(n);
+        entries
+    }
+
     pub fn get_risk_level(risk_score: f64) -> (&'static str, ratatui::style::Color) {
         use ratatui::style::Color;
         if risk_score >= 50.0 {
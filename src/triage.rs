@@ -0,0 +1,147 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use chrono::Utc;
+use anyhow::{Result, Context};
+use crate::avc::AVCAlert;
+
+/// Состояние разбора сигнатуры отказа.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum TriageState {
+    New,
+    /// Отказ известен, но еще требует действий
+    Acknowledged,
+    /// Отказ намеренно игнорируется (с причиной)
+    Ignored,
+    /// Ожидаемое поведение — не является проблемой
+    Expected,
+}
+
+impl TriageState {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::New => "new",
+            Self::Acknowledged => "ack",
+            Self::Ignored => "ignored",
+            Self::Expected => "expected",
+        }
+    }
+
+    /// Скрывается ли отказ из списка, рекомендаций и оценки риска
+    pub fn is_suppressed(&self) -> bool {
+        matches!(self, Self::Ignored | Self::Expected)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TriageEntry {
+    pub signature: String,
+    pub state: TriageState,
+    #[serde(default)]
+    pub reason: String,
+    pub updated: String,
+}
+
+/// Отметки разбора по сигнатурам (`~/.config/selab/triage.json`).
+#[derive(Clone)]
+pub struct TriageManager {
+    pub entries: HashMap<String, TriageEntry>,
+    triage_path: PathBuf,
+}
+
+impl TriageManager {
+    pub fn new() -> Self {
+        let mut manager = Self {
+            entries: HashMap::new(),
+            triage_path: Self::default_triage_path(),
+        };
+        let path = manager.triage_path.clone();
+        let _ = manager.merge_from_file(&path); // тихая попытка загрузки
+        manager
+    }
+
+    fn default_triage_path() -> PathBuf {
+        if let Some(mut dir) = dirs::config_dir() {
+            dir.push("selab");
+            let _ = fs::create_dir_all(&dir);
+            dir.push("triage.json");
+            return dir;
+        }
+        let mut home = std::env::var_os("HOME").map(PathBuf::from).unwrap_or_else(|| PathBuf::from("."));
+        home.push(".selab_triage.json");
+        home
+    }
+
+    fn save_to_disk(&self) -> Result<()> {
+        self.export_to_file(&self.triage_path, false)
+    }
+
+    pub fn state_of(&self, alert: &AVCAlert) -> TriageState {
+        self.entries
+            .get(&alert.signature())
+            .map(|e| e.state)
+            .unwrap_or(TriageState::New)
+    }
+
+    pub fn entry_of(&self, alert: &AVCAlert) -> Option<&TriageEntry> {
+        self.entries.get(&alert.signature())
+    }
+
+    pub fn is_suppressed(&self, alert: &AVCAlert) -> bool {
+        self.state_of(alert).is_suppressed()
+    }
+
+    pub fn set_state(&mut self, alert: &AVCAlert, state: TriageState, reason: &str) -> Result<()> {
+        let signature = alert.signature();
+        if state == TriageState::New {
+            self.entries.remove(&signature);
+        } else {
+            self.entries.insert(
+                signature.clone(),
+                TriageEntry {
+                    signature,
+                    state,
+                    reason: reason.to_string(),
+                    updated: Utc::now().to_rfc3339(),
+                },
+            );
+        }
+        self.save_to_disk()
+    }
+
+    /// Экспортирует отметки в файл. `suppressed_only` — только игнорируемые и
+    /// ожидаемые (список для переноса на другие хосты).
+    pub fn export_to_file(&self, path: &Path, suppressed_only: bool) -> Result<()> {
+        let mut list: Vec<&TriageEntry> = self
+            .entries
+            .values()
+            .filter(|e| !suppressed_only || e.state.is_suppressed())
+            .collect();
+        list.sort_by(|a, b| a.signature.cmp(&b.signature));
+        let data = serde_json::to_string_pretty(&list).with_context(|| "Failed to serialize triage list")?;
+        fs::write(path, data).with_context(|| format!("Failed to write triage list at {:?}", path))?;
+        Ok(())
+    }
+
+    /// Объединяет отметки из файла с текущими (записи из файла имеют приоритет).
+    /// Возвращает количество импортированных записей.
+    pub fn merge_from_file(&mut self, path: &Path) -> Result<usize> {
+        let data = fs::read_to_string(path).with_context(|| format!("Failed to read triage list at {:?}", path))?;
+        if data.trim().is_empty() {
+            return Ok(0);
+        }
+        let list: Vec<TriageEntry> = serde_json::from_str(&data).with_context(|| "Failed to parse triage list JSON")?;
+        let count = list.len();
+        for entry in list {
+            self.entries.insert(entry.signature.clone(), entry);
+        }
+        Ok(count)
+    }
+
+    pub fn import_from_file(&mut self, path: &Path) -> Result<usize> {
+        let count = self.merge_from_file(path)?;
+        self.save_to_disk()?;
+        Ok(count)
+    }
+}
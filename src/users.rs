@@ -0,0 +1,310 @@
+use serde::{Deserialize, Serialize};
+use std::process::Command;
+use anyhow::{Result, anyhow};
+use crate::rollback::shell_quote;
+
+/// Сопоставление Linux-логина (или `%группы`) пользователю SELinux.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LoginMapping {
+    pub login: String,
+    pub seuser: String,
+    /// MLS/MCS диапазон, пустой — политика без MLS
+    #[serde(default)]
+    pub range: String,
+    /// Локальная настройка (`semanage login -l -C`)
+    #[serde(default)]
+    pub local: bool,
+}
+
+/// Пользователь SELinux и его роли.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SELinuxUser {
+    pub name: String,
+    pub roles: Vec<String>,
+    #[serde(default)]
+    pub range: String,
+    #[serde(default)]
+    pub local: bool,
+}
+
+/// Запись вкладки пользователей: сопоставление логина или пользователь SELinux
+#[derive(Debug, Clone)]
+pub enum UserEntry {
+    Login(LoginMapping),
+    User(SELinuxUser),
+}
+
+impl LoginMapping {
+    /// Строка для снимка состояния: "login:seuser:range"
+    pub fn state_line(&self) -> String {
+        format!("{}:{}:{}", self.login, self.seuser, self.range)
+    }
+
+    pub fn from_state_line(s: &str) -> Option<Self> {
+        let mut it = s.splitn(3, ':');
+        Some(Self {
+            login: it.next()?.to_string(),
+            seuser: it.next()?.to_string(),
+            range: it.next()?.to_string(),
+            local: true,
+        })
+    }
+
+    /// Команда, задающая это сопоставление (`-a` или `-m`), — для отката
+    pub fn semanage_command(&self, action: &str) -> String {
+        let range = if self.range.is_empty() { String::new() } else { format!(" -r {}", shell_quote(&self.range)) };
+        format!("semanage login {} -s {}{} {}", action, shell_quote(&self.seuser), range, shell_quote(&self.login))
+    }
+}
+
+impl SELinuxUser {
+    /// Строка для снимка состояния: "name:role role:range"
+    pub fn state_line(&self) -> String {
+        format!("{}:{}:{}", self.name, self.roles.join(" "), self.range)
+    }
+
+    pub fn from_state_line(s: &str) -> Option<Self> {
+        let mut it = s.splitn(3, ':');
+        Some(Self {
+            name: it.next()?.to_string(),
+            roles: it.next()?.split_whitespace().map(String::from).collect(),
+            range: it.next()?.to_string(),
+            local: true,
+        })
+    }
+
+    pub fn semanage_command(&self, action: &str) -> String {
+        let range = if self.range.is_empty() { String::new() } else { format!(" -r {}", shell_quote(&self.range)) };
+        format!("semanage user {} -R {}{} {}", action, shell_quote(&self.roles.join(" ")), range, shell_quote(&self.name))
+    }
+}
+
+#[derive(Clone)]
+pub struct UserManager {
+    pub logins: Vec<LoginMapping>,
+    pub users: Vec<SELinuxUser>,
+}
+
+impl UserManager {
+    pub fn new() -> Self {
+        Self {
+            logins: Vec::new(),
+            users: Vec::new(),
+        }
+    }
+
+    pub fn load(&mut self) -> Result<()> {
+        let logins = Self::semanage_list("login", false)?;
+        let local_logins = Self::semanage_list("login", true)?;
+        self.logins = Self::parse_login_list(&logins);
+        for l in Self::parse_login_list(&local_logins) {
+            if let Some(existing) = self.logins.iter_mut().find(|e| e.login == l.login) {
+                existing.local = true;
+            }
+        }
+
+        let users = Self::semanage_list("user", false)?;
+        let local_users = Self::semanage_list("user", true)?;
+        self.users = Self::parse_user_list(&users);
+        for u in Self::parse_user_list(&local_users) {
+            if let Some(existing) = self.users.iter_mut().find(|e| e.name == u.name) {
+                existing.local = true;
+            }
+        }
+        Ok(())
+    }
+
+    fn semanage_list(object: &str, local_only: bool) -> Result<String> {
+        let mut cmd = Command::new("semanage");
+        cmd.args([object, "-l", "-n"]);
+        if local_only {
+            cmd.arg("-C");
+        }
+        Ok(String::from_utf8_lossy(&cmd.output()?.stdout).to_string())
+    }
+
+    /// Разбирает `semanage login -l -n`: логин, пользователь SELinux,
+    /// диапазон (только при MLS) и служба
+    pub fn parse_login_list(output: &str) -> Vec<LoginMapping> {
+        output
+            .lines()
+            .filter_map(|line| {
+                let fields: Vec<&str> = line.split_whitespace().collect();
+                let (login, seuser) = (fields.first()?, fields.get(1)?);
+                let range = fields.get(2).filter(|r| is_mls_range(r)).copied().unwrap_or("");
+                Some(LoginMapping {
+                    login: login.to_string(),
+                    seuser: seuser.to_string(),
+                    range: range.to_string(),
+                    local: false,
+                })
+            })
+            .collect()
+    }
+
+    /// Разбирает `semanage user -l -n`: имя, префикс меток, при MLS — уровень
+    /// и диапазон, затем роли (`staff_u user s0 s0-s0:c0.c1023 staff_r sysadm_r`)
+    pub fn parse_user_list(output: &str) -> Vec<SELinuxUser> {
+        output
+            .lines()
+            .filter_map(|line| {
+                let fields: Vec<&str> = line.split_whitespace().collect();
+                if fields.len() < 3 {
+                    return None;
+                }
+                let (range, roles) = if fields.len() >= 5 && is_mls_range(fields[2]) && is_mls_range(fields[3]) {
+                    (fields[3], &fields[4..])
+                } else {
+                    ("", &fields[2..])
+                };
+                Some(SELinuxUser {
+                    name: fields[0].to_string(),
+                    roles: roles.iter().map(|r| r.to_string()).collect(),
+                    range: range.to_string(),
+                    local: false,
+                })
+            })
+            .collect()
+    }
+
+    pub fn find_login(&self, login: &str) -> Option<&LoginMapping> {
+        self.logins.iter().find(|l| l.login == login)
+    }
+
+    pub fn find_user(&self, name: &str) -> Option<&SELinuxUser> {
+        self.users.iter().find(|u| u.name == name)
+    }
+
+    fn run_semanage(args: &[&str]) -> Result<()> {
+        let output = Command::new("semanage").args(args).output()?;
+        if !output.status.success() {
+            return Err(anyhow!("semanage {} failed: {}", args[0], String::from_utf8_lossy(&output.stderr).trim()));
+        }
+        Ok(())
+    }
+
+    /// `semanage login -a` для нового логина, `-m` для существующего
+    pub fn set_login(&mut self, login: &str, seuser: &str, range: &str, simulation: bool) -> Result<()> {
+        if simulation {
+            if self.find_user(seuser).is_none() {
+                return Err(anyhow!("SELinux user {} does not exist", seuser));
+            }
+            let mapping = LoginMapping {
+                login: login.to_string(),
+                seuser: seuser.to_string(),
+                range: range.to_string(),
+                local: true,
+            };
+            match self.logins.iter_mut().find(|l| l.login == login) {
+                Some(existing) => *existing = mapping,
+                None => self.logins.push(mapping),
+            }
+            return Ok(());
+        }
+
+        let action = if self.find_login(login).is_some() { "-m" } else { "-a" };
+        let mut args = vec!["login", action, "-s", seuser];
+        if !range.is_empty() {
+            args.extend(["-r", range]);
+        }
+        args.push(login);
+        Self::run_semanage(&args)?;
+
+        self.load()?;
+        Ok(())
+    }
+
+    pub fn remove_login(&mut self, login: &str, simulation: bool) -> Result<()> {
+        if simulation {
+            self.logins.retain(|l| l.login != login);
+            return Ok(());
+        }
+
+        Self::run_semanage(&["login", "-d", login])?;
+
+        self.load()?;
+        Ok(())
+    }
+
+    /// `semanage user -a` для нового пользователя, `-m` для существующего
+    pub fn set_user(&mut self, name: &str, roles: &[String], range: &str, simulation: bool) -> Result<()> {
+        if roles.is_empty() {
+            return Err(anyhow!("SELinux user {} needs at least one role", name));
+        }
+        if simulation {
+            let user = SELinuxUser {
+                name: name.to_string(),
+                roles: roles.to_vec(),
+                range: range.to_string(),
+                local: true,
+            };
+            match self.users.iter_mut().find(|u| u.name == name) {
+                Some(existing) => *existing = user,
+                None => self.users.push(user),
+            }
+            return Ok(());
+        }
+
+        let action = if self.find_user(name).is_some() { "-m" } else { "-a" };
+        let roles = roles.join(" ");
+        let mut args = vec!["user", action, "-R", roles.as_str()];
+        if !range.is_empty() {
+            args.extend(["-r", range]);
+        }
+        args.push(name);
+        Self::run_semanage(&args)?;
+
+        self.load()?;
+        Ok(())
+    }
+
+    pub fn remove_user(&mut self, name: &str, simulation: bool) -> Result<()> {
+        if let Some(l) = self.logins.iter().find(|l| l.seuser == name) {
+            return Err(anyhow!("SELinux user {} is still mapped to login {}", name, l.login));
+        }
+        if simulation {
+            self.users.retain(|u| u.name != name);
+            return Ok(());
+        }
+
+        Self::run_semanage(&["user", "-d", name])?;
+
+        self.load()?;
+        Ok(())
+    }
+
+    pub fn load_simulation_data(&mut self) {
+        self.users = Self::parse_user_list(
+            "guest_u         user       s0         s0                             guest_r\n\
+             root            user       s0         s0-s0:c0.c1023                 staff_r sysadm_r system_r unconfined_r\n\
+             staff_u         user       s0         s0-s0:c0.c1023                 staff_r sysadm_r system_r unconfined_r\n\
+             sysadm_u        user       s0         s0-s0:c0.c1023                 sysadm_r\n\
+             system_u        user       s0         s0-s0:c0.c1023                 system_r unconfined_r\n\
+             unconfined_u    user       s0         s0-s0:c0.c1023                 system_r unconfined_r\n\
+             user_u          user       s0         s0                             user_r\n\
+             xguest_u        user       s0         s0                             xguest_r\n",
+        );
+        self.logins = Self::parse_login_list(
+            "__default__          unconfined_u         s0-s0:c0.c1023       *\n\
+             root                 unconfined_u         s0-s0:c0.c1023       *\n\
+             %wheel               staff_u              s0-s0:c0.c1023       *\n",
+        );
+        if let Some(l) = self.logins.iter_mut().find(|l| l.login == "%wheel") {
+            l.local = true;
+        }
+    }
+
+    /// Локальные сопоставления логинов (для экспорта и снимков состояния)
+    pub fn local_logins(&self) -> impl Iterator<Item = &LoginMapping> {
+        self.logins.iter().filter(|l| l.local)
+    }
+
+    pub fn local_users(&self) -> impl Iterator<Item = &SELinuxUser> {
+        self.users.iter().filter(|u| u.local)
+    }
+}
+
+/// `s0`, `s0-s0:c0.c1023`, `SystemLow-SystemHigh`
+fn is_mls_range(s: &str) -> bool {
+    (s.starts_with('s') && s[1..].starts_with(|c: char| c.is_ascii_digit())) || s.starts_with("System")
+}